@@ -1,6 +1,12 @@
 // Copyright 2022 the Peniko Authors
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::{ColorSpace, PremulColor, PremulDynamicColor};
+
 /// Defines the color mixing function for a [blend operation](BlendMode).
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -109,6 +115,206 @@ impl BlendMode {
     pub fn new(mix: Mix, compose: Compose) -> Self {
         Self { mix, compose }
     }
+
+    /// Blends the source color onto the backdrop color.
+    ///
+    /// This is a reference implementation of the formulas in the W3C
+    /// [Compositing and Blending Level 1][spec] specification, intended for
+    /// testing renderers and for CPU fallback rendering. Both colors are
+    /// converted to sRGB, and the result is in sRGB.
+    ///
+    /// The mixing function is applied to the unpremultiplied colors and the
+    /// mixed source is then composited with the Porter-Duff operator. The
+    /// result of [`Compose::Plus`] is not clamped, while
+    /// [`Compose::PlusLighter`] clamps both the color and alpha to `1.0`.
+    ///
+    /// [spec]: https://www.w3.org/TR/compositing-1/
+    #[must_use]
+    pub fn apply(
+        self,
+        src: PremulDynamicColor,
+        backdrop: PremulDynamicColor,
+    ) -> PremulDynamicColor {
+        let src = src.convert(ColorSpace::Srgb);
+        let backdrop = backdrop.convert(ColorSpace::Srgb);
+        let alpha_s = f64::from(src.alpha);
+        let alpha_b = f64::from(backdrop.alpha);
+        let cs = unpremultiply(src.components.map(f64::from), alpha_s);
+        let cb = unpremultiply(backdrop.components.map(f64::from), alpha_b);
+        // Cs' = (1 - αb) * Cs + αb * B(Cb, Cs)
+        let mixed = self.mix.apply(cs, cb);
+        let cs = [0, 1, 2].map(|i| (1.0 - alpha_b) * cs[i] + alpha_b * mixed[i]);
+        let (components, alpha) = self.compose.apply((cs, alpha_s), (cb, alpha_b));
+        PremulDynamicColor::new(ColorSpace::Srgb, components.map(|c| c as f32), alpha as f32)
+    }
+
+    /// Blends the source color onto the backdrop color with 8-bit colors.
+    ///
+    /// This is the same as [`apply`](Self::apply), with the result saturated
+    /// and rounded to 8 bits.
+    #[must_use]
+    pub fn apply_rgba8(self, src: PremulColor, backdrop: PremulColor) -> PremulColor {
+        self.apply(src.into(), backdrop.into()).to_premul_color()
+    }
+}
+
+impl Mix {
+    /// Applies the mixing function to unpremultiplied source and backdrop
+    /// colors.
+    fn apply(self, cs: [f64; 3], cb: [f64; 3]) -> [f64; 3] {
+        match self {
+            Self::Normal | Self::Clip => cs,
+            Self::Hue => set_lum(set_sat(cs, sat(cb)), lum(cb)),
+            Self::Saturation => set_lum(set_sat(cb, sat(cs)), lum(cb)),
+            Self::Color => set_lum(cs, lum(cb)),
+            Self::Luminosity => set_lum(cb, lum(cs)),
+            _ => [0, 1, 2].map(|i| self.apply_separable(cs[i], cb[i])),
+        }
+    }
+
+    /// Applies a separable mixing function to a single channel.
+    fn apply_separable(self, cs: f64, cb: f64) -> f64 {
+        match self {
+            Self::Multiply => cb * cs,
+            Self::Screen => screen(cs, cb),
+            Self::Overlay => hard_light(cb, cs),
+            Self::Darken => cb.min(cs),
+            Self::Lighten => cb.max(cs),
+            Self::ColorDodge => {
+                if cb == 0.0 {
+                    0.0
+                } else if cs >= 1.0 {
+                    1.0
+                } else {
+                    (cb / (1.0 - cs)).min(1.0)
+                }
+            }
+            Self::ColorBurn => {
+                if cb >= 1.0 {
+                    1.0
+                } else if cs <= 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - cb) / cs).min(1.0)
+                }
+            }
+            Self::HardLight => hard_light(cs, cb),
+            Self::SoftLight => {
+                if cs <= 0.5 {
+                    cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+                } else {
+                    let d = if cb <= 0.25 {
+                        ((16.0 * cb - 12.0) * cb + 4.0) * cb
+                    } else {
+                        cb.sqrt()
+                    };
+                    cb + (2.0 * cs - 1.0) * (d - cb)
+                }
+            }
+            Self::Difference => (cb - cs).abs(),
+            Self::Exclusion => cb + cs - 2.0 * cb * cs,
+            Self::Normal
+            | Self::Clip
+            | Self::Hue
+            | Self::Saturation
+            | Self::Color
+            | Self::Luminosity => cs,
+        }
+    }
+}
+
+impl Compose {
+    /// Composites the unpremultiplied source and backdrop colors with their
+    /// alpha values, returning a premultiplied color.
+    fn apply(
+        self,
+        (cs, alpha_s): ([f64; 3], f64),
+        (cb, alpha_b): ([f64; 3], f64),
+    ) -> ([f64; 3], f64) {
+        let (fa, fb) = match self {
+            Self::Clear => (0.0, 0.0),
+            Self::Copy => (1.0, 0.0),
+            Self::Dest => (0.0, 1.0),
+            Self::SrcOver => (1.0, 1.0 - alpha_s),
+            Self::DestOver => (1.0 - alpha_b, 1.0),
+            Self::SrcIn => (alpha_b, 0.0),
+            Self::DestIn => (0.0, alpha_s),
+            Self::SrcOut => (1.0 - alpha_b, 0.0),
+            Self::DestOut => (0.0, 1.0 - alpha_s),
+            Self::SrcAtop => (alpha_b, 1.0 - alpha_s),
+            Self::DestAtop => (1.0 - alpha_b, alpha_s),
+            Self::Xor => (1.0 - alpha_b, 1.0 - alpha_s),
+            Self::Plus | Self::PlusLighter => (1.0, 1.0),
+        };
+        let (ws, wb) = (alpha_s * fa, alpha_b * fb);
+        let components = [0, 1, 2].map(|i| ws * cs[i] + wb * cb[i]);
+        let alpha = ws + wb;
+        if self == Self::PlusLighter {
+            (components.map(|c| c.min(1.0)), alpha.min(1.0))
+        } else {
+            (components, alpha)
+        }
+    }
+}
+
+fn unpremultiply(c: [f64; 3], alpha: f64) -> [f64; 3] {
+    if alpha == 0.0 {
+        [0.0; 3]
+    } else {
+        c.map(|c| c / alpha)
+    }
+}
+
+fn screen(cs: f64, cb: f64) -> f64 {
+    cb + cs - cb * cs
+}
+
+fn hard_light(cs: f64, cb: f64) -> f64 {
+    if cs <= 0.5 {
+        cb * 2.0 * cs
+    } else {
+        screen(2.0 * cs - 1.0, cb)
+    }
+}
+
+fn lum([r, g, b]: [f64; 3]) -> f64 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn clip_color(c: [f64; 3]) -> [f64; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    let mut c = c;
+    if n < 0.0 {
+        c = c.map(|c| l + (c - l) * l / (l - n));
+    }
+    if x > 1.0 {
+        c = c.map(|c| l + (c - l) * (1.0 - l) / (x - l));
+    }
+    c
+}
+
+fn set_lum(c: [f64; 3], l: f64) -> [f64; 3] {
+    let d = l - lum(c);
+    clip_color(c.map(|c| c + d))
+}
+
+fn sat(c: [f64; 3]) -> f64 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn set_sat(c: [f64; 3], s: f64) -> [f64; 3] {
+    // Indices of the minimum, middle and maximum channels.
+    let mut order = [0, 1, 2];
+    order.sort_unstable_by(|&a, &b| c[a].total_cmp(&c[b]));
+    let [min, mid, max] = order;
+    let mut result = [0.0; 3];
+    if c[max] > c[min] {
+        result[mid] = (c[mid] - c[min]) * s / (c[max] - c[min]);
+        result[max] = s;
+    }
+    result
 }
 
 impl Default for BlendMode {
@@ -137,3 +343,137 @@ impl From<Compose> for BlendMode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BlendMode, Compose, Mix};
+    use crate::{ColorSpace, PremulColor, PremulDynamicColor};
+
+    /// A translucent source and backdrop, as premultiplied colors.
+    const SRC: PremulColor = PremulColor::rgba8(204, 51, 102, 204);
+    const BACKDROP: PremulColor = PremulColor::rgba8(30, 90, 150, 150);
+
+    /// An opaque source and backdrop, for which the result is the mixed color.
+    const OPAQUE_SRC: PremulColor = PremulColor::rgba8(230, 100, 40, 255);
+    const OPAQUE_BACKDROP: PremulColor = PremulColor::rgba8(60, 170, 220, 255);
+
+    /// The expected results of each mixing function composited with
+    /// [`Compose::SrcOver`], for the translucent and opaque pairs.
+    const MIX: [(Mix, [u8; 4], [u8; 4]); 17] = [
+        (Mix::Normal, [210, 69, 132, 234], [230, 100, 40, 255]),
+        (Mix::Multiply, [114, 57, 132, 234], [54, 67, 35, 255]),
+        (Mix::Screen, [210, 123, 192, 234], [236, 203, 225, 255]),
+        (Mix::Overlay, [138, 87, 192, 234], [108, 152, 196, 255]),
+        (Mix::Darken, [114, 69, 132, 234], [60, 100, 40, 255]),
+        (Mix::Lighten, [210, 111, 192, 234], [230, 170, 220, 255]),
+        (Mix::ColorDodge, [210, 135, 192, 234], [255, 255, 255, 255]),
+        (Mix::ColorBurn, [114, 39, 192, 234], [39, 38, 32, 255]),
+        (Mix::HardLight, [210, 75, 192, 234], [217, 133, 69, 255]),
+        (Mix::SoftLight, [144, 97, 192, 234], [111, 158, 199, 255]),
+        (Mix::Difference, [186, 81, 132, 234], [170, 70, 180, 255]),
+        (Mix::Exclusion, [186, 105, 132, 234], [182, 137, 191, 255]),
+        (Mix::Hue, [210, 73, 135, 234], [225, 115, 65, 255]),
+        (Mix::Saturation, [116, 110, 188, 234], [45, 175, 235, 255]),
+        (Mix::Color, [210, 73, 135, 234], [240, 110, 50, 255]),
+        (Mix::Luminosity, [111, 108, 189, 234], [50, 160, 210, 255]),
+        (Mix::Clip, [210, 69, 132, 234], [230, 100, 40, 255]),
+    ];
+
+    /// The expected results of each composition function with
+    /// [`Mix::Normal`], for the translucent and opaque pairs.
+    const COMPOSE: [(Compose, [u8; 4], [u8; 4]); 14] = [
+        (Compose::Clear, [0, 0, 0, 0], [0, 0, 0, 0]),
+        (Compose::Copy, [204, 51, 102, 204], [230, 100, 40, 255]),
+        (Compose::Dest, [30, 90, 150, 150], [60, 170, 220, 255]),
+        (Compose::SrcOver, [210, 69, 132, 234], [230, 100, 40, 255]),
+        (Compose::DestOver, [114, 111, 192, 234], [60, 170, 220, 255]),
+        (Compose::SrcIn, [120, 30, 60, 120], [230, 100, 40, 255]),
+        (Compose::DestIn, [24, 72, 120, 120], [60, 170, 220, 255]),
+        (Compose::SrcOut, [84, 21, 42, 84], [0, 0, 0, 0]),
+        (Compose::DestOut, [6, 18, 30, 30], [0, 0, 0, 0]),
+        (Compose::SrcAtop, [126, 48, 90, 150], [230, 100, 40, 255]),
+        (Compose::DestAtop, [108, 93, 162, 204], [60, 170, 220, 255]),
+        (Compose::Xor, [90, 39, 72, 114], [0, 0, 0, 0]),
+        (Compose::Plus, [234, 141, 252, 255], [255, 255, 255, 255]),
+        (
+            Compose::PlusLighter,
+            [234, 141, 252, 255],
+            [255, 255, 255, 255],
+        ),
+    ];
+
+    fn rgba8([r, g, b, a]: [u8; 4]) -> PremulColor {
+        PremulColor::rgba8(r, g, b, a)
+    }
+
+    #[test]
+    fn mix_golden() {
+        for (mix, translucent, opaque) in MIX {
+            let mode = BlendMode::new(mix, Compose::SrcOver);
+            assert_eq!(
+                mode.apply_rgba8(SRC, BACKDROP),
+                rgba8(translucent),
+                "{mix:?} with translucent colors"
+            );
+            assert_eq!(
+                mode.apply_rgba8(OPAQUE_SRC, OPAQUE_BACKDROP),
+                rgba8(opaque),
+                "{mix:?} with opaque colors"
+            );
+        }
+    }
+
+    #[test]
+    fn compose_golden() {
+        for (compose, translucent, opaque) in COMPOSE {
+            let mode = BlendMode::new(Mix::Normal, compose);
+            assert_eq!(
+                mode.apply_rgba8(SRC, BACKDROP),
+                rgba8(translucent),
+                "{compose:?} with translucent colors"
+            );
+            assert_eq!(
+                mode.apply_rgba8(OPAQUE_SRC, OPAQUE_BACKDROP),
+                rgba8(opaque),
+                "{compose:?} with opaque colors"
+            );
+        }
+    }
+
+    #[test]
+    fn plus_clamping() {
+        let src = PremulColor::rgba8(200, 200, 200, 255).into();
+        let backdrop = PremulColor::rgba8(100, 100, 100, 255).into();
+        let plus = BlendMode::new(Mix::Normal, Compose::Plus).apply(src, backdrop);
+        let expected = 300.0 / 255.0;
+        assert!(
+            plus.components.iter().all(|c| (c - expected).abs() < 1e-6),
+            "plus should not clamp components, got {:?}",
+            plus.components
+        );
+        assert!(
+            (plus.alpha - 2.0).abs() < 1e-6,
+            "plus should not clamp alpha"
+        );
+        let lighter = BlendMode::new(Mix::Normal, Compose::PlusLighter).apply(src, backdrop);
+        assert_eq!(
+            lighter,
+            PremulDynamicColor::new(ColorSpace::Srgb, [1.0; 3], 1.0),
+            "plus-lighter clamps to 1.0"
+        );
+    }
+
+    #[test]
+    fn plus_unsaturated() {
+        // Below saturation, both additive operators are the same.
+        let src = PremulColor::rgba8(100, 50, 0, 128);
+        let backdrop = PremulColor::rgba8(60, 60, 60, 100);
+        for compose in [Compose::Plus, Compose::PlusLighter] {
+            assert_eq!(
+                BlendMode::new(Mix::Normal, compose).apply_rgba8(src, backdrop),
+                PremulColor::rgba8(160, 110, 60, 228),
+                "{compose:?}"
+            );
+        }
+    }
+}
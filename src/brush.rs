@@ -1,7 +1,15 @@
 // Copyright 2022 the Peniko Authors
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
-use super::{Color, Gradient, Image};
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::{
+    Color, DynamicColor, Gradient, Image, MeshGradient, Pattern, PremulColor, PremulDynamicColor,
+};
+
+use kurbo::Affine;
 
 /// Describes the color content of a filled or stroked shape.
 ///
@@ -11,10 +19,17 @@ use super::{Color, Gradient, Image};
 pub enum Brush {
     /// Solid color brush.
     Solid(Color),
+    /// Solid color brush with a floating point color, which may be outside
+    /// of the sRGB gamut or have more than 8 bits of precision.
+    DynamicSolid(DynamicColor),
     /// Gradient brush.
     Gradient(Gradient),
     /// Image brush.
     Image(Image),
+    /// Pattern brush.
+    Pattern(Pattern),
+    /// Mesh gradient brush.
+    Mesh(MeshGradient),
 }
 
 impl From<Color> for Brush {
@@ -23,6 +38,24 @@ impl From<Color> for Brush {
     }
 }
 
+impl From<DynamicColor> for Brush {
+    fn from(c: DynamicColor) -> Self {
+        Self::DynamicSolid(c)
+    }
+}
+
+impl From<PremulColor> for Brush {
+    fn from(c: PremulColor) -> Self {
+        Self::DynamicSolid(c.into())
+    }
+}
+
+impl From<PremulDynamicColor> for Brush {
+    fn from(c: PremulDynamicColor) -> Self {
+        Self::DynamicSolid(c.into())
+    }
+}
+
 impl From<Gradient> for Brush {
     fn from(g: Gradient) -> Self {
         Self::Gradient(g)
@@ -35,6 +68,18 @@ impl From<Image> for Brush {
     }
 }
 
+impl From<Pattern> for Brush {
+    fn from(value: Pattern) -> Self {
+        Self::Pattern(value)
+    }
+}
+
+impl From<MeshGradient> for Brush {
+    fn from(value: MeshGradient) -> Self {
+        Self::Mesh(value)
+    }
+}
+
 impl Default for Brush {
     fn default() -> Self {
         Self::Solid(Color::default())
@@ -42,6 +87,12 @@ impl Default for Brush {
 }
 
 impl Brush {
+    /// Attaches a transform from brush space to the space of the shape.
+    #[must_use]
+    pub fn with_transform(self, transform: Affine) -> TransformedBrush {
+        TransformedBrush::new(self, Some(transform))
+    }
+
     /// Returns the brush with the alpha component multiplied by the specified
     /// factor.
     #[must_use]
@@ -51,6 +102,7 @@ impl Brush {
         } else {
             match self {
                 Self::Solid(color) => color.with_alpha_factor(alpha).into(),
+                Self::DynamicSolid(color) => color.with_alpha_factor(alpha).into(),
                 Self::Gradient(mut gradient) => {
                     gradient
                         .stops
@@ -59,6 +111,8 @@ impl Brush {
                     gradient.into()
                 }
                 Self::Image(image) => image.with_alpha_factor(alpha).into(),
+                Self::Pattern(pattern) => pattern.with_alpha_factor(alpha).into(),
+                Self::Mesh(mesh) => mesh.with_alpha_factor(alpha).into(),
             }
         }
     }
@@ -73,20 +127,35 @@ impl Brush {
 pub enum BrushRef<'a> {
     /// Solid color brush.
     Solid(Color),
+    /// Solid color brush with a floating point color.
+    DynamicSolid(DynamicColor),
     /// Gradient brush.
     Gradient(&'a Gradient),
     /// Image brush.
     Image(&'a Image),
+    /// Pattern brush.
+    Pattern(&'a Pattern),
+    /// Mesh gradient brush.
+    Mesh(&'a MeshGradient),
 }
 
-impl BrushRef<'_> {
+impl<'a> BrushRef<'a> {
+    /// Attaches a transform from brush space to the space of the shape.
+    #[must_use]
+    pub fn with_transform(self, transform: Affine) -> TransformedBrushRef<'a> {
+        TransformedBrushRef::new(self, Some(transform))
+    }
+
     /// Converts the reference to an owned brush.
     #[must_use]
     pub fn to_owned(&self) -> Brush {
         match self {
             Self::Solid(color) => Brush::Solid(*color),
+            Self::DynamicSolid(color) => Brush::DynamicSolid(*color),
             Self::Gradient(gradient) => Brush::Gradient((*gradient).clone()),
             Self::Image(image) => Brush::Image((*image).clone()),
+            Self::Pattern(pattern) => Brush::Pattern((*pattern).clone()),
+            Self::Mesh(mesh) => Brush::Mesh((*mesh).clone()),
         }
     }
 }
@@ -103,6 +172,30 @@ impl<'a> From<&'a Color> for BrushRef<'_> {
     }
 }
 
+impl From<DynamicColor> for BrushRef<'_> {
+    fn from(color: DynamicColor) -> Self {
+        Self::DynamicSolid(color)
+    }
+}
+
+impl<'a> From<&'a DynamicColor> for BrushRef<'_> {
+    fn from(color: &'a DynamicColor) -> Self {
+        Self::DynamicSolid(*color)
+    }
+}
+
+impl From<PremulColor> for BrushRef<'_> {
+    fn from(color: PremulColor) -> Self {
+        Self::DynamicSolid(color.into())
+    }
+}
+
+impl From<PremulDynamicColor> for BrushRef<'_> {
+    fn from(color: PremulDynamicColor) -> Self {
+        Self::DynamicSolid(color.into())
+    }
+}
+
 impl<'a> From<&'a Gradient> for BrushRef<'a> {
     fn from(gradient: &'a Gradient) -> Self {
         Self::Gradient(gradient)
@@ -115,18 +208,231 @@ impl<'a> From<&'a Image> for BrushRef<'a> {
     }
 }
 
+impl<'a> From<&'a Pattern> for BrushRef<'a> {
+    fn from(pattern: &'a Pattern) -> Self {
+        Self::Pattern(pattern)
+    }
+}
+
+impl<'a> From<&'a MeshGradient> for BrushRef<'a> {
+    fn from(mesh: &'a MeshGradient) -> Self {
+        Self::Mesh(mesh)
+    }
+}
+
 impl<'a> From<&'a Brush> for BrushRef<'a> {
     fn from(brush: &'a Brush) -> Self {
         match brush {
             Brush::Solid(color) => Self::Solid(*color),
+            Brush::DynamicSolid(color) => Self::DynamicSolid(*color),
             Brush::Gradient(gradient) => Self::Gradient(gradient),
             Brush::Image(image) => Self::Image(image),
+            Brush::Pattern(pattern) => Self::Pattern(pattern),
+            Brush::Mesh(mesh) => Self::Mesh(mesh),
+        }
+    }
+}
+
+/// A [brush](Brush) with a transform from brush space to the space of the
+/// shape that it fills.
+///
+/// Gradient geometry and image pixels are defined in brush space, with pixel
+/// `(x, y)` of an image covering the unit square at `(x, y)`. The transform
+/// maps them to the space of the shape, independently of any transform
+/// applied to the shape itself. A transform of `None` is the identity.
+///
+/// See also [`TransformedBrushRef`] which can be used to avoid allocations.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransformedBrush {
+    /// The brush.
+    pub brush: Brush,
+    /// Transform from brush space to the space of the shape.
+    pub transform: Option<Affine>,
+}
+
+impl TransformedBrush {
+    /// Creates a new brush with the given transform.
+    pub fn new(brush: impl Into<Brush>, transform: Option<Affine>) -> Self {
+        Self {
+            brush: brush.into(),
+            transform,
+        }
+    }
+
+    /// Builder method for setting the transform from brush space to the
+    /// space of the shape.
+    #[must_use]
+    pub fn with_transform(mut self, transform: Affine) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Applies `transform` after the current transform, for example to move
+    /// the brush together with the shape.
+    #[must_use]
+    pub fn then_transform(mut self, transform: Affine) -> Self {
+        self.transform = Some(compose(transform, self.transform));
+        self
+    }
+
+    /// Applies `transform` before the current transform, which changes the
+    /// brush space.
+    #[must_use]
+    pub fn pre_transform(mut self, transform: Affine) -> Self {
+        self.transform = Some(self.transform_or_identity() * transform);
+        self
+    }
+
+    /// Returns the transform from brush space to the space of the shape.
+    #[must_use]
+    pub fn transform_or_identity(&self) -> Affine {
+        self.transform.unwrap_or(Affine::IDENTITY)
+    }
+
+    /// Returns the transform from the space of the shape to brush space, or
+    /// `None` if the transform is not invertible.
+    ///
+    /// This is the mapping used to sample the brush: the color at point `p`
+    /// of the shape is the color of the brush at `inverse * p`.
+    #[must_use]
+    pub fn inverse_transform(&self) -> Option<Affine> {
+        invert(self.transform_or_identity())
+    }
+
+    /// Applies the transform to the geometry of the brush where this can be
+    /// done exactly, and returns the brush with the remaining transform.
+    ///
+    /// Solid colors ignore the transform, gradients are
+    /// [transformed](Gradient::transformed) when possible, mesh gradients are
+    /// [transformed](MeshGradient::transformed) and the transform of a
+    /// pattern is combined with the brush transform. Images, and
+    /// gradients that cannot be transformed exactly, keep the transform.
+    #[must_use]
+    pub fn apply_transform(self) -> Self {
+        let Some(transform) = self.transform else {
+            return self;
+        };
+        match self.brush {
+            Brush::Solid(color) => Self::new(color, None),
+            Brush::DynamicSolid(color) => Self::new(color, None),
+            Brush::Gradient(gradient) => match gradient.transformed(transform) {
+                Some(gradient) => Self::new(gradient, None),
+                None => Self::new(gradient, Some(transform)),
+            },
+            Brush::Pattern(mut pattern) => {
+                pattern.transform = transform * pattern.transform;
+                Self::new(pattern, None)
+            }
+            Brush::Mesh(mesh) => Self::new(mesh.transformed(transform), None),
+            Brush::Image(image) => Self::new(image, Some(transform)),
         }
     }
 }
 
+impl From<Brush> for TransformedBrush {
+    fn from(brush: Brush) -> Self {
+        Self::new(brush, None)
+    }
+}
+
+/// Reference to a [transformed brush](TransformedBrush).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TransformedBrushRef<'a> {
+    /// The brush.
+    pub brush: BrushRef<'a>,
+    /// Transform from brush space to the space of the shape.
+    pub transform: Option<Affine>,
+}
+
+impl<'a> TransformedBrushRef<'a> {
+    /// Creates a new brush reference with the given transform.
+    pub fn new(brush: impl Into<BrushRef<'a>>, transform: Option<Affine>) -> Self {
+        Self {
+            brush: brush.into(),
+            transform,
+        }
+    }
+
+    /// Builder method for setting the transform from brush space to the
+    /// space of the shape.
+    #[must_use]
+    pub fn with_transform(mut self, transform: Affine) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Applies `transform` after the current transform, for example to move
+    /// the brush together with the shape.
+    #[must_use]
+    pub fn then_transform(mut self, transform: Affine) -> Self {
+        self.transform = Some(compose(transform, self.transform));
+        self
+    }
+
+    /// Applies `transform` before the current transform, which changes the
+    /// brush space.
+    #[must_use]
+    pub fn pre_transform(mut self, transform: Affine) -> Self {
+        self.transform = Some(self.transform_or_identity() * transform);
+        self
+    }
+
+    /// Returns the transform from brush space to the space of the shape.
+    #[must_use]
+    pub fn transform_or_identity(&self) -> Affine {
+        self.transform.unwrap_or(Affine::IDENTITY)
+    }
+
+    /// Returns the transform from the space of the shape to brush space, or
+    /// `None` if the transform is not invertible.
+    #[must_use]
+    pub fn inverse_transform(&self) -> Option<Affine> {
+        invert(self.transform_or_identity())
+    }
+
+    /// Converts the reference to an owned brush.
+    #[must_use]
+    pub fn to_owned(&self) -> TransformedBrush {
+        TransformedBrush::new(self.brush.to_owned(), self.transform)
+    }
+}
+
+impl<'a> From<BrushRef<'a>> for TransformedBrushRef<'a> {
+    fn from(brush: BrushRef<'a>) -> Self {
+        Self::new(brush, None)
+    }
+}
+
+impl<'a> From<&'a Brush> for TransformedBrushRef<'a> {
+    fn from(brush: &'a Brush) -> Self {
+        Self::new(brush, None)
+    }
+}
+
+impl<'a> From<&'a TransformedBrush> for TransformedBrushRef<'a> {
+    fn from(brush: &'a TransformedBrush) -> Self {
+        Self::new(&brush.brush, brush.transform)
+    }
+}
+
+fn compose(outer: Affine, inner: Option<Affine>) -> Affine {
+    match inner {
+        Some(inner) => outer * inner,
+        None => outer,
+    }
+}
+
+fn invert(transform: Affine) -> Option<Affine> {
+    let det = transform.determinant();
+    (det != 0.0 && det.is_finite() && transform.is_finite()).then(|| transform.inverse())
+}
+
 /// Defines how a brush is extended when the content does not
 /// fill a shape.
+///
+/// Gradients extend along their parameter, while images and
+/// [patterns](crate::Pattern) extend separately in each direction.
 #[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Extend {
@@ -137,4 +443,169 @@ pub enum Extend {
     Repeat,
     /// Extends the image by reflecting the brush.
     Reflect,
+    /// Leaves the area outside of the brush transparent.
+    ///
+    /// This is also known as clamping to transparent, and corresponds to
+    /// `background-repeat: no-repeat` in CSS.
+    Decal,
+}
+
+impl Extend {
+    /// Maps a parameter onto the range `0.0` to `1.0` according to the extend
+    /// mode.
+    ///
+    /// [`Pad`](Self::Pad) clamps the parameter, [`Repeat`](Self::Repeat)
+    /// takes its fractional part and [`Reflect`](Self::Reflect) mirrors every
+    /// other repetition. [`Decal`](Self::Decal) returns `None` for parameters
+    /// outside of the range, as the brush is transparent there.
+    #[must_use]
+    pub fn apply(self, t: f32) -> Option<f32> {
+        Some(match self {
+            Self::Pad => t.clamp(0.0, 1.0),
+            Self::Repeat => t - t.floor(),
+            Self::Decal if !(0.0..=1.0).contains(&t) => return None,
+            Self::Decal => t,
+            Self::Reflect => {
+                let t = (t * 0.5 - (t * 0.5).floor()) * 2.0;
+                if t > 1.0 {
+                    2.0 - t
+                } else {
+                    t
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Brush, TransformedBrush, TransformedBrushRef};
+    use crate::{Blob, Color, Format, Gradient, Image, Pattern, PatternContent};
+    use kurbo::{Affine, Point, Rect};
+
+    const MOVE: Affine = Affine::new([1.0, 0.0, 0.0, 1.0, 10.0, 0.0]);
+    const SCALE: Affine = Affine::scale(2.0);
+
+    #[track_caller]
+    fn assert_maps(transform: Option<Affine>, from: Point, to: Point, message: &str) {
+        let actual = transform.unwrap() * from;
+        assert!(
+            (actual - to).hypot() < 1e-9,
+            "{message}: {from:?} maps to {actual:?}, expected {to:?}"
+        );
+    }
+
+    #[test]
+    fn compose() {
+        let p = Point::new(1.0, 1.0);
+        let moved = TransformedBrush::new(Color::RED, Some(MOVE));
+        // The brush is first moved, then scaled with the shape.
+        let then = moved.clone().then_transform(SCALE);
+        assert_eq!(then.transform, Some(SCALE * MOVE), "then_transform");
+        assert_maps(then.transform, p, Point::new(22.0, 2.0), "then_transform");
+        // The brush space is scaled, then moved.
+        let pre = moved.clone().pre_transform(SCALE);
+        assert_eq!(pre.transform, Some(MOVE * SCALE), "pre_transform");
+        assert_maps(pre.transform, p, Point::new(12.0, 2.0), "pre_transform");
+        let plain = TransformedBrush::new(Color::RED, None);
+        assert_eq!(
+            plain.clone().then_transform(SCALE).transform,
+            Some(SCALE),
+            "then_transform without a transform"
+        );
+        assert_eq!(
+            plain.pre_transform(SCALE).transform,
+            Some(SCALE),
+            "pre_transform without a transform"
+        );
+        assert_eq!(
+            moved.clone().with_transform(SCALE).transform,
+            Some(SCALE),
+            "with_transform replaces the transform"
+        );
+
+        let brush = Brush::from(Color::RED);
+        let by_ref = TransformedBrushRef::new(&brush, Some(MOVE));
+        assert_eq!(
+            by_ref.then_transform(SCALE).transform,
+            then.transform,
+            "then_transform on a reference"
+        );
+        assert_eq!(
+            by_ref.pre_transform(SCALE).transform,
+            pre.transform,
+            "pre_transform on a reference"
+        );
+        assert_eq!(by_ref.to_owned(), moved, "to_owned");
+    }
+
+    #[test]
+    fn inverse_transform() {
+        let plain = TransformedBrush::new(Color::RED, None);
+        assert_eq!(plain.transform_or_identity(), Affine::IDENTITY, "identity");
+        assert_eq!(
+            plain.inverse_transform(),
+            Some(Affine::IDENTITY),
+            "inverse of the identity"
+        );
+        let transform = MOVE * Affine::rotate(0.5) * SCALE;
+        let brush = TransformedBrush::new(Color::RED, Some(transform));
+        let p = Point::new(3.0, -4.0);
+        assert_maps(
+            brush.inverse_transform(),
+            transform * p,
+            p,
+            "inverse maps the shape to brush space",
+        );
+        let collapsed =
+            TransformedBrush::new(Color::RED, Some(Affine::scale_non_uniform(1.0, 0.0)));
+        assert_eq!(collapsed.inverse_transform(), None, "singular transform");
+        let brush_ref = TransformedBrushRef::from(&collapsed);
+        assert_eq!(brush_ref.inverse_transform(), None, "singular reference");
+    }
+
+    #[test]
+    fn apply_transform() {
+        let transform = MOVE * Affine::rotate(0.5) * SCALE;
+        let solid = TransformedBrush::new(Color::RED, Some(transform)).apply_transform();
+        assert_eq!(solid, TransformedBrush::new(Color::RED, None), "solid");
+
+        let linear =
+            Gradient::new_linear((0.0, 0.0), (10.0, 0.0)).with_stops([Color::RED, Color::BLUE]);
+        let applied = TransformedBrush::new(linear.clone(), Some(transform)).apply_transform();
+        assert_eq!(
+            applied,
+            TransformedBrush::new(linear.transformed(transform).unwrap(), None),
+            "linear gradient"
+        );
+
+        let radial = Gradient::new_radial((0.0, 0.0), 5.0).with_stops([Color::RED, Color::BLUE]);
+        let stretch = Affine::scale_non_uniform(2.0, 1.0);
+        let kept = TransformedBrush::new(radial, Some(stretch));
+        assert_eq!(
+            kept.clone().apply_transform(),
+            kept,
+            "radial gradient with a non-uniform scale"
+        );
+
+        let image = Image::new(Blob::from(vec![0_u8; 4]), Format::Rgba8, 1, 1).unwrap();
+        let image_brush = TransformedBrush::new(image, Some(transform));
+        assert_eq!(image_brush.clone().apply_transform(), image_brush, "image");
+
+        let pattern = Pattern::new(PatternContent::new(()), Rect::new(0.0, 0.0, 1.0, 1.0))
+            .with_transform(SCALE);
+        let applied_pattern = TransformedBrush::new(pattern.clone(), Some(MOVE)).apply_transform();
+        assert_eq!(
+            applied_pattern,
+            TransformedBrush::new(pattern.with_transform(MOVE * SCALE), None),
+            "pattern"
+        );
+
+        let untransformed = TransformedBrush::new(Color::RED, None);
+        assert_eq!(
+            untransformed.clone().apply_transform(),
+            untransformed,
+            "no transform"
+        );
+    }
 }
@@ -3,6 +3,12 @@
 
 // Borrows code heavily from the piet (https://github.com/linebender/piet/) Color
 // type.
+use core::fmt;
+use core::str::FromStr;
+
+use super::colorspace::gamut_map_srgb;
+use super::{css, ColorSpace, PremulColor};
+
 #[cfg(all(not(feature = "std"), feature = "libm"))]
 #[allow(unused_imports)]
 use kurbo::common::FloatFuncs as _;
@@ -70,9 +76,10 @@ impl Color {
     /// support high-gamut colorspaces, it can be used to specify more colors
     /// or existing colors with a higher accuracy.
     ///
-    /// Currently out-of-gamut values are clipped to the nearest sRGB color,
-    /// which is perhaps not ideal (the clipping might change the hue). See
-    /// <https://github.com/d3/d3-color/issues/33> for discussion.
+    /// This is the same conversion as for the CSS `lch()` function and
+    /// [`ColorSpace::Lch`], with a D50 white point. Out-of-gamut values are
+    /// brought into sRGB with the CSS gamut mapping algorithm, which reduces
+    /// chroma to preserve lightness and hue.
     #[must_use]
     pub fn hlc(h: f64, l: f64, c: f64) -> Self {
         Self::hlca(h, l, c, 1.0)
@@ -81,61 +88,32 @@ impl Color {
     /// Create a color from a CIEL\*a\*b\* polar specification and alpha.
     ///
     /// The `a` value represents alpha in the range `0.0` to `1.0`.
-    #[allow(non_snake_case)]
-    #[allow(clippy::many_single_char_names)]
-    #[allow(clippy::unreadable_literal)]
     #[must_use]
     pub fn hlca(h: f64, l: f64, c: f64, alpha: f64) -> Self {
-        // The reverse transformation from Lab to XYZ, see
-        // https://en.wikipedia.org/wiki/CIELAB_color_space
-        fn f_inv(t: f64) -> f64 {
-            let d = 6. / 29.;
-            if t > d {
-                t.powi(3)
-            } else {
-                3. * d * d * (t - 4. / 29.)
-            }
-        }
-        let th = h * (core::f64::consts::PI / 180.);
-        let a = c * th.cos();
-        let b = c * th.sin();
-        let ll = (l + 16.) * (1. / 116.);
-        // Produce raw XYZ values
-        let X = f_inv(ll + a * (1. / 500.));
-        let Y = f_inv(ll);
-        let Z = f_inv(ll - b * (1. / 200.));
-        // This matrix is the concatenation of three sources.
-        // First, the white point is taken to be ICC standard D50, so
-        // the diagonal matrix of [0.9642, 1, 0.8249]. Note that there
-        // is some controversy around this value. However, it matches
-        // the other matrices, thus minimizing chroma error.
-        //
-        // Second, an adaption matrix from D50 to D65. This is the
-        // inverse of the recommended D50 to D65 adaptation matrix
-        // from the W3C sRGB spec:
-        // https://www.w3.org/Graphics/Color/srgb
-        //
-        // Finally, the conversion from XYZ to linear sRGB values,
-        // also taken from the W3C sRGB spec.
-        let r_lin = 3.02172918 * X - 1.61692294 * Y - 0.40480625 * Z;
-        let g_lin = -0.94339358 * X + 1.91584267 * Y + 0.02755094 * Z;
-        let b_lin = 0.06945666 * X - 0.22903204 * Y + 1.15957526 * Z;
-        fn gamma(u: f64) -> f64 {
-            if u <= 0.0031308 {
-                12.92 * u
-            } else {
-                1.055 * u.powf(1. / 2.4) - 0.055
-            }
-        }
-        Self::rgba(gamma(r_lin), gamma(g_lin), gamma(b_lin), alpha)
+        let [r, g, b] = gamut_map_srgb(ColorSpace::Lch, [l, c, h]);
+        Self::rgba(r, g, b, alpha)
     }
 
     /// Parses a color from a string.
     ///
-    /// Currently accepts CSS style hexadecimal colors of the forms #RGB, #RGBA,
-    /// #RRGGBB, #RRGGBBAA or the name of an SVG color such as "aliceblue".
-    #[must_use]
-    pub fn parse(s: &str) -> Option<Self> {
+    /// Accepts the color syntax of CSS Color Module Level 4:
+    /// - hexadecimal colors of the forms #RGB, #RGBA, #RRGGBB and #RRGGBBAA,
+    /// - the name of an SVG color such as "aliceblue", or "transparent",
+    /// - the `rgb()`, `rgba()`, `hsl()`, `hsla()`, `hwb()`, `lab()`, `lch()`,
+    ///   `oklab()`, `oklch()` and `color()` functions, including percentages
+    ///   and the `none` keyword for missing components, which are treated as zero.
+    ///
+    /// Names and function names are matched case-insensitively. Components are
+    /// clamped as described in the specification, and colors outside of the
+    /// sRGB gamut are brought into it with the CSS gamut mapping algorithm.
+    /// Math functions such as `calc()`, `currentcolor` and system colors are
+    /// not supported.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ColorParseError`] describing the location and kind of the
+    /// problem if the string is not a valid color.
+    pub fn parse(s: &str) -> Result<Self, ColorParseError> {
         parse_color(s)
     }
 
@@ -149,13 +127,11 @@ impl Color {
     }
 
     /// Returns the color as a packed premultiplied value.
+    ///
+    /// See [`PremulColor`] for a typed alternative.
     #[must_use]
     pub fn to_premul_u32(self) -> u32 {
-        let a = self.a as f64 * (1.0 / 255.0);
-        let r = (self.r as f64 * a).round() as u32;
-        let g = (self.g as f64 * a).round() as u32;
-        let b = (self.b as f64 * a).round() as u32;
-        (r << 24) | (g << 16) | (b << 8) | self.a as u32
+        PremulColor::from(self).to_u32()
     }
 }
 
@@ -460,159 +436,365 @@ impl From<[u8; 4]> for Color {
     }
 }
 
-fn parse_color(s: &str) -> Option<Color> {
-    let s = s.trim();
-    if let Some(stripped) = s.strip_prefix('#') {
-        Some(color_from_4bit_hex(get_4bit_hex_channels(stripped)?))
+impl FromStr for Color {
+    type Err = ColorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// The kind of problem encountered when [parsing](Color::parse) a color.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum ColorParseErrorKind {
+    /// The input was empty or only contained whitespace.
+    Empty,
+    /// A hexadecimal color contained a character that is not a hex digit.
+    InvalidHexDigit,
+    /// A hexadecimal color did not have 3, 4, 6 or 8 digits.
+    InvalidHexLength,
+    /// The input was not the name of a known color.
+    UnknownName,
+    /// The input used a color function that is not known.
+    UnknownFunction,
+    /// An argument of a color function was missing, out of place or of the
+    /// wrong type.
+    InvalidArgument,
+    /// A color function was malformed, such as by a missing parenthesis.
+    InvalidSyntax,
+    /// The color was followed by unexpected input.
+    TrailingInput,
+}
+
+impl ColorParseErrorKind {
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Self::Empty => "empty color string",
+            Self::InvalidHexDigit => "invalid hex digit",
+            Self::InvalidHexLength => "hex color must have 3, 4, 6 or 8 digits",
+            Self::UnknownName => "unknown color name",
+            Self::UnknownFunction => "unknown color function",
+            Self::InvalidArgument => "invalid color function argument",
+            Self::InvalidSyntax => "invalid color function syntax",
+            Self::TrailingInput => "unexpected input after color",
+        }
+    }
+}
+
+/// Error returned when [parsing](Color::parse) a color fails.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ColorParseError {
+    offset: usize,
+    kind: ColorParseErrorKind,
+    suggestion: Option<&'static str>,
+}
+
+impl ColorParseError {
+    pub(crate) fn new(offset: usize, kind: ColorParseErrorKind) -> Self {
+        Self {
+            offset,
+            kind,
+            suggestion: None,
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn with_suggestion(mut self, suggestion: Option<&'static str>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+
+    /// Returns the byte offset in the input at which the problem was found.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the kind of problem.
+    #[must_use]
+    pub fn kind(&self) -> ColorParseErrorKind {
+        self.kind
+    }
+
+    /// Returns a similarly spelled known name that may have been intended.
+    ///
+    /// This is provided for unknown color names, function names and color
+    /// spaces.
+    #[must_use]
+    pub fn suggestion(&self) -> Option<&'static str> {
+        self.suggestion
+    }
+}
+
+impl fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind.description(), self.offset)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, "; did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ColorParseError {}
+
+/// Named colors, sorted by name so that they can be binary searched.
+pub(crate) const NAMED_COLORS: &[(&str, Color)] = &[
+    ("aliceblue", Color::ALICE_BLUE),
+    ("antiquewhite", Color::ANTIQUE_WHITE),
+    ("aqua", Color::AQUA),
+    ("aquamarine", Color::AQUAMARINE),
+    ("azure", Color::AZURE),
+    ("beige", Color::BEIGE),
+    ("bisque", Color::BISQUE),
+    ("black", Color::BLACK),
+    ("blanchedalmond", Color::BLANCHED_ALMOND),
+    ("blue", Color::BLUE),
+    ("blueviolet", Color::BLUE_VIOLET),
+    ("brown", Color::BROWN),
+    ("burlywood", Color::BURLYWOOD),
+    ("cadetblue", Color::CADET_BLUE),
+    ("chartreuse", Color::CHARTREUSE),
+    ("chocolate", Color::CHOCOLATE),
+    ("coral", Color::CORAL),
+    ("cornflowerblue", Color::CORNFLOWER_BLUE),
+    ("cornsilk", Color::CORNSILK),
+    ("crimson", Color::CRIMSON),
+    ("cyan", Color::CYAN),
+    ("darkblue", Color::DARK_BLUE),
+    ("darkcyan", Color::DARK_CYAN),
+    ("darkgoldenrod", Color::DARK_GOLDENROD),
+    ("darkgray", Color::DARK_GRAY),
+    ("darkgreen", Color::DARK_GREEN),
+    ("darkkhaki", Color::DARK_KHAKI),
+    ("darkmagenta", Color::DARK_MAGENTA),
+    ("darkolivegreen", Color::DARK_OLIVE_GREEN),
+    ("darkorange", Color::DARK_ORANGE),
+    ("darkorchid", Color::DARK_ORCHID),
+    ("darkred", Color::DARK_RED),
+    ("darksalmon", Color::DARK_SALMON),
+    ("darkseagreen", Color::DARK_SEA_GREEN),
+    ("darkslateblue", Color::DARK_SLATE_BLUE),
+    ("darkslategray", Color::DARK_SLATE_GRAY),
+    ("darkturquoise", Color::DARK_TURQUOISE),
+    ("darkviolet", Color::DARK_VIOLET),
+    ("deeppink", Color::DEEP_PINK),
+    ("deepskyblue", Color::DEEP_SKY_BLUE),
+    ("dimgray", Color::DIM_GRAY),
+    ("dodgerblue", Color::DODGER_BLUE),
+    ("firebrick", Color::FIREBRICK),
+    ("floralwhite", Color::FLORAL_WHITE),
+    ("forestgreen", Color::FOREST_GREEN),
+    ("fuchsia", Color::FUCHSIA),
+    ("gainsboro", Color::GAINSBORO),
+    ("ghostwhite", Color::GHOST_WHITE),
+    ("gold", Color::GOLD),
+    ("goldenrod", Color::GOLDENROD),
+    ("gray", Color::GRAY),
+    ("green", Color::GREEN),
+    ("greenyellow", Color::GREEN_YELLOW),
+    ("honeydew", Color::HONEYDEW),
+    ("hotpink", Color::HOT_PINK),
+    ("indianred", Color::INDIAN_RED),
+    ("indigo", Color::INDIGO),
+    ("ivory", Color::IVORY),
+    ("khaki", Color::KHAKI),
+    ("lavender", Color::LAVENDER),
+    ("lavenderblush", Color::LAVENDER_BLUSH),
+    ("lawngreen", Color::LAWN_GREEN),
+    ("lemonchiffon", Color::LEMON_CHIFFON),
+    ("lightblue", Color::LIGHT_BLUE),
+    ("lightcoral", Color::LIGHT_CORAL),
+    ("lightcyan", Color::LIGHT_CYAN),
+    ("lightgoldenrodyellow", Color::LIGHT_GOLDENROD_YELLOW),
+    ("lightgray", Color::LIGHT_GRAY),
+    ("lightgreen", Color::LIGHT_GREEN),
+    ("lightpink", Color::LIGHT_PINK),
+    ("lightsalmon", Color::LIGHT_SALMON),
+    ("lightseagreen", Color::LIGHT_SEA_GREEN),
+    ("lightskyblue", Color::LIGHT_SKY_BLUE),
+    ("lightslategray", Color::LIGHT_SLATE_GRAY),
+    ("lightsteelblue", Color::LIGHT_STEEL_BLUE),
+    ("lightyellow", Color::LIGHT_YELLOW),
+    ("lime", Color::LIME),
+    ("limegreen", Color::LIME_GREEN),
+    ("linen", Color::LINEN),
+    ("magenta", Color::MAGENTA),
+    ("maroon", Color::MAROON),
+    ("mediumaquamarine", Color::MEDIUM_AQUAMARINE),
+    ("mediumblue", Color::MEDIUM_BLUE),
+    ("mediumorchid", Color::MEDIUM_ORCHID),
+    ("mediumpurple", Color::MEDIUM_PURPLE),
+    ("mediumseagreen", Color::MEDIUM_SEA_GREEN),
+    ("mediumslateblue", Color::MEDIUM_SLATE_BLUE),
+    ("mediumspringgreen", Color::MEDIUM_SPRING_GREEN),
+    ("mediumturquoise", Color::MEDIUM_TURQUOISE),
+    ("mediumvioletred", Color::MEDIUM_VIOLET_RED),
+    ("midnightblue", Color::MIDNIGHT_BLUE),
+    ("mintcream", Color::MINT_CREAM),
+    ("mistyrose", Color::MISTY_ROSE),
+    ("moccasin", Color::MOCCASIN),
+    ("navajowhite", Color::NAVAJO_WHITE),
+    ("navy", Color::NAVY),
+    ("oldlace", Color::OLD_LACE),
+    ("olive", Color::OLIVE),
+    ("olivedrab", Color::OLIVE_DRAB),
+    ("orange", Color::ORANGE),
+    ("orangered", Color::ORANGE_RED),
+    ("orchid", Color::ORCHID),
+    ("palegoldenrod", Color::PALE_GOLDENROD),
+    ("palegreen", Color::PALE_GREEN),
+    ("paleturquoise", Color::PALE_TURQUOISE),
+    ("palevioletred", Color::PALE_VIOLET_RED),
+    ("papayawhip", Color::PAPAYA_WHIP),
+    ("peachpuff", Color::PEACH_PUFF),
+    ("peru", Color::PERU),
+    ("pink", Color::PINK),
+    ("plum", Color::PLUM),
+    ("powderblue", Color::POWDER_BLUE),
+    ("purple", Color::PURPLE),
+    ("rebeccapurple", Color::REBECCA_PURPLE),
+    ("red", Color::RED),
+    ("rosybrown", Color::ROSY_BROWN),
+    ("royalblue", Color::ROYAL_BLUE),
+    ("saddlebrown", Color::SADDLE_BROWN),
+    ("salmon", Color::SALMON),
+    ("sandybrown", Color::SANDY_BROWN),
+    ("seagreen", Color::SEA_GREEN),
+    ("seashell", Color::SEASHELL),
+    ("sienna", Color::SIENNA),
+    ("silver", Color::SILVER),
+    ("skyblue", Color::SKY_BLUE),
+    ("slateblue", Color::SLATE_BLUE),
+    ("slategray", Color::SLATE_GRAY),
+    ("snow", Color::SNOW),
+    ("springgreen", Color::SPRING_GREEN),
+    ("steelblue", Color::STEEL_BLUE),
+    ("tan", Color::TAN),
+    ("teal", Color::TEAL),
+    ("thistle", Color::THISTLE),
+    ("tomato", Color::TOMATO),
+    ("transparent", Color::TRANSPARENT),
+    ("turquoise", Color::TURQUOISE),
+    ("violet", Color::VIOLET),
+    ("wheat", Color::WHEAT),
+    ("white", Color::WHITE),
+    ("whitesmoke", Color::WHITE_SMOKE),
+    ("yellow", Color::YELLOW),
+    ("yellowgreen", Color::YELLOW_GREEN),
+];
+
+/// Returns the named color with the given lowercase name.
+fn named_color(name: &str) -> Option<Color> {
+    NAMED_COLORS
+        .binary_search_by_key(&name, |(n, _)| n)
+        .ok()
+        .map(|i| NAMED_COLORS[i].1)
+}
+
+/// Returns the shortest name of the color, if it has one.
+pub(crate) fn color_name(color: Color) -> Option<&'static str> {
+    NAMED_COLORS
+        .iter()
+        .filter(|(_, c)| *c == color)
+        .map(|(n, _)| *n)
+        .min_by_key(|n| n.len())
+}
+
+fn parse_color(s: &str) -> Result<Color, ColorParseError> {
+    let offset = s.len() - s.trim_start().len();
+    let trimmed = s.trim();
+    if trimmed.is_empty() {
+        return Err(ColorParseError::new(offset, ColorParseErrorKind::Empty));
+    }
+    if let Some(hex) = trimmed.strip_prefix('#') {
+        if let Some(i) = hex.bytes().position(|b| hex_from_ascii_byte(b).is_err()) {
+            return Err(ColorParseError::new(
+                offset + 1 + i,
+                ColorParseErrorKind::InvalidHexDigit,
+            ));
+        }
+        get_4bit_hex_channels(hex)
+            .map(color_from_4bit_hex)
+            .ok_or(ColorParseError::new(
+                offset,
+                ColorParseErrorKind::InvalidHexLength,
+            ))
+    } else if trimmed.contains('(') {
+        css::parse_color_function(s, offset)
     } else {
-        Some(match s {
-            "aliceblue" => Color::ALICE_BLUE,
-            "antiquewhite" => Color::ANTIQUE_WHITE,
-            "aqua" => Color::AQUA,
-            "aquamarine" => Color::AQUAMARINE,
-            "azure" => Color::AZURE,
-            "beige" => Color::BEIGE,
-            "bisque" => Color::BISQUE,
-            "black" => Color::BLACK,
-            "blanchedalmond" => Color::BLANCHED_ALMOND,
-            "blue" => Color::BLUE,
-            "blueviolet" => Color::BLUE_VIOLET,
-            "brown" => Color::BROWN,
-            "burlywood" => Color::BURLYWOOD,
-            "cadetblue" => Color::CADET_BLUE,
-            "chartreuse" => Color::CHARTREUSE,
-            "chocolate" => Color::CHOCOLATE,
-            "coral" => Color::CORAL,
-            "cornflowerblue" => Color::CORNFLOWER_BLUE,
-            "cornsilk" => Color::CORNSILK,
-            "crimson" => Color::CRIMSON,
-            "cyan" => Color::CYAN,
-            "darkblue" => Color::DARK_BLUE,
-            "darkcyan" => Color::DARK_CYAN,
-            "darkgoldenrod" => Color::DARK_GOLDENROD,
-            "darkgray" => Color::DARK_GRAY,
-            "darkgreen" => Color::DARK_GREEN,
-            "darkkhaki" => Color::DARK_KHAKI,
-            "darkmagenta" => Color::DARK_MAGENTA,
-            "darkolivegreen" => Color::DARK_OLIVE_GREEN,
-            "darkorange" => Color::DARK_ORANGE,
-            "darkorchid" => Color::DARK_ORCHID,
-            "darkred" => Color::DARK_RED,
-            "darksalmon" => Color::DARK_SALMON,
-            "darkseagreen" => Color::DARK_SEA_GREEN,
-            "darkslateblue" => Color::DARK_SLATE_BLUE,
-            "darkslategray" => Color::DARK_SLATE_GRAY,
-            "darkturquoise" => Color::DARK_TURQUOISE,
-            "darkviolet" => Color::DARK_VIOLET,
-            "deeppink" => Color::DEEP_PINK,
-            "deepskyblue" => Color::DEEP_SKY_BLUE,
-            "dimgray" => Color::DIM_GRAY,
-            "dodgerblue" => Color::DODGER_BLUE,
-            "firebrick" => Color::FIREBRICK,
-            "floralwhite" => Color::FLORAL_WHITE,
-            "forestgreen" => Color::FOREST_GREEN,
-            "fuchsia" => Color::FUCHSIA,
-            "gainsboro" => Color::GAINSBORO,
-            "ghostwhite" => Color::GHOST_WHITE,
-            "gold" => Color::GOLD,
-            "goldenrod" => Color::GOLDENROD,
-            "gray" => Color::GRAY,
-            "green" => Color::GREEN,
-            "greenyellow" => Color::GREEN_YELLOW,
-            "honeydew" => Color::HONEYDEW,
-            "hotpink" => Color::HOT_PINK,
-            "indianred" => Color::INDIAN_RED,
-            "indigo" => Color::INDIGO,
-            "ivory" => Color::IVORY,
-            "khaki" => Color::KHAKI,
-            "lavender" => Color::LAVENDER,
-            "lavenderblush" => Color::LAVENDER_BLUSH,
-            "lawngreen" => Color::LAWN_GREEN,
-            "lemonchiffon" => Color::LEMON_CHIFFON,
-            "lightblue" => Color::LIGHT_BLUE,
-            "lightcoral" => Color::LIGHT_CORAL,
-            "lightcyan" => Color::LIGHT_CYAN,
-            "lightgoldenrodyellow" => Color::LIGHT_GOLDENROD_YELLOW,
-            "lightgray" => Color::LIGHT_GRAY,
-            "lightgreen" => Color::LIGHT_GREEN,
-            "lightpink" => Color::LIGHT_PINK,
-            "lightsalmon" => Color::LIGHT_SALMON,
-            "lightseagreen" => Color::LIGHT_SEA_GREEN,
-            "lightskyblue" => Color::LIGHT_SKY_BLUE,
-            "lightslategray" => Color::LIGHT_SLATE_GRAY,
-            "lightsteelblue" => Color::LIGHT_STEEL_BLUE,
-            "lightyellow" => Color::LIGHT_YELLOW,
-            "lime" => Color::LIME,
-            "limegreen" => Color::LIME_GREEN,
-            "linen" => Color::LINEN,
-            "magenta" => Color::MAGENTA,
-            "maroon" => Color::MAROON,
-            "mediumaquamarine" => Color::MEDIUM_AQUAMARINE,
-            "mediumblue" => Color::MEDIUM_BLUE,
-            "mediumorchid" => Color::MEDIUM_ORCHID,
-            "mediumpurple" => Color::MEDIUM_PURPLE,
-            "mediumseagreen" => Color::MEDIUM_SEA_GREEN,
-            "mediumslateblue" => Color::MEDIUM_SLATE_BLUE,
-            "mediumspringgreen" => Color::MEDIUM_SPRING_GREEN,
-            "mediumturquoise" => Color::MEDIUM_TURQUOISE,
-            "mediumvioletred" => Color::MEDIUM_VIOLET_RED,
-            "midnightblue" => Color::MIDNIGHT_BLUE,
-            "mintcream" => Color::MINT_CREAM,
-            "mistyrose" => Color::MISTY_ROSE,
-            "moccasin" => Color::MOCCASIN,
-            "navajowhite" => Color::NAVAJO_WHITE,
-            "navy" => Color::NAVY,
-            "oldlace" => Color::OLD_LACE,
-            "olive" => Color::OLIVE,
-            "olivedrab" => Color::OLIVE_DRAB,
-            "orange" => Color::ORANGE,
-            "orangered" => Color::ORANGE_RED,
-            "orchid" => Color::ORCHID,
-            "palegoldenrod" => Color::PALE_GOLDENROD,
-            "palegreen" => Color::PALE_GREEN,
-            "paleturquoise" => Color::PALE_TURQUOISE,
-            "palevioletred" => Color::PALE_VIOLET_RED,
-            "papayawhip" => Color::PAPAYA_WHIP,
-            "peachpuff" => Color::PEACH_PUFF,
-            "peru" => Color::PERU,
-            "pink" => Color::PINK,
-            "plum" => Color::PLUM,
-            "powderblue" => Color::POWDER_BLUE,
-            "purple" => Color::PURPLE,
-            "rebeccapurple" => Color::REBECCA_PURPLE,
-            "red" => Color::RED,
-            "rosybrown" => Color::ROSY_BROWN,
-            "royalblue" => Color::ROYAL_BLUE,
-            "saddlebrown" => Color::SADDLE_BROWN,
-            "salmon" => Color::SALMON,
-            "sandybrown" => Color::SANDY_BROWN,
-            "seagreen" => Color::SEA_GREEN,
-            "seashell" => Color::SEASHELL,
-            "sienna" => Color::SIENNA,
-            "silver" => Color::SILVER,
-            "skyblue" => Color::SKY_BLUE,
-            "slateblue" => Color::SLATE_BLUE,
-            "slategray" => Color::SLATE_GRAY,
-            "snow" => Color::SNOW,
-            "springgreen" => Color::SPRING_GREEN,
-            "steelblue" => Color::STEEL_BLUE,
-            "tan" => Color::TAN,
-            "teal" => Color::TEAL,
-            "thistle" => Color::THISTLE,
-            "tomato" => Color::TOMATO,
-            "transparent" => Color::TRANSPARENT,
-            "turquoise" => Color::TURQUOISE,
-            "violet" => Color::VIOLET,
-            "wheat" => Color::WHEAT,
-            "white" => Color::WHITE,
-            "whitesmoke" => Color::WHITE_SMOKE,
-            "yellow" => Color::YELLOW,
-            "yellowgreen" => Color::YELLOW_GREEN,
-            _ => return None,
+        // Named colors are ASCII case-insensitive. The longest name is 20
+        // bytes, so anything longer can be rejected before lowercasing.
+        let mut buf = [0_u8; 20];
+        let color = buf.get_mut(..trimmed.len()).and_then(|name| {
+            name.copy_from_slice(trimmed.as_bytes());
+            name.make_ascii_lowercase();
+            named_color(core::str::from_utf8(name).ok()?)
+        });
+        color.ok_or_else(|| {
+            ColorParseError::new(offset, ColorParseErrorKind::UnknownName)
+                .with_suggestion(suggest(trimmed, NAMED_COLORS.iter().map(|(n, _)| *n)))
         })
     }
 }
 
+/// Returns the candidate closest to `input` by edit distance, if any is close
+/// enough to be a plausible typo.
+pub(crate) fn suggest(
+    input: &str,
+    candidates: impl Iterator<Item = &'static str>,
+) -> Option<&'static str> {
+    // Inputs longer than this are not worth a suggestion, and the limit lets
+    // the distance be computed without allocating.
+    const MAX_LEN: usize = 32;
+    let input = input.as_bytes();
+    if input.is_empty() || input.len() > MAX_LEN {
+        return None;
+    }
+    let max_distance = (input.len() / 3).max(1);
+    let mut best: Option<(usize, &'static str)> = None;
+    for candidate in candidates {
+        let distance = edit_distance(input, candidate.as_bytes());
+        if distance <= max_distance && best.map_or(true, |(d, _)| distance < d) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+/// Computes the ASCII case-insensitive edit distance between `a`, which must
+/// be at most 32 bytes long, and `b`.
+///
+/// This is the optimal string alignment distance, which counts insertions,
+/// deletions, substitutions and transpositions of adjacent characters.
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let eq = |x: u8, y: u8| x.eq_ignore_ascii_case(&y);
+    // Rows of the distance matrix for the two previous and the current
+    // character of `b`.
+    let mut prev2 = [0_usize; 33];
+    let mut prev = [0_usize; 33];
+    let mut row = [0_usize; 33];
+    for (i, d) in prev.iter_mut().enumerate().take(a.len() + 1) {
+        *d = i;
+    }
+    for (j, &cb) in b.iter().enumerate() {
+        row[0] = j + 1;
+        for (i, &ca) in a.iter().enumerate() {
+            let substitution = prev[i] + usize::from(!eq(ca, cb));
+            let mut d = substitution.min(prev[i + 1] + 1).min(row[i] + 1);
+            if i > 0 && j > 0 && eq(ca, b[j - 1]) && eq(a[i - 1], cb) {
+                d = d.min(prev2[i - 1] + 1);
+            }
+            row[i + 1] = d;
+        }
+        prev2 = prev;
+        prev = row;
+    }
+    prev[a.len()]
+}
+
 // The following hex color parsing code taken from piet:
 
 const fn get_4bit_hex_channels(hex_str: &str) -> Option<[u8; 8]> {
@@ -656,3 +838,122 @@ const fn hex_from_ascii_byte(b: u8) -> Result<u8, u8> {
         _ => Err(b),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, ColorParseErrorKind};
+
+    /// Hand-written cases covering the CSS Color Module Level 4 syntax.
+    const FIXTURE: &str = include_str!("../testdata/color-parsing.txt");
+
+    fn parse_kind(name: &str) -> ColorParseErrorKind {
+        match name {
+            "Empty" => ColorParseErrorKind::Empty,
+            "InvalidHexDigit" => ColorParseErrorKind::InvalidHexDigit,
+            "InvalidHexLength" => ColorParseErrorKind::InvalidHexLength,
+            "UnknownName" => ColorParseErrorKind::UnknownName,
+            "UnknownFunction" => ColorParseErrorKind::UnknownFunction,
+            "InvalidArgument" => ColorParseErrorKind::InvalidArgument,
+            "InvalidSyntax" => ColorParseErrorKind::InvalidSyntax,
+            "TrailingInput" => ColorParseErrorKind::TrailingInput,
+            _ => panic!("unknown error kind `{name}` in fixture"),
+        }
+    }
+
+    #[test]
+    fn fixture() {
+        let mut cases = 0;
+        for line in FIXTURE.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[..] {
+                ["valid", input, expected] => {
+                    let expected = Color::parse(expected).unwrap();
+                    assert_eq!(Color::parse(input), Ok(expected), "parsing `{input}`");
+                }
+                ["invalid", input, kind, offset] => {
+                    let error = Color::parse(input).expect_err(input);
+                    assert_eq!(error.kind(), parse_kind(kind), "kind for `{input}`");
+                    assert_eq!(
+                        error.offset(),
+                        offset.parse::<usize>().unwrap(),
+                        "offset for `{input}`"
+                    );
+                }
+                _ => panic!("malformed fixture line `{line}`"),
+            }
+            cases += 1;
+        }
+        assert!(cases > 50, "fixture should not be empty");
+    }
+
+    #[test]
+    fn suggestions() {
+        let suggestion = |input: &str| Color::parse(input).unwrap_err().suggestion();
+        assert_eq!(suggestion("bleu"), Some("blue"), "transposition");
+        assert_eq!(suggestion("  Grean "), Some("green"), "substitution");
+        assert_eq!(
+            suggestion("cornflowerblu"),
+            Some("cornflowerblue"),
+            "deletion"
+        );
+        assert_eq!(
+            suggestion("rebecapurple"),
+            Some("rebeccapurple"),
+            "deletion"
+        );
+        assert_eq!(suggestion("rbg(1 2 3)"), Some("rgb"), "function name");
+        assert_eq!(suggestion("okclh(1 0 0)"), Some("oklch"), "function name");
+        assert_eq!(
+            suggestion("color(display-p4 1 0 0)"),
+            Some("display-p3"),
+            "color space"
+        );
+        assert_eq!(suggestion("qwertyuiop"), None, "nothing close");
+        assert_eq!(suggestion("#12"), None, "not a name");
+    }
+
+    #[test]
+    fn error_display() {
+        let unknown = Color::parse("bleu").unwrap_err();
+        assert_eq!(
+            unknown.to_string(),
+            "unknown color name at offset 0; did you mean `blue`?",
+            "display with suggestion"
+        );
+        let unclosed = Color::parse("rgb(1 2 3").unwrap_err();
+        assert_eq!(
+            unclosed.to_string(),
+            "invalid color function syntax at offset 9",
+            "display without suggestion"
+        );
+    }
+
+    #[test]
+    fn hlc() {
+        assert_eq!(
+            Color::hlc(40.0, 50.0, 30.0),
+            Color::rgb8(162, 103, 87),
+            "hlc"
+        );
+        assert_eq!(
+            Color::hlca(250.0, 70.0, 20.0, 0.5),
+            Color::rgba8(142, 176, 205, 128),
+            "hlca"
+        );
+        // Out of gamut colors are mapped as by the CSS `lch()` function.
+        for (h, l, c) in [
+            (40.0, 50.0, 30.0),
+            (40.0, 50.0, 150.0),
+            (300.0, 90.0, 120.0),
+        ] {
+            assert_eq!(
+                Color::hlc(h, l, c),
+                Color::parse(&format!("lch({l} {c} {h})")).unwrap(),
+                "lch({l} {c} {h})"
+            );
+        }
+    }
+}
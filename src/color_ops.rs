@@ -0,0 +1,249 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Perceptual manipulation of colors.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::colorspace::{gamut_map_srgb, interpolate, linear_to_srgb, srgb_to_linear};
+use super::{Color, ColorSpace, HueInterpolation};
+
+/// Color manipulation.
+///
+/// Unless noted otherwise, these operations work in the CIE LCH color space
+/// (the polar form of CIE L\*a\*b\*, with the same components as
+/// [`Color::hlc`] and the CSS `lch()` function), whose lightness and chroma
+/// match perceived lightness and colorfulness much better than sRGB.
+///
+/// All computation is in `f64`, and the result is rounded to 8 bits once, so
+/// each channel is within half a unit of the exact result. Results that fall
+/// outside of the sRGB gamut, such as a saturated color made lighter, are
+/// mapped into it with the CSS gamut mapping algorithm, which reduces chroma
+/// to preserve lightness and hue. Alpha is preserved exactly, except by
+/// [`mix`](Self::mix). An amount of zero returns the color unchanged, but
+/// chaining operations accumulates rounding error, and the hue of colors near
+/// gray is not well defined, so rotating it or saturating them may produce
+/// noticeable shifts.
+impl Color {
+    /// Mixes this color with `other` in the given color space.
+    ///
+    /// A `t` of `0.0` produces this color and `1.0` produces `other`. This
+    /// interpolates with premultiplied alpha as in CSS `color-mix()`, taking
+    /// the shorter arc between hues in polar spaces. Common choices are
+    /// [`ColorSpace::Srgb`] to match legacy blending,
+    /// [`ColorSpace::LinearSrgb`] to match physical mixing of light, and
+    /// [`ColorSpace::Oklab`] for perceptually even steps.
+    #[must_use]
+    pub fn mix(self, other: Self, t: f64, space: ColorSpace) -> Self {
+        let to_space = |color: Self| {
+            let components = ColorSpace::Srgb.convert(space, color.components());
+            (components, f64::from(color.a) / 255.0)
+        };
+        let (components, alpha) = interpolate(
+            space,
+            HueInterpolation::Shorter,
+            to_space(self),
+            to_space(other),
+            t,
+        );
+        let [r, g, b] = gamut_map_srgb(space, components);
+        Self::rgba(r, g, b, alpha)
+    }
+
+    /// Makes the color lighter by adding `amount` times `100.0` to its
+    /// CIE lightness, so `0.1` lightens by a tenth of the range from black to
+    /// white. Negative amounts darken.
+    #[must_use]
+    pub fn lighten(self, amount: f64) -> Self {
+        self.map_lch(|[l, c, h]| [(l + amount * 100.0).clamp(0.0, 100.0), c, h])
+    }
+
+    /// Makes the color darker by subtracting `amount` times `100.0` from its
+    /// CIE lightness. This is the same as [`lighten`](Self::lighten) with a
+    /// negated amount.
+    #[must_use]
+    pub fn darken(self, amount: f64) -> Self {
+        self.lighten(-amount)
+    }
+
+    /// Makes the color more colorful by scaling its CIE chroma by
+    /// `1.0 + amount`, so `0.5` increases chroma by half. Negative amounts
+    /// desaturate.
+    ///
+    /// Grays have no chroma, and so are unchanged.
+    #[must_use]
+    pub fn saturate(self, amount: f64) -> Self {
+        self.map_lch(|[l, c, h]| [l, (c * (1.0 + amount)).max(0.0), h])
+    }
+
+    /// Makes the color less colorful by scaling its CIE chroma by
+    /// `1.0 - amount`, so `1.0` produces a gray. This is the same as
+    /// [`saturate`](Self::saturate) with a negated amount.
+    #[must_use]
+    pub fn desaturate(self, amount: f64) -> Self {
+        self.saturate(-amount)
+    }
+
+    /// Rotates the CIE hue of the color by the given angle in degrees.
+    #[must_use]
+    pub fn rotate_hue(self, degrees: f64) -> Self {
+        self.map_lch(|[l, c, h]| [l, c, h + degrees])
+    }
+
+    /// Inverts the red, green and blue channels of the color, as the CSS
+    /// `invert()` filter does.
+    ///
+    /// This is exact, and is its own inverse.
+    #[must_use]
+    pub fn invert(self) -> Self {
+        Self::rgba8(255 - self.r, 255 - self.g, 255 - self.b, self.a)
+    }
+
+    /// Converts the color to the gray with the same
+    /// [relative luminance](Self::relative_luminance), and so the same
+    /// perceived lightness.
+    ///
+    /// This is close to [`desaturate`](Self::desaturate) with an amount of
+    /// `1.0`, but unlike CIE lightness, which is relative to a D50 white
+    /// point, the luminance matches that of the sRGB color up to rounding.
+    #[must_use]
+    pub fn grayscale(self) -> Self {
+        let y = self.relative_luminance();
+        let [gray, ..] = linear_to_srgb([y; 3]);
+        Self {
+            a: self.a,
+            ..Self::rgb(gray, gray, gray)
+        }
+    }
+
+    /// Returns the relative luminance of the color as defined by WCAG 2, in
+    /// the range `0.0` for black to `1.0` for white.
+    ///
+    /// This ignores alpha. The contrast ratio between two colors is
+    /// `(l1 + 0.05) / (l2 + 0.05)`, where `l1` is the relative luminance of
+    /// the lighter color.
+    #[must_use]
+    pub fn relative_luminance(self) -> f64 {
+        let [r, g, b] = srgb_to_linear(self.components());
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    /// Returns the red, green and blue channels in the range `0.0` to `1.0`.
+    fn components(self) -> [f64; 3] {
+        [self.r, self.g, self.b].map(|c| f64::from(c) / 255.0)
+    }
+
+    /// Applies a function to the CIE LCH components of the color, and
+    /// converts the result back as [`Color::hlc`] does.
+    fn map_lch(self, f: impl FnOnce([f64; 3]) -> [f64; 3]) -> Self {
+        let [l, c, h] = f(ColorSpace::Srgb.convert(ColorSpace::Lch, self.components()));
+        Self {
+            a: self.a,
+            ..Self::hlc(h, l, c)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color, ColorSpace};
+
+    const ORANGE: Color = Color::rgb8(200, 100, 50);
+    const BEIGE: Color = Color::rgb8(150, 120, 100);
+
+    // Expected values are computed with the conversions of CSS Color Module
+    // Level 4, and are not near a rounding boundary.
+
+    #[test]
+    fn lighten() {
+        assert_eq!(
+            Color::rgb8(128, 128, 128).lighten(0.1),
+            Color::rgb8(154, 154, 154),
+            "gray"
+        );
+        assert_eq!(ORANGE.lighten(0.1), Color::rgb8(230, 126, 74), "orange");
+        assert_eq!(ORANGE.darken(0.1), Color::rgb8(170, 74, 26), "darken");
+        assert_eq!(ORANGE.lighten(0.0), ORANGE, "zero amount");
+        let translucent = Color::rgba8(200, 100, 50, 77);
+        assert_eq!(translucent.lighten(0.1).a, 77, "alpha is preserved");
+    }
+
+    #[test]
+    fn saturate() {
+        assert_eq!(BEIGE.saturate(0.5), Color::rgb8(160, 117, 87), "saturate");
+        assert_eq!(
+            ORANGE.desaturate(0.5),
+            Color::rgb8(169, 117, 91),
+            "desaturate"
+        );
+        let gray = Color::rgb8(128, 128, 128);
+        assert_eq!(gray.saturate(1.0), gray, "gray has no chroma");
+    }
+
+    #[test]
+    fn rotate_hue() {
+        assert_eq!(
+            BEIGE.rotate_hue(120.0),
+            Color::rgb8(89, 136, 125),
+            "120 degrees"
+        );
+        assert_eq!(BEIGE.rotate_hue(360.0), BEIGE, "full turn");
+        assert_eq!(
+            BEIGE.rotate_hue(120.0),
+            BEIGE.rotate_hue(-240.0),
+            "negative angles"
+        );
+    }
+
+    #[test]
+    fn mix() {
+        let (red, blue) = (Color::RED, Color::BLUE);
+        assert_eq!(
+            red.mix(blue, 0.5, ColorSpace::Srgb),
+            Color::rgb8(128, 0, 128),
+            "sRGB"
+        );
+        assert_eq!(
+            red.mix(blue, 0.5, ColorSpace::LinearSrgb),
+            Color::rgb8(188, 0, 188),
+            "linear sRGB"
+        );
+        assert_eq!(
+            red.mix(blue, 0.5, ColorSpace::Oklab),
+            Color::rgb8(140, 83, 162),
+            "Oklab"
+        );
+        assert_eq!(red.mix(blue, 0.0, ColorSpace::Oklab), red, "start");
+        assert_eq!(red.mix(blue, 1.0, ColorSpace::Oklab), blue, "end");
+        // Premultiplied interpolation keeps the color of the opaque side.
+        assert_eq!(
+            red.mix(Color::TRANSPARENT, 0.5, ColorSpace::Srgb),
+            Color::rgba8(255, 0, 0, 128),
+            "transparent"
+        );
+    }
+
+    #[test]
+    fn relative_luminance() {
+        let cases = [
+            (Color::BLACK, 0.0),
+            (Color::WHITE, 1.0),
+            (Color::RED, 0.2126),
+            (Color::rgb8(0, 255, 0), 0.7152),
+            (Color::BLUE, 0.0722),
+            (Color::rgb8(128, 128, 128), 0.215_860_5),
+            (ORANGE, 0.216_239_9),
+        ];
+        for (color, expected) in cases {
+            let luminance = color.relative_luminance();
+            assert!(
+                (luminance - expected).abs() < 1e-6,
+                "{color:?} has luminance {luminance}, expected {expected}"
+            );
+        }
+        let gray = ORANGE.grayscale();
+        assert_eq!(gray, Color::rgb8(128, 128, 128), "grayscale");
+    }
+}
@@ -0,0 +1,651 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Conversions between the color spaces defined by CSS Color Module Level 4.
+//!
+//! The matrices and transfer functions are taken from the sample code in the
+//! specification: <https://www.w3.org/TR/css-color-4/#color-conversion-code>.
+//! All conversions go through CIE XYZ with a D65 white point.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+/// A color space defined by CSS Color Module Level 4.
+///
+/// The variant determines how the three components of a
+/// [`DynamicColor`](crate::DynamicColor) are interpreted. The RGB spaces have
+/// red, green and blue components, where `0.0` to `1.0` is the nominal range
+/// and values outside of it represent colors outside of the gamut.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ColorSpace {
+    /// The sRGB color space.
+    #[default]
+    Srgb,
+    /// The sRGB color space with a linear transfer function.
+    LinearSrgb,
+    /// The Display P3 color space.
+    DisplayP3,
+    /// The Adobe RGB (1998) color space.
+    A98Rgb,
+    /// The `prophoto-rgb` color space, with a D50 white point.
+    ProphotoRgb,
+    /// The ITU-R BT.2020 color space.
+    Rec2020,
+    /// The CIE L\*a\*b\* color space, with a D50 white point.
+    ///
+    /// The components are lightness in the range `0.0` to `100.0`, and the
+    /// unbounded `a` and `b` axes, which are usually within `-125.0` to `125.0`.
+    Lab,
+    /// The polar form of CIE L\*a\*b\*.
+    ///
+    /// The components are lightness in the range `0.0` to `100.0`, chroma,
+    /// which is usually less than `150.0`, and hue in degrees.
+    Lch,
+    /// The HSL form of sRGB.
+    ///
+    /// The components are hue in degrees, and saturation and lightness in the
+    /// range `0.0` to `100.0`.
+    Hsl,
+    /// The HWB form of sRGB.
+    ///
+    /// The components are hue in degrees, and whiteness and blackness in the
+    /// range `0.0` to `100.0`.
+    Hwb,
+    /// The Oklab color space.
+    ///
+    /// The components are lightness in the range `0.0` to `1.0`, and the
+    /// unbounded `a` and `b` axes, which are usually within `-0.4` to `0.4`.
+    Oklab,
+    /// The polar form of Oklab.
+    ///
+    /// The components are lightness in the range `0.0` to `1.0`, chroma,
+    /// which is usually less than `0.4`, and hue in degrees.
+    Oklch,
+    /// CIE XYZ with a D50 white point.
+    ///
+    /// The `Y` component of the reference white is `1.0`.
+    XyzD50,
+    /// CIE XYZ with a D65 white point.
+    ///
+    /// The `Y` component of the reference white is `1.0`.
+    XyzD65,
+}
+
+/// Method for interpolating the hue of colors in a polar [color space](ColorSpace).
+///
+/// This corresponds to the `<hue-interpolation-method>` of CSS Color Module
+/// Level 4: <https://www.w3.org/TR/css-color-4/#hue-interpolation>.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HueInterpolation {
+    /// Takes the shorter of the two arcs between the hues.
+    #[default]
+    Shorter,
+    /// Takes the longer of the two arcs between the hues.
+    Longer,
+    /// Interpolates with increasing hue angles.
+    Increasing,
+    /// Interpolates with decreasing hue angles.
+    Decreasing,
+}
+
+pub(crate) type Mat3 = [[f64; 3]; 3];
+
+pub(crate) fn mul3(m: &Mat3, v: [f64; 3]) -> [f64; 3] {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+#[rustfmt::skip]
+const LIN_SRGB_TO_XYZ: Mat3 = [
+    [0.41239079926595934, 0.357584339383878, 0.1804807884018343],
+    [0.21263900587151027, 0.715168678767756, 0.07219231536073371],
+    [0.01933081871559182, 0.11919477979462598, 0.9505321522496607],
+];
+#[rustfmt::skip]
+const XYZ_TO_LIN_SRGB: Mat3 = [
+    [3.2409699419045226, -1.537383177570094, -0.4986107602930034],
+    [-0.9692436362808796, 1.8759675015077202, 0.04155505740717559],
+    [0.05563007969699366, -0.20397695888897652, 1.0569715142428786],
+];
+#[rustfmt::skip]
+const LIN_P3_TO_XYZ: Mat3 = [
+    [0.4865709486482162, 0.26566769316909306, 0.1982172852343625],
+    [0.2289745640697488, 0.6917385218365064, 0.079286914093745],
+    [0.0, 0.04511338185890264, 1.043944368900976],
+];
+#[rustfmt::skip]
+const XYZ_TO_LIN_P3: Mat3 = [
+    [2.493496911941425, -0.9313836179191239, -0.40271078445071684],
+    [-0.8294889695615747, 1.7626640603183463, 0.023624685841943577],
+    [0.03584583024378447, -0.07617238926804182, 0.9568845240076872],
+];
+#[rustfmt::skip]
+const LIN_A98_TO_XYZ: Mat3 = [
+    [0.5766690429101305, 0.1855582379065463, 0.1882286462349947],
+    [0.29734497525053605, 0.6273635662554661, 0.07529145849399788],
+    [0.02703136138641234, 0.07068885253582723, 0.9913375368376388],
+];
+#[rustfmt::skip]
+const XYZ_TO_LIN_A98: Mat3 = [
+    [2.0415879038107465, -0.5650069742788596, -0.34473135077832956],
+    [-0.9692436362808795, 1.8759675015077202, 0.04155505740717557],
+    [0.013444280632031142, -0.11836239223101838, 1.0151749943912054],
+];
+// This color space has a D50 white point.
+#[rustfmt::skip]
+const LIN_PROPHOTO_TO_XYZ_D50: Mat3 = [
+    [0.7977666449006423, 0.13518129740053308, 0.0313477341283922],
+    [0.2880748288194013, 0.711835234241873, 0.00008993693872564],
+    [0.0, 0.0, 0.8251046025104602],
+];
+#[rustfmt::skip]
+const XYZ_D50_TO_LIN_PROPHOTO: Mat3 = [
+    [1.3457868816471583, -0.25557208737979464, -0.05110186497554526],
+    [-0.5446307051249019, 1.5082477428451468, 0.02052744743642139],
+    [0.0, 0.0, 1.2119675456389452],
+];
+#[rustfmt::skip]
+const LIN_REC2020_TO_XYZ: Mat3 = [
+    [0.6369580483012914, 0.14461690358620832, 0.1688809751641721],
+    [0.2627002120112671, 0.6779980715188708, 0.05930171646986196],
+    [0.0, 0.028072693049087428, 1.060985057710791],
+];
+#[rustfmt::skip]
+const XYZ_TO_LIN_REC2020: Mat3 = [
+    [1.7166511879712674, -0.35567078377639233, -0.25336628137365974],
+    [-0.6666843518324892, 1.6164812366349395, 0.01576854581391113],
+    [0.017639857445310783, -0.042770613257808524, 0.9421031212354738],
+];
+/// Bradford chromatic adaptation from D65 to D50.
+#[rustfmt::skip]
+const D65_TO_D50: Mat3 = [
+    [1.0479297925449969, 0.022946870601609652, -0.05019226628920524],
+    [0.02962780877005599, 0.9904344267538799, -0.017073799063418826],
+    [-0.009243040646204504, 0.015055191490298152, 0.7518742814281371],
+];
+/// Bradford chromatic adaptation from D50 to D65.
+#[rustfmt::skip]
+const D50_TO_D65: Mat3 = [
+    [0.955473421488075, -0.02309845494876471, 0.06325924320057072],
+    [-0.0283697093338637, 1.0099953980813041, 0.021041441191917323],
+    [0.012314014864481998, -0.020507649298898964, 1.330365926242124],
+];
+#[rustfmt::skip]
+const XYZ_TO_LMS: Mat3 = [
+    [0.819022437996703, 0.3619062600528904, -0.1288737815209879],
+    [0.0329836539323885, 0.9292868615863434, 0.0361446663506424],
+    [0.0481771893596242, 0.2642395317527308, 0.6335478284694309],
+];
+#[rustfmt::skip]
+const LMS_TO_XYZ: Mat3 = [
+    [1.2268798758459243, -0.5578149944602171, 0.2813910456659647],
+    [-0.0405757452148008, 1.112286803280317, -0.0717110580655164],
+    [-0.0763729366746601, -0.4214933324022432, 1.5869240198367816],
+];
+#[rustfmt::skip]
+const LMS_TO_OKLAB: Mat3 = [
+    [0.210454268309314, 0.7936177747023054, -0.0040720430116193],
+    [1.9779985324311684, -2.42859224204858, 0.450593709617411],
+    [0.0259040424655478, 0.7827717124575296, -0.8086757549230774],
+];
+#[rustfmt::skip]
+const OKLAB_TO_LMS: Mat3 = [
+    [1.0, 0.3963377773761749, 0.2158037573099136],
+    [1.0, -0.1055613458156586, -0.0638541728258133],
+    [1.0, -0.0894841775298119, -1.2914855480194092],
+];
+
+/// The D50 reference white, as used by CIE L\*a\*b\*.
+const D50_WHITE: [f64; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+
+/// Applies `f` to the magnitude of each component, preserving the sign.
+fn map_signed(c: [f64; 3], f: impl Fn(f64) -> f64) -> [f64; 3] {
+    c.map(|x| if x < 0.0 { -f(-x) } else { f(x) })
+}
+
+pub(crate) fn srgb_to_linear(c: [f64; 3]) -> [f64; 3] {
+    map_signed(c, |x| {
+        if x <= 0.04045 {
+            x / 12.92
+        } else {
+            ((x + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+pub(crate) fn linear_to_srgb(c: [f64; 3]) -> [f64; 3] {
+    map_signed(c, |x| {
+        if x > 0.0031308 {
+            1.055 * x.powf(1.0 / 2.4) - 0.055
+        } else {
+            12.92 * x
+        }
+    })
+}
+
+fn a98_to_linear(c: [f64; 3]) -> [f64; 3] {
+    map_signed(c, |x| x.powf(563.0 / 256.0))
+}
+
+fn linear_to_a98(c: [f64; 3]) -> [f64; 3] {
+    map_signed(c, |x| x.powf(256.0 / 563.0))
+}
+
+fn prophoto_to_linear(c: [f64; 3]) -> [f64; 3] {
+    map_signed(c, |x| {
+        if x <= 16.0 / 512.0 {
+            x / 16.0
+        } else {
+            x.powf(1.8)
+        }
+    })
+}
+
+fn linear_to_prophoto(c: [f64; 3]) -> [f64; 3] {
+    map_signed(c, |x| {
+        if x >= 1.0 / 512.0 {
+            x.powf(1.0 / 1.8)
+        } else {
+            16.0 * x
+        }
+    })
+}
+
+const REC2020_ALPHA: f64 = 1.09929682680944;
+const REC2020_BETA: f64 = 0.018053968510807;
+
+fn rec2020_to_linear(c: [f64; 3]) -> [f64; 3] {
+    map_signed(c, |x| {
+        if x < REC2020_BETA * 4.5 {
+            x / 4.5
+        } else {
+            ((x + REC2020_ALPHA - 1.0) / REC2020_ALPHA).powf(1.0 / 0.45)
+        }
+    })
+}
+
+fn linear_to_rec2020(c: [f64; 3]) -> [f64; 3] {
+    map_signed(c, |x| {
+        if x > REC2020_BETA {
+            REC2020_ALPHA * x.powf(0.45) - (REC2020_ALPHA - 1.0)
+        } else {
+            4.5 * x
+        }
+    })
+}
+
+const LAB_KAPPA: f64 = 24389.0 / 27.0;
+const LAB_EPSILON: f64 = 216.0 / 24389.0;
+
+fn xyz_d50_to_lab(xyz: [f64; 3]) -> [f64; 3] {
+    let f = [0, 1, 2].map(|i| {
+        let x = xyz[i] / D50_WHITE[i];
+        if x > LAB_EPSILON {
+            x.cbrt()
+        } else {
+            (LAB_KAPPA * x + 16.0) / 116.0
+        }
+    });
+    [
+        116.0 * f[1] - 16.0,
+        500.0 * (f[0] - f[1]),
+        200.0 * (f[1] - f[2]),
+    ]
+}
+
+fn lab_to_xyz_d50(lab: [f64; 3]) -> [f64; 3] {
+    let [l, a, b] = lab;
+    let f1 = (l + 16.0) / 116.0;
+    let f0 = a / 500.0 + f1;
+    let f2 = f1 - b / 200.0;
+    let x = if f0.powi(3) > LAB_EPSILON {
+        f0.powi(3)
+    } else {
+        (116.0 * f0 - 16.0) / LAB_KAPPA
+    };
+    let y = if l > LAB_KAPPA * LAB_EPSILON {
+        f1.powi(3)
+    } else {
+        l / LAB_KAPPA
+    };
+    let z = if f2.powi(3) > LAB_EPSILON {
+        f2.powi(3)
+    } else {
+        (116.0 * f2 - 16.0) / LAB_KAPPA
+    };
+    [x * D50_WHITE[0], y * D50_WHITE[1], z * D50_WHITE[2]]
+}
+
+fn xyz_d65_to_oklab(xyz: [f64; 3]) -> [f64; 3] {
+    let lms = mul3(&XYZ_TO_LMS, xyz).map(f64::cbrt);
+    mul3(&LMS_TO_OKLAB, lms)
+}
+
+fn oklab_to_xyz_d65(lab: [f64; 3]) -> [f64; 3] {
+    let lms = mul3(&OKLAB_TO_LMS, lab).map(|x| x * x * x);
+    mul3(&LMS_TO_XYZ, lms)
+}
+
+/// Normalizes an angle in degrees to the range `[0, 360)`.
+pub(crate) fn normalize_hue(h: f64) -> f64 {
+    let h = h - 360.0 * (h / 360.0).floor();
+    // Guard against rounding producing exactly 360.
+    if h >= 360.0 {
+        0.0
+    } else {
+        h
+    }
+}
+
+fn rectangular_to_polar(c: [f64; 3]) -> [f64; 3] {
+    let [l, a, b] = c;
+    let chroma = a.hypot(b);
+    let hue = normalize_hue(b.atan2(a).to_degrees());
+    [l, chroma, hue]
+}
+
+fn polar_to_rectangular(c: [f64; 3]) -> [f64; 3] {
+    let [l, chroma, hue] = c;
+    let (sin, cos) = hue.to_radians().sin_cos();
+    [l, chroma * cos, chroma * sin]
+}
+
+/// Converts HSL (hue in degrees, saturation and lightness in `0..=100`) to sRGB.
+fn hsl_to_srgb(c: [f64; 3]) -> [f64; 3] {
+    let h = normalize_hue(c[0]);
+    let s = c[1] * 0.01;
+    let l = c[2] * 0.01;
+    let f = |n: f64| {
+        let k = (n + h / 30.0) % 12.0;
+        let a = s * l.min(1.0 - l);
+        l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    [f(0.0), f(8.0), f(4.0)]
+}
+
+/// Returns the hue in degrees of an sRGB color, along with its maximum and
+/// minimum components.
+fn srgb_hue(c: [f64; 3]) -> (f64, f64, f64) {
+    let [r, g, b] = c;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let d = max - min;
+    let hue = if d == 0.0 {
+        0.0
+    } else if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (hue * 60.0, max, min)
+}
+
+fn srgb_to_hsl(c: [f64; 3]) -> [f64; 3] {
+    let (mut hue, max, min) = srgb_hue(c);
+    let l = (min + max) * 0.5;
+    let d = max - min;
+    let mut s = if d == 0.0 || l <= 0.0 || l >= 1.0 {
+        0.0
+    } else {
+        (max - l) / l.min(1.0 - l)
+    };
+    // Out of gamut colors can produce a negative saturation.
+    if s < 0.0 {
+        hue += 180.0;
+        s = -s;
+    }
+    [normalize_hue(hue), s * 100.0, l * 100.0]
+}
+
+/// Converts HWB (hue in degrees, whiteness and blackness in `0..=100`) to sRGB.
+fn hwb_to_srgb(c: [f64; 3]) -> [f64; 3] {
+    let w = c[1] * 0.01;
+    let b = c[2] * 0.01;
+    if w + b >= 1.0 {
+        let gray = w / (w + b);
+        return [gray; 3];
+    }
+    hsl_to_srgb([c[0], 100.0, 50.0]).map(|x| x * (1.0 - w - b) + w)
+}
+
+fn srgb_to_hwb(c: [f64; 3]) -> [f64; 3] {
+    let (hue, max, min) = srgb_hue(c);
+    [normalize_hue(hue), min * 100.0, (1.0 - max) * 100.0]
+}
+
+impl ColorSpace {
+    /// Returns true if this is a cylindrical color space with a hue component.
+    #[must_use]
+    pub fn is_polar(self) -> bool {
+        self.hue_index().is_some()
+    }
+
+    /// Returns the index of the hue component for polar color spaces.
+    pub(crate) fn hue_index(self) -> Option<usize> {
+        match self {
+            Self::Lch | Self::Oklch => Some(2),
+            Self::Hsl | Self::Hwb => Some(0),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the hue of the given components has no effect on the
+    /// color, such as for grays.
+    fn is_hue_powerless(self, c: [f64; 3]) -> bool {
+        match self {
+            Self::Lch | Self::Hsl => c[1] <= 1e-3,
+            Self::Oklch => c[1] <= 1e-5,
+            Self::Hwb => c[1] + c[2] >= 100.0 - 1e-3,
+            _ => false,
+        }
+    }
+
+    /// Converts components in this color space to CIE XYZ with a D65 white point.
+    pub(crate) fn to_xyz_d65(self, c: [f64; 3]) -> [f64; 3] {
+        match self {
+            Self::Srgb => mul3(&LIN_SRGB_TO_XYZ, srgb_to_linear(c)),
+            Self::LinearSrgb => mul3(&LIN_SRGB_TO_XYZ, c),
+            Self::DisplayP3 => mul3(&LIN_P3_TO_XYZ, srgb_to_linear(c)),
+            Self::A98Rgb => mul3(&LIN_A98_TO_XYZ, a98_to_linear(c)),
+            Self::ProphotoRgb => mul3(
+                &D50_TO_D65,
+                mul3(&LIN_PROPHOTO_TO_XYZ_D50, prophoto_to_linear(c)),
+            ),
+            Self::Rec2020 => mul3(&LIN_REC2020_TO_XYZ, rec2020_to_linear(c)),
+            Self::Lab => mul3(&D50_TO_D65, lab_to_xyz_d50(c)),
+            Self::Lch => mul3(&D50_TO_D65, lab_to_xyz_d50(polar_to_rectangular(c))),
+            Self::Hsl => Self::Srgb.to_xyz_d65(hsl_to_srgb(c)),
+            Self::Hwb => Self::Srgb.to_xyz_d65(hwb_to_srgb(c)),
+            Self::Oklab => oklab_to_xyz_d65(c),
+            Self::Oklch => oklab_to_xyz_d65(polar_to_rectangular(c)),
+            Self::XyzD50 => mul3(&D50_TO_D65, c),
+            Self::XyzD65 => c,
+        }
+    }
+
+    /// Converts CIE XYZ with a D65 white point to components in this color space.
+    pub(crate) fn convert_xyz_d65(self, xyz: [f64; 3]) -> [f64; 3] {
+        match self {
+            Self::Srgb => linear_to_srgb(mul3(&XYZ_TO_LIN_SRGB, xyz)),
+            Self::LinearSrgb => mul3(&XYZ_TO_LIN_SRGB, xyz),
+            Self::DisplayP3 => linear_to_srgb(mul3(&XYZ_TO_LIN_P3, xyz)),
+            Self::A98Rgb => linear_to_a98(mul3(&XYZ_TO_LIN_A98, xyz)),
+            Self::ProphotoRgb => {
+                linear_to_prophoto(mul3(&XYZ_D50_TO_LIN_PROPHOTO, mul3(&D65_TO_D50, xyz)))
+            }
+            Self::Rec2020 => linear_to_rec2020(mul3(&XYZ_TO_LIN_REC2020, xyz)),
+            Self::Lab => xyz_d50_to_lab(mul3(&D65_TO_D50, xyz)),
+            Self::Lch => rectangular_to_polar(xyz_d50_to_lab(mul3(&D65_TO_D50, xyz))),
+            Self::Hsl => srgb_to_hsl(Self::Srgb.convert_xyz_d65(xyz)),
+            Self::Hwb => srgb_to_hwb(Self::Srgb.convert_xyz_d65(xyz)),
+            Self::Oklab => xyz_d65_to_oklab(xyz),
+            Self::Oklch => rectangular_to_polar(xyz_d65_to_oklab(xyz)),
+            Self::XyzD50 => mul3(&D65_TO_D50, xyz),
+            Self::XyzD65 => xyz,
+        }
+    }
+
+    /// Converts components in this color space to the `target` color space.
+    pub(crate) fn convert(self, target: Self, c: [f64; 3]) -> [f64; 3] {
+        if self == target {
+            return c;
+        }
+        // Shortcuts that avoid the round trip through XYZ.
+        match (self, target) {
+            (Self::Srgb, Self::Hsl) => return srgb_to_hsl(c),
+            (Self::Srgb, Self::Hwb) => return srgb_to_hwb(c),
+            (Self::Hsl, Self::Srgb) => return hsl_to_srgb(c),
+            (Self::Hwb, Self::Srgb) => return hwb_to_srgb(c),
+            (Self::Srgb, Self::LinearSrgb) => return srgb_to_linear(c),
+            (Self::LinearSrgb, Self::Srgb) => return linear_to_srgb(c),
+            (Self::Lab, Self::Lch) | (Self::Oklab, Self::Oklch) => return rectangular_to_polar(c),
+            (Self::Lch, Self::Lab) | (Self::Oklch, Self::Oklab) => return polar_to_rectangular(c),
+            _ => {}
+        }
+        target.convert_xyz_d65(self.to_xyz_d65(c))
+    }
+}
+
+/// Returns true if the sRGB components are within the gamut, allowing for a
+/// small amount of rounding error.
+fn srgb_in_gamut(c: [f64; 3]) -> bool {
+    const EPSILON: f64 = 1e-6;
+    c.iter().all(|x| (-EPSILON..=1.0 + EPSILON).contains(x))
+}
+
+fn delta_eok(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+}
+
+/// Maps a color into the sRGB gamut, returning sRGB components in `0.0..=1.0`.
+///
+/// This implements the binary search gamut mapping algorithm from CSS Color
+/// Module Level 4, which reduces Oklch chroma until the clipped result is
+/// within a just noticeable difference of the unclipped color:
+/// <https://www.w3.org/TR/css-color-4/#css-gamut-mapping>.
+pub(crate) fn gamut_map_srgb(space: ColorSpace, c: [f64; 3]) -> [f64; 3] {
+    const JND: f64 = 0.02;
+    const EPSILON: f64 = 0.0001;
+    let clip = |rgb: [f64; 3]| rgb.map(|x| x.clamp(0.0, 1.0));
+    let origin_srgb = space.convert(ColorSpace::Srgb, c);
+    if srgb_in_gamut(origin_srgb) {
+        return clip(origin_srgb);
+    }
+    let origin = space.convert(ColorSpace::Oklch, c);
+    if origin[0] >= 1.0 {
+        return [1.0; 3];
+    }
+    if origin[0] <= 0.0 {
+        return [0.0; 3];
+    }
+    let to_srgb = |lch: [f64; 3]| ColorSpace::Oklch.convert(ColorSpace::Srgb, lch);
+    let to_oklab = |rgb: [f64; 3]| ColorSpace::Srgb.convert(ColorSpace::Oklab, rgb);
+    let mut current = origin;
+    let mut clipped = clip(to_srgb(current));
+    if delta_eok(to_oklab(clipped), polar_to_rectangular(current)) < JND {
+        return clipped;
+    }
+    let mut min = 0.0;
+    let mut max = origin[1];
+    let mut min_in_gamut = true;
+    while max - min > EPSILON {
+        let chroma = (min + max) * 0.5;
+        current[1] = chroma;
+        let current_srgb = to_srgb(current);
+        if min_in_gamut && srgb_in_gamut(current_srgb) {
+            min = chroma;
+            continue;
+        }
+        clipped = clip(current_srgb);
+        let e = delta_eok(to_oklab(clipped), polar_to_rectangular(current));
+        if e < JND {
+            if JND - e < EPSILON {
+                return clipped;
+            }
+            min_in_gamut = false;
+            min = chroma;
+        } else {
+            max = chroma;
+        }
+    }
+    clipped
+}
+
+/// Interpolates between two colors given as components and alpha in `space`.
+///
+/// This follows the procedure of CSS Color Module Level 4: powerless hues take
+/// the hue of the other color, the other components are interpolated in
+/// premultiplied form and hues are interpolated with the given method:
+/// <https://www.w3.org/TR/css-color-4/#interpolation>.
+pub(crate) fn interpolate(
+    space: ColorSpace,
+    hue_interpolation: HueInterpolation,
+    a: ([f64; 3], f64),
+    b: ([f64; 3], f64),
+    t: f64,
+) -> ([f64; 3], f64) {
+    let (mut ca, alpha_a) = a;
+    let (mut cb, alpha_b) = b;
+    let hue_index = space.hue_index();
+    if let Some(h) = hue_index {
+        match (space.is_hue_powerless(ca), space.is_hue_powerless(cb)) {
+            (true, false) => ca[h] = cb[h],
+            (false, true) => cb[h] = ca[h],
+            _ => {}
+        }
+        let (mut ha, mut hb) = (normalize_hue(ca[h]), normalize_hue(cb[h]));
+        let d = hb - ha;
+        match hue_interpolation {
+            HueInterpolation::Shorter => {
+                if d > 180.0 {
+                    ha += 360.0;
+                } else if d < -180.0 {
+                    hb += 360.0;
+                }
+            }
+            HueInterpolation::Longer => {
+                if 0.0 < d && d < 180.0 {
+                    ha += 360.0;
+                } else if -180.0 < d && d <= 0.0 {
+                    hb += 360.0;
+                }
+            }
+            HueInterpolation::Increasing => {
+                if hb < ha {
+                    hb += 360.0;
+                }
+            }
+            HueInterpolation::Decreasing => {
+                if ha < hb {
+                    ha += 360.0;
+                }
+            }
+        }
+        ca[h] = ha;
+        cb[h] = hb;
+    }
+    let alpha = alpha_a + (alpha_b - alpha_a) * t;
+    let mut c = [0.0; 3];
+    for i in 0..3 {
+        c[i] = if Some(i) == hue_index {
+            normalize_hue(ca[i] + (cb[i] - ca[i]) * t)
+        } else if alpha == 0.0 {
+            ca[i] + (cb[i] - ca[i]) * t
+        } else {
+            let pa = ca[i] * alpha_a;
+            let pb = cb[i] * alpha_b;
+            (pa + (pb - pa) * t) / alpha
+        };
+    }
+    (c, alpha)
+}
@@ -0,0 +1,439 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Parsing of the functional color syntax from CSS Color Module Level 4.
+//!
+//! See <https://www.w3.org/TR/css-color-4/> for the grammar. Math functions
+//! such as `calc()` and the relative color syntax are not supported.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::color::{suggest, ColorParseError, ColorParseErrorKind};
+use super::colorspace::{gamut_map_srgb, ColorSpace};
+use super::{Color, DynamicColor};
+
+type Result<T> = core::result::Result<T, ColorParseError>;
+
+/// A single component of a color function.
+#[derive(Copy, Clone, Debug)]
+enum Value<'a> {
+    Number(f64),
+    Percentage(f64),
+    Dimension(f64, &'a str),
+    /// The `none` keyword, which represents a missing component.
+    None,
+}
+
+/// A component of a color function along with its byte offset in the input.
+#[derive(Copy, Clone, Debug)]
+struct Arg<'a> {
+    value: Value<'a>,
+    offset: usize,
+}
+
+impl Arg<'_> {
+    fn error(&self) -> ColorParseError {
+        ColorParseError::new(self.offset, ColorParseErrorKind::InvalidArgument)
+    }
+
+    /// Resolves a number or percentage, where `100%` maps to `scale`, and
+    /// `none` to NaN.
+    fn number_or_percentage(&self, scale: f64) -> Result<f64> {
+        match self.value {
+            Value::Number(n) => Ok(n),
+            Value::Percentage(p) => Ok(p * 0.01 * scale),
+            Value::None => Ok(f64::NAN),
+            Value::Dimension(..) => Err(self.error()),
+        }
+    }
+
+    /// Resolves a hue, returning an angle in degrees, or NaN for `none`.
+    fn hue(&self) -> Result<f64> {
+        match self.value {
+            Value::Number(n) => Ok(n),
+            Value::None => Ok(f64::NAN),
+            Value::Dimension(n, unit) => {
+                let scale = if unit.eq_ignore_ascii_case("deg") {
+                    1.0
+                } else if unit.eq_ignore_ascii_case("grad") {
+                    0.9
+                } else if unit.eq_ignore_ascii_case("rad") {
+                    180.0 / core::f64::consts::PI
+                } else if unit.eq_ignore_ascii_case("turn") {
+                    360.0
+                } else {
+                    return Err(self.error());
+                };
+                Ok(n * scale)
+            }
+            Value::Percentage(_) => Err(self.error()),
+        }
+    }
+
+    fn alpha(&self) -> Result<f64> {
+        self.number_or_percentage(1.0).map(|a| a.clamp(0.0, 1.0))
+    }
+}
+
+/// Cursor over the input string.
+pub(crate) struct Parser<'a> {
+    pub(crate) s: &'a str,
+    pub(crate) pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(s: &'a str, pos: usize) -> Self {
+        Self { s, pos }
+    }
+
+    fn error(&self, kind: ColorParseErrorKind) -> ColorParseError {
+        ColorParseError::new(self.pos, kind)
+    }
+
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    pub(crate) fn skip_ws(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    pub(crate) fn eat(&mut self, b: u8) -> bool {
+        self.skip_ws();
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, b: u8) -> Result<()> {
+        if self.eat(b) {
+            Ok(())
+        } else {
+            Err(self.error(ColorParseErrorKind::InvalidSyntax))
+        }
+    }
+
+    pub(crate) fn at_end(&mut self) -> bool {
+        self.skip_ws();
+        self.pos == self.s.len()
+    }
+
+    pub(crate) fn ident(&mut self) -> Option<&'a str> {
+        self.skip_ws();
+        let start = self.pos;
+        let bytes = self.s.as_bytes();
+        match bytes.get(self.pos) {
+            Some(b) if b.is_ascii_alphabetic() || *b == b'_' || *b == b'-' => {}
+            _ => return None,
+        }
+        while bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_' || *b == b'-')
+        {
+            self.pos += 1;
+        }
+        Some(&self.s[start..self.pos])
+    }
+
+    pub(crate) fn number(&mut self) -> Option<f64> {
+        self.skip_ws();
+        let bytes = self.s.as_bytes();
+        let digits = |pos: &mut usize| {
+            let start = *pos;
+            while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+                *pos += 1;
+            }
+            *pos > start
+        };
+        let start = self.pos;
+        let mut pos = self.pos;
+        if matches!(bytes.get(pos), Some(b'+' | b'-')) {
+            pos += 1;
+        }
+        let mut any = digits(&mut pos);
+        if bytes.get(pos) == Some(&b'.') && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit) {
+            pos += 1;
+            any |= digits(&mut pos);
+        }
+        if !any {
+            return None;
+        }
+        // Only consume an exponent when digits follow, so that units like
+        // `em` are not mistaken for one.
+        if matches!(bytes.get(pos), Some(b'e' | b'E')) {
+            let mut exp = pos + 1;
+            if matches!(bytes.get(exp), Some(b'+' | b'-')) {
+                exp += 1;
+            }
+            if digits(&mut exp) {
+                pos = exp;
+            }
+        }
+        let value = self.s[start..pos].parse::<f64>().ok()?;
+        self.pos = pos;
+        value.is_finite().then_some(value)
+    }
+
+    fn value(&mut self) -> Result<Arg<'a>> {
+        self.skip_ws();
+        let offset = self.pos;
+        let arg = |value| Ok(Arg { value, offset });
+        if let Some(n) = self.number() {
+            if self.peek() == Some(b'%') {
+                self.pos += 1;
+                return arg(Value::Percentage(n));
+            }
+            if self.peek().is_some_and(|b| b.is_ascii_alphabetic()) {
+                let unit = self.ident().unwrap_or_default();
+                return arg(Value::Dimension(n, unit));
+            }
+            return arg(Value::Number(n));
+        }
+        match self.ident() {
+            Some(ident) if ident.eq_ignore_ascii_case("none") => arg(Value::None),
+            _ => Err(ColorParseError::new(
+                offset,
+                ColorParseErrorKind::InvalidArgument,
+            )),
+        }
+    }
+}
+
+/// The arguments of a color function, along with whether the legacy comma
+/// separated syntax was used.
+struct Args<'a> {
+    values: [Arg<'a>; 3],
+    alpha: Option<Arg<'a>>,
+    legacy: bool,
+}
+
+impl Args<'_> {
+    fn alpha(&self) -> Result<f64> {
+        self.alpha.as_ref().map_or(Ok(1.0), Arg::alpha)
+    }
+}
+
+/// Parses the arguments of a color function up to and including the closing
+/// parenthesis.
+fn parse_args<'a>(p: &mut Parser<'a>, allow_legacy: bool) -> Result<Args<'a>> {
+    let first = p.value()?;
+    let legacy = allow_legacy && p.eat(b',');
+    let mut alpha = None;
+    let values = if legacy {
+        let second = p.value()?;
+        p.expect(b',')?;
+        let third = p.value()?;
+        if p.eat(b',') {
+            alpha = Some(p.value()?);
+        }
+        let values = [first, second, third];
+        // The `none` keyword is not valid in the legacy syntax.
+        if let Some(arg) = values
+            .iter()
+            .chain(&alpha)
+            .find(|arg| matches!(arg.value, Value::None))
+        {
+            return Err(arg.error());
+        }
+        values
+    } else {
+        let values = [first, p.value()?, p.value()?];
+        if p.eat(b'/') {
+            alpha = Some(p.value()?);
+        }
+        values
+    };
+    p.expect(b')')?;
+    Ok(Args {
+        values,
+        alpha,
+        legacy,
+    })
+}
+
+fn rgb(args: &Args<'_>) -> Result<[f64; 3]> {
+    if args.legacy {
+        // The legacy syntax requires all channels to be of the same type.
+        let is_number = matches!(args.values[0].value, Value::Number(_));
+        if let Some(arg) = args.values.iter().find(|arg| {
+            let ok = if is_number {
+                matches!(arg.value, Value::Number(_))
+            } else {
+                matches!(arg.value, Value::Percentage(_))
+            };
+            !ok
+        }) {
+            return Err(arg.error());
+        }
+    }
+    let channel = |arg: &Arg<'_>| {
+        arg.number_or_percentage(255.0)
+            .map(|c| c.clamp(0.0, 255.0) * (1.0 / 255.0))
+    };
+    let [r, g, b] = &args.values;
+    Ok([channel(r)?, channel(g)?, channel(b)?])
+}
+
+/// Parses the components of `hsl()` and `hwb()`.
+fn hsl_like(args: &Args<'_>) -> Result<[f64; 3]> {
+    let [h, c1, c2] = &args.values;
+    let percent = |arg: &Arg<'_>| {
+        if args.legacy && !matches!(arg.value, Value::Percentage(_)) {
+            return Err(arg.error());
+        }
+        arg.number_or_percentage(100.0).map(|c| c.clamp(0.0, 100.0))
+    };
+    Ok([h.hue()?, percent(c1)?, percent(c2)?])
+}
+
+/// Parses the components of `lab()`, `lch()`, `oklab()` and `oklch()`.
+///
+/// `l_scale` and `ab_scale` are the values that `100%` resolves to for the
+/// lightness and remaining components.
+fn lab_like(args: &Args<'_>, polar: bool, l_scale: f64, ab_scale: f64) -> Result<[f64; 3]> {
+    let [l, c1, c2] = &args.values;
+    let l = l.number_or_percentage(l_scale)?.clamp(0.0, l_scale);
+    let c1 = c1.number_or_percentage(ab_scale)?;
+    if polar {
+        // Unlike `f64::max`, this keeps a missing chroma as NaN.
+        let c1 = if c1 < 0.0 { 0.0 } else { c1 };
+        Ok([l, c1, c2.hue()?])
+    } else {
+        Ok([l, c1, c2.number_or_percentage(ab_scale)?])
+    }
+}
+
+/// Color spaces accepted by the `color()` function.
+pub(crate) const PREDEFINED_SPACES: &[(&str, ColorSpace)] = &[
+    ("srgb", ColorSpace::Srgb),
+    ("srgb-linear", ColorSpace::LinearSrgb),
+    ("display-p3", ColorSpace::DisplayP3),
+    ("a98-rgb", ColorSpace::A98Rgb),
+    ("prophoto-rgb", ColorSpace::ProphotoRgb),
+    ("rec2020", ColorSpace::Rec2020),
+    ("xyz", ColorSpace::XyzD65),
+    ("xyz-d50", ColorSpace::XyzD50),
+    ("xyz-d65", ColorSpace::XyzD65),
+];
+
+#[derive(Copy, Clone, Debug)]
+enum Function {
+    Rgb,
+    Hsl,
+    Hwb,
+    Lab,
+    Lch,
+    Oklab,
+    Oklch,
+    Color,
+}
+
+const FUNCTIONS: &[(&str, Function)] = &[
+    ("rgb", Function::Rgb),
+    ("rgba", Function::Rgb),
+    ("hsl", Function::Hsl),
+    ("hsla", Function::Hsl),
+    ("hwb", Function::Hwb),
+    ("lab", Function::Lab),
+    ("lch", Function::Lch),
+    ("oklab", Function::Oklab),
+    ("oklch", Function::Oklch),
+    ("color", Function::Color),
+];
+
+pub(crate) fn lookup<T: Copy>(table: &[(&'static str, T)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, value)| *value)
+}
+
+/// Parses a CSS color function such as `rgb(255 0 0 / 50%)` or
+/// `oklch(70% 0.1 120)`, starting at byte offset `start` of `s`.
+///
+/// Missing components are treated as zero, and colors outside of the sRGB
+/// gamut are mapped into it with the CSS gamut mapping algorithm.
+pub(crate) fn parse_color_function(s: &str, start: usize) -> Result<Color> {
+    let (space, components, a) = parse_function_components(s, start)?;
+    let zero_missing = |c: f64| if c.is_nan() { 0.0 } else { c };
+    let [r, g, b] = gamut_map_srgb(space, components.map(zero_missing));
+    Ok(Color::rgba(r, g, b, zero_missing(a)))
+}
+
+/// Parses a CSS color function like [`parse_color_function`], but keeps the
+/// color in the color space of the function, and missing components as NaN.
+pub(crate) fn parse_dynamic_color_function(s: &str, start: usize) -> Result<DynamicColor> {
+    let (space, components, a) = parse_function_components(s, start)?;
+    Ok(DynamicColor::new(
+        space,
+        components.map(|c| c as f32),
+        a as f32,
+    ))
+}
+
+/// Parses a CSS color function, returning the color space, components and
+/// alpha, where missing components are NaN.
+fn parse_function_components(s: &str, start: usize) -> Result<(ColorSpace, [f64; 3], f64)> {
+    let mut p = Parser::new(s, start);
+    let name = p
+        .ident()
+        .ok_or_else(|| p.error(ColorParseErrorKind::InvalidSyntax))?;
+    // Whitespace is not permitted between the function name and parenthesis.
+    if p.peek() != Some(b'(') {
+        return Err(p.error(ColorParseErrorKind::InvalidSyntax));
+    }
+    let function = lookup(FUNCTIONS, name).ok_or_else(|| {
+        ColorParseError::new(start, ColorParseErrorKind::UnknownFunction)
+            .with_suggestion(suggest(name, FUNCTIONS.iter().map(|(n, _)| *n)))
+    })?;
+    p.pos += 1;
+    let space = match function {
+        Function::Rgb => ColorSpace::Srgb,
+        Function::Hsl => ColorSpace::Hsl,
+        Function::Hwb => ColorSpace::Hwb,
+        Function::Lab => ColorSpace::Lab,
+        Function::Lch => ColorSpace::Lch,
+        Function::Oklab => ColorSpace::Oklab,
+        Function::Oklch => ColorSpace::Oklch,
+        Function::Color => {
+            p.skip_ws();
+            let offset = p.pos;
+            let space_name = p.ident().unwrap_or_default();
+            lookup(PREDEFINED_SPACES, space_name).ok_or_else(|| {
+                ColorParseError::new(offset, ColorParseErrorKind::InvalidArgument).with_suggestion(
+                    suggest(space_name, PREDEFINED_SPACES.iter().map(|(n, _)| *n)),
+                )
+            })?
+        }
+    };
+    let legacy = matches!(function, Function::Rgb | Function::Hsl);
+    let args = parse_args(&mut p, legacy)?;
+    let components = match function {
+        Function::Rgb => rgb(&args)?,
+        Function::Hsl | Function::Hwb => hsl_like(&args)?,
+        Function::Lab => lab_like(&args, false, 100.0, 125.0)?,
+        Function::Lch => lab_like(&args, true, 100.0, 150.0)?,
+        Function::Oklab => lab_like(&args, false, 1.0, 0.4)?,
+        Function::Oklch => lab_like(&args, true, 1.0, 0.4)?,
+        Function::Color => {
+            let [c0, c1, c2] = &args.values;
+            [
+                c0.number_or_percentage(1.0)?,
+                c1.number_or_percentage(1.0)?,
+                c2.number_or_percentage(1.0)?,
+            ]
+        }
+    };
+    let a = args.alpha()?;
+    if !p.at_end() {
+        return Err(p.error(ColorParseErrorKind::TrailingInput));
+    }
+    Ok((space, components, a))
+}
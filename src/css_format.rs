@@ -0,0 +1,1008 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Formatting of colors, gradients and brushes as CSS.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::color::color_name;
+use super::gradient_sample::DEGENERATE_TOLERANCE;
+use super::{
+    Brush, Color, ColorSpace, DynamicColor, Extend, Gradient, GradientKind, HueInterpolation,
+    TransformedBrush,
+};
+
+use kurbo::{Affine, Point, Rect};
+
+use core::fmt::{self, Write as _};
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Error returned when a brush cannot be [formatted](Brush::to_css) as CSS.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum CssFormatError {
+    /// The brush is an image, pattern or mesh gradient, which have no CSS
+    /// equivalent.
+    UnsupportedBrush,
+    /// The gradient uses [`Extend::Decal`], which CSS gradients cannot
+    /// express.
+    UnsupportedExtend,
+    /// The gradient geometry cannot be expressed, such as a radial gradient
+    /// whose circles do not share a center, or degenerate or non-finite
+    /// geometry.
+    UnsupportedGeometry,
+    /// The brush transform cannot be expressed, such as a rotated
+    /// elliptical gradient or a skewed sweep gradient.
+    UnsupportedTransform,
+    /// A color hint cannot be expressed, because it is on a segment that is
+    /// reversed, reflected, or cut at the edge of a repeating gradient.
+    UnsupportedHint,
+    /// The gradient has no color stops.
+    NoStops,
+}
+
+impl fmt::Display for CssFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::UnsupportedBrush => "brush has no CSS equivalent",
+            Self::UnsupportedExtend => "extend mode has no CSS equivalent",
+            Self::UnsupportedGeometry => "gradient geometry has no CSS equivalent",
+            Self::UnsupportedTransform => "brush transform has no CSS equivalent",
+            Self::UnsupportedHint => "color hint has no CSS equivalent",
+            Self::NoStops => "gradient has no color stops",
+        };
+        f.write_str(description)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CssFormatError {}
+
+type Result<T> = core::result::Result<T, CssFormatError>;
+
+impl Color {
+    /// Formats the color as the shortest CSS hexadecimal color, such as
+    /// `#f80`, `#ff8800` or `#ff880080`.
+    ///
+    /// The alpha digits are omitted for opaque colors.
+    #[must_use]
+    pub fn to_css_hex(self) -> String {
+        format_with(|out| write_hex(out, self))
+    }
+
+    /// Formats the color as a CSS `rgb()` function, such as `rgb(255 136 0)`,
+    /// or `rgb(255 136 0 / 0.5)` for translucent colors.
+    ///
+    /// Alpha is written with the fewest decimal places that
+    /// [parse](Color::parse) back to the same 8-bit value, which is at most
+    /// three.
+    #[must_use]
+    pub fn to_css_rgb(self) -> String {
+        format_with(|out| write_rgb(out, self))
+    }
+
+    /// Formats the color as CSS, choosing the shortest form.
+    ///
+    /// Opaque colors are written as their name or as a
+    /// [hexadecimal color](Self::to_css_hex), whichever is shorter, with the
+    /// name preferred when they are the same length. Translucent colors are
+    /// written with [`rgb()`](Self::to_css_rgb), which is more readable and
+    /// more widely supported than hexadecimal colors with alpha, except for
+    /// `transparent`. The result [parses](Color::parse) back to the same
+    /// color.
+    #[must_use]
+    pub fn to_css(self) -> String {
+        format_with(|out| write_color(out, self))
+    }
+}
+
+impl DynamicColor {
+    /// Formats the color as CSS without loss of precision.
+    ///
+    /// sRGB colors that are exactly representable as a [`Color`] are written
+    /// as by [`Color::to_css`]. Other colors use the CSS function of their
+    /// color space, such as `oklch(0.7 0.1 120)` or
+    /// `color(display-p3 1 0 0)`, with each component written with the
+    /// fewest digits that [parse](DynamicColor::parse) back to the same `f32`.
+    ///
+    /// Missing (NaN) components are written as `none`, and infinite
+    /// components as the largest finite value. Components outside of the
+    /// ranges accepted by CSS, such as a negative lightness, are clamped when
+    /// parsed. Use `self.to_color().to_css()` for a shorter form that is
+    /// gamut mapped to 8-bit sRGB.
+    #[must_use]
+    pub fn to_css(self) -> String {
+        format_with(|out| write_dynamic_color(out, self))
+    }
+}
+
+impl Gradient {
+    /// Formats the gradient as a CSS gradient function, resolved against a
+    /// reference box.
+    ///
+    /// Linear gradients are written as `linear-gradient()` with an angle,
+    /// radial gradients as a circular `radial-gradient()` and sweep gradients
+    /// as `conic-gradient()`, with stop positions in `px` or `deg` and
+    /// centers relative to the origin of `reference_box`. [`Extend::Repeat`]
+    /// uses the `repeating-` variants, and [`Extend::Reflect`] is written as a
+    /// repeating gradient over twice the period with mirrored stops. Stop
+    /// colors are written as by [`DynamicColor::to_css`], and the
+    /// interpolation method is written unless it is the CSS default for the
+    /// stops.
+    ///
+    /// The result [parses](Gradient::parse_css) against the same reference
+    /// box to a gradient that renders the same, up to floating point
+    /// precision, though its geometry and stops may be arranged differently.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CssFormatError`] if the gradient has no stops or cannot be
+    /// expressed in CSS, such as for [`Extend::Decal`] and radial gradients
+    /// whose circles do not share a center.
+    pub fn to_css(&self, reference_box: Rect) -> Result<String> {
+        self.to_css_transformed(Affine::IDENTITY, reference_box)
+    }
+
+    /// Formats the gradient painted with a brush transform that
+    /// [`transformed`](Self::transformed) cannot apply to its geometry.
+    fn to_css_transformed(&self, transform: Affine, reference_box: Rect) -> Result<String> {
+        if self.stops.is_empty() {
+            return Err(CssFormatError::NoStops);
+        }
+        let (stops, repeating) = match self.extend {
+            Extend::Pad => (self.clipped_stops()?, false),
+            Extend::Repeat => (self.clipped_stops()?, true),
+            Extend::Reflect => (reflect(self.clipped_stops()?)?, true),
+            Extend::Decal => return Err(CssFormatError::UnsupportedExtend),
+        };
+        let geometry = GradientGeometry::new(self.kind, transform, reference_box)?;
+        let mut positions: Vec<(f64, &Stop)> = stops
+            .iter()
+            .map(|stop| (geometry.position(stop.t), stop))
+            .collect();
+        if !positions.iter().all(|(position, _)| position.is_finite()) {
+            return Err(CssFormatError::UnsupportedGeometry);
+        }
+        let reversed = geometry.is_reversed();
+        if reversed {
+            if stops.iter().any(|stop| stop.hint.is_some()) {
+                return Err(CssFormatError::UnsupportedHint);
+            }
+            positions.reverse();
+        }
+        let legacy = stops.iter().all(|stop| exact_color(stop.color).is_some());
+        Ok(format_with(|out| {
+            if repeating {
+                out.push_str("repeating-");
+            }
+            geometry.write_prelude(out, reference_box)?;
+            if self.interpolation_space != ColorSpace::Srgb || !legacy {
+                write!(out, " in {}", space_name(self.interpolation_space))?;
+                if self.interpolation_space.is_polar() {
+                    match self.hue_interpolation {
+                        HueInterpolation::Shorter => {}
+                        HueInterpolation::Longer => out.push_str(" longer hue"),
+                        HueInterpolation::Increasing => out.push_str(" increasing hue"),
+                        HueInterpolation::Decreasing => out.push_str(" decreasing hue"),
+                    }
+                }
+            }
+            let unit = geometry.unit();
+            for (i, (position, stop)) in positions.iter().enumerate() {
+                out.push_str(", ");
+                write_dynamic_color(out, stop.color)?;
+                write!(out, " {position}{unit}")?;
+                let next = positions.get(i + 1).map(|(next, _)| *next);
+                if let (Some(hint), Some(next)) = (stop.hint, next) {
+                    if next > *position {
+                        let hint = position + f64::from(hint) * (next - position);
+                        write!(out, ", {hint}{unit}")?;
+                    }
+                }
+            }
+            out.push(')');
+            Ok(())
+        }))
+    }
+
+    /// Returns the stops over the range `0.0` to `1.0` of the gradient
+    /// parameter, which is all that the extend modes other than decal use.
+    ///
+    /// Stops outside of the range are replaced with stops that hold the
+    /// colors at its edges. Repeating gradients always have stops at the
+    /// edges, as CSS repeats the range between the first and last stops.
+    fn clipped_stops(&self) -> Result<Vec<Stop>> {
+        let stops = &self.stops;
+        let pad = self.extend == Extend::Pad;
+        let keep = |i: usize| -> Stop {
+            let kept = &stops[i];
+            Stop {
+                t: kept.offset.into(),
+                color: kept.color,
+                hint: kept.hint,
+            }
+        };
+        // Color at `t` between stops `i` and `i + 1`, which must not have a
+        // hint, as the cut segment would no longer follow the hint curve.
+        let cut = |i: usize, t: f32| {
+            let (a, b) = (&stops[i], &stops[i + 1]);
+            if a.hint.is_some() {
+                return Err(CssFormatError::UnsupportedHint);
+            }
+            let local = (t - a.offset) / (b.offset - a.offset);
+            let color = a.color.interpolate(
+                b.color,
+                local,
+                self.interpolation_space,
+                self.hue_interpolation,
+            );
+            Ok(Stop::at(t.into(), color))
+        };
+        let mut clipped = Vec::with_capacity(stops.len() + 2);
+        // The color just after the start, where the last of several stops
+        // at the same offset wins.
+        match stops.iter().rposition(|stop| stop.offset <= 0.0) {
+            Some(i) if stops[i].offset == 0.0 => clipped.push(keep(i)),
+            Some(i) if i + 1 < stops.len() => clipped.push(cut(i, 0.0)?),
+            Some(i) => clipped.push(Stop::at(0.0, stops[i].color)),
+            None if pad => {}
+            None => clipped.push(Stop::at(0.0, stops[0].color)),
+        }
+        clipped.extend(
+            (0..stops.len())
+                .filter(|&i| stops[i].offset > 0.0 && stops[i].offset < 1.0)
+                .map(keep),
+        );
+        // The color just before the end. Padding uses the color at the end,
+        // which is the last of several stops at the same offset.
+        match stops.iter().position(|stop| stop.offset >= 1.0) {
+            Some(i) if stops[i].offset == 1.0 && pad => {
+                clipped.extend(
+                    (i..stops.len())
+                        .filter(|&j| stops[j].offset == 1.0)
+                        .map(keep),
+                );
+            }
+            Some(i) if stops[i].offset == 1.0 || i == 0 => {
+                clipped.push(Stop::at(1.0, stops[i].color));
+            }
+            Some(i) => clipped.push(cut(i - 1, 1.0)?),
+            None if pad => {}
+            None => clipped.push(Stop::at(1.0, stops[stops.len() - 1].color)),
+        }
+        // CSS requires at least two stops.
+        if let [only] = &clipped[..] {
+            clipped.push(Stop::at(only.t, only.color));
+        }
+        Ok(clipped)
+    }
+}
+
+impl Brush {
+    /// Formats the brush as CSS, resolving gradients against a reference box.
+    ///
+    /// Solid colors are formatted with [`Color::to_css`] or
+    /// [`DynamicColor::to_css`] and gradients with [`Gradient::to_css`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CssFormatError`] for image, pattern and mesh brushes, and
+    /// for gradients that cannot be expressed in CSS.
+    pub fn to_css(&self, reference_box: Rect) -> Result<String> {
+        match self {
+            Self::Solid(color) => Ok(color.to_css()),
+            Self::DynamicSolid(color) => Ok(color.to_css()),
+            Self::Gradient(gradient) => gradient.to_css(reference_box),
+            Self::Image(_) | Self::Pattern(_) | Self::Mesh(_) => {
+                Err(CssFormatError::UnsupportedBrush)
+            }
+        }
+    }
+}
+
+impl TransformedBrush {
+    /// Formats the brush as CSS, resolving gradients against a reference box
+    /// in the space of the shape.
+    ///
+    /// This is the inverse of [`Gradient::parse_css`] together with its
+    /// transform. Transforms that [`Gradient::transformed`] can apply are
+    /// applied to the geometry, rotations of sweep gradients are written as
+    /// the `from` angle of `conic-gradient()`, and axis-aligned scales of
+    /// radial gradients as an `ellipse` with two radii. Otherwise, this
+    /// formats the brush as [`Brush::to_css`] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CssFormatError`] for the same brushes as
+    /// [`Brush::to_css`], and
+    /// [`UnsupportedTransform`](CssFormatError::UnsupportedTransform) for
+    /// gradients with other transforms, such as rotated ellipses.
+    pub fn to_css(&self, reference_box: Rect) -> Result<String> {
+        match (&self.brush, self.transform) {
+            (Brush::Gradient(gradient), Some(transform)) => match gradient.transformed(transform) {
+                Some(gradient) => gradient.to_css(reference_box),
+                None => gradient.to_css_transformed(transform, reference_box),
+            },
+            (brush, _) => brush.to_css(reference_box),
+        }
+    }
+}
+
+/// A color stop with its gradient parameter.
+struct Stop {
+    t: f64,
+    color: DynamicColor,
+    hint: Option<f32>,
+}
+
+impl Stop {
+    fn at(t: f64, color: DynamicColor) -> Self {
+        Self {
+            t,
+            color,
+            hint: None,
+        }
+    }
+}
+
+/// Mirrors the stops of a period, doubling its length.
+fn reflect(mut period: Vec<Stop>) -> Result<Vec<Stop>> {
+    if period.iter().any(|stop| stop.hint.is_some()) {
+        return Err(CssFormatError::UnsupportedHint);
+    }
+    let mirrored: Vec<_> = period
+        .iter()
+        .rev()
+        .skip(1)
+        .map(|stop| Stop::at(2.0 - stop.t, stop.color))
+        .collect();
+    period.extend(mirrored);
+    Ok(period)
+}
+
+/// Geometry of a gradient as a CSS gradient function.
+enum GradientGeometry {
+    /// A linear gradient with an angle in degrees, where parameter `t` is at
+    /// `base + t * length` pixels along the gradient line.
+    Linear { angle: f64, base: f64, length: f64 },
+    /// A radial gradient, where parameter `t` is at a horizontal radius of
+    /// `start + t * (end - start)` pixels, and the vertical radius is
+    /// `aspect` times the horizontal radius.
+    Radial {
+        center: Point,
+        start: f64,
+        end: f64,
+        aspect: f64,
+    },
+    /// A conic gradient starting at `from` degrees clockwise from the top,
+    /// where parameter `t` is at `start + t * (end - start)` degrees from
+    /// there.
+    Conic {
+        center: Point,
+        from: f64,
+        start: f64,
+        end: f64,
+    },
+}
+
+impl GradientGeometry {
+    /// Returns the geometry of a gradient painted with `transform`, which
+    /// must be the identity, an axis-aligned scale and translation of a
+    /// radial gradient, or a rotation, positive uniform scale and
+    /// translation of a sweep gradient.
+    fn new(kind: GradientKind, transform: Affine, bounds: Rect) -> Result<Self> {
+        let [a, b, c, d, _, _] = transform.as_coeffs();
+        // Tolerances are relative to the scale of the transform.
+        let tolerance = 1e-9 * (a * a + b * b + c * c + d * d).max(f64::MIN_POSITIVE);
+        if !transform.is_finite() || transform.determinant().abs() <= tolerance {
+            return Err(CssFormatError::UnsupportedTransform);
+        }
+        let geometry = match kind {
+            GradientKind::Linear { .. } if transform != Affine::IDENTITY => {
+                return Err(CssFormatError::UnsupportedTransform);
+            }
+            GradientKind::Linear { start, end } => {
+                let line = end - start;
+                let length = line.hypot();
+                if !(line.is_finite() && length * length > DEGENERATE_TOLERANCE) {
+                    return Err(CssFormatError::UnsupportedGeometry);
+                }
+                let dir = line / length;
+                // The CSS gradient line passes through the center of the box,
+                // with a length such that the corners are at its ends.
+                let css_length = (bounds.width() * dir.x).abs() + (bounds.height() * dir.y).abs();
+                let css_start = bounds.center() - dir * (0.5 * css_length);
+                Self::Linear {
+                    angle: dir.x.atan2(-dir.y).to_degrees(),
+                    base: (start - css_start).dot(dir),
+                    length,
+                }
+            }
+            GradientKind::Radial {
+                start_center,
+                start_radius,
+                end_center,
+                end_radius,
+            } => {
+                let (start, end) = (f64::from(start_radius), f64::from(end_radius));
+                if (end_center - start_center).hypot2() > DEGENERATE_TOLERANCE
+                    || start < 0.0
+                    || end < 0.0
+                    || (end - start).abs() <= DEGENERATE_TOLERANCE
+                {
+                    return Err(CssFormatError::UnsupportedGeometry);
+                }
+                if b.abs() > tolerance.sqrt() || c.abs() > tolerance.sqrt() {
+                    return Err(CssFormatError::UnsupportedTransform);
+                }
+                let (sx, sy) = (a.abs(), d.abs());
+                Self::Radial {
+                    center: transform * start_center,
+                    start: start * sx,
+                    end: end * sx,
+                    aspect: sy / sx,
+                }
+            }
+            GradientKind::Sweep {
+                center,
+                start_angle,
+                end_angle,
+            } => {
+                let (start, end) = (f64::from(start_angle), f64::from(end_angle));
+                if (end - start).abs() <= DEGENERATE_TOLERANCE {
+                    return Err(CssFormatError::UnsupportedGeometry);
+                }
+                let is_rotation = (a - d).abs() <= tolerance.sqrt()
+                    && (b + c).abs() <= tolerance.sqrt()
+                    && transform.determinant() > 0.0;
+                if !is_rotation {
+                    return Err(CssFormatError::UnsupportedTransform);
+                }
+                // Sweep gradients start at the positive x-axis, while CSS
+                // angles start at the top.
+                Self::Conic {
+                    center: transform * center,
+                    from: 90.0 + b.atan2(a).to_degrees(),
+                    start: start.to_degrees(),
+                    end: end.to_degrees(),
+                }
+            }
+        };
+        Ok(geometry)
+    }
+
+    /// Returns the CSS position of the gradient parameter `t`.
+    fn position(&self, t: f64) -> f64 {
+        match *self {
+            Self::Linear { base, length, .. } => base + t * length,
+            Self::Radial { start, end, .. } | Self::Conic { start, end, .. } => {
+                start + t * (end - start)
+            }
+        }
+    }
+
+    /// Returns true if positions decrease with the gradient parameter.
+    fn is_reversed(&self) -> bool {
+        match *self {
+            Self::Linear { .. } => false,
+            Self::Radial { start, end, .. } | Self::Conic { start, end, .. } => end < start,
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            Self::Linear { .. } | Self::Radial { .. } => "px",
+            Self::Conic { .. } => "deg",
+        }
+    }
+
+    /// Writes the gradient function name and the part of its arguments
+    /// before the color stops.
+    fn write_prelude(&self, out: &mut String, bounds: Rect) -> fmt::Result {
+        match *self {
+            Self::Linear { angle, .. } => write!(out, "linear-gradient({angle}deg"),
+            Self::Radial {
+                center,
+                start,
+                end,
+                aspect,
+            } => {
+                let (x, y) = (center.x - bounds.x0, center.y - bounds.y0);
+                let radius = start.max(end);
+                if aspect == 1.0 {
+                    write!(out, "radial-gradient(circle {radius}px at {x}px {y}px")
+                } else {
+                    let ry = radius * aspect;
+                    write!(
+                        out,
+                        "radial-gradient(ellipse {radius}px {ry}px at {x}px {y}px"
+                    )
+                }
+            }
+            Self::Conic { center, from, .. } => {
+                let (x, y) = (center.x - bounds.x0, center.y - bounds.y0);
+                write!(out, "conic-gradient(from {from}deg at {x}px {y}px")
+            }
+        }
+    }
+}
+
+/// Runs a formatting function on a new string.
+fn format_with(f: impl FnOnce(&mut String) -> fmt::Result) -> String {
+    let mut out = String::new();
+    // Writing to a string cannot fail.
+    let _ = f(&mut out);
+    out
+}
+
+fn write_hex(out: &mut String, color: Color) -> fmt::Result {
+    let channels = [color.r, color.g, color.b, color.a];
+    let channels = if color.a == 255 {
+        &channels[..3]
+    } else {
+        &channels[..]
+    };
+    out.push('#');
+    if channels.iter().all(|c| c >> 4 == c & 0xf) {
+        for c in channels {
+            write!(out, "{:x}", c & 0xf)?;
+        }
+    } else {
+        for c in channels {
+            write!(out, "{c:02x}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_rgb(out: &mut String, color: Color) -> fmt::Result {
+    write!(out, "rgb({} {} {}", color.r, color.g, color.b)?;
+    if color.a != 255 {
+        let a = f64::from(color.a) / 255.0;
+        // Three decimal places are always enough to recover 8 bits.
+        let alpha = [10.0, 100.0, 1000.0]
+            .into_iter()
+            .map(|scale: f64| (a * scale).round() / scale)
+            .find(|alpha| (alpha * 255.0).round() as u8 == color.a)
+            .unwrap_or(a);
+        write!(out, " / {alpha}")?;
+    }
+    out.push(')');
+    Ok(())
+}
+
+fn write_color(out: &mut String, color: Color) -> fmt::Result {
+    let name = color_name(color);
+    if color.a == 255 {
+        let hex_len = if [color.r, color.g, color.b]
+            .iter()
+            .all(|c| c >> 4 == c & 0xf)
+        {
+            4
+        } else {
+            7
+        };
+        match name {
+            Some(name) if name.len() <= hex_len => out.push_str(name),
+            _ => write_hex(out, color)?,
+        }
+        return Ok(());
+    }
+    match name {
+        Some(name) => out.push_str(name),
+        None => write_rgb(out, color)?,
+    }
+    Ok(())
+}
+
+fn write_dynamic_color(out: &mut String, color: DynamicColor) -> fmt::Result {
+    if let Some(color) = exact_color(color) {
+        return write_color(out, color);
+    }
+    let function = match color.space {
+        ColorSpace::Lab => "lab(",
+        ColorSpace::Lch => "lch(",
+        ColorSpace::Hsl => "hsl(",
+        ColorSpace::Hwb => "hwb(",
+        ColorSpace::Oklab => "oklab(",
+        ColorSpace::Oklch => "oklch(",
+        space => {
+            write!(out, "color({} ", space_name(space))?;
+            ""
+        }
+    };
+    out.push_str(function);
+    for (i, c) in color.components.into_iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write_component(out, c)?;
+    }
+    if color.alpha != 1.0 {
+        out.push_str(" / ");
+        write_component(out, color.alpha)?;
+    }
+    out.push(')');
+    Ok(())
+}
+
+fn write_component(out: &mut String, c: f32) -> fmt::Result {
+    if c.is_nan() {
+        out.push_str("none");
+        Ok(())
+    } else {
+        write!(out, "{}", c.clamp(f32::MIN, f32::MAX))
+    }
+}
+
+/// Returns the 8-bit color that converts to exactly the given color, if any.
+fn exact_color(color: DynamicColor) -> Option<Color> {
+    if color.space != ColorSpace::Srgb {
+        return None;
+    }
+    let [r, g, b] = color.components;
+    let quantized = Color::rgba(r.into(), g.into(), b.into(), color.alpha.into());
+    (DynamicColor::from(quantized) == color).then_some(quantized)
+}
+
+/// Returns the name of the color space in the `color()` function and in
+/// interpolation methods.
+fn space_name(space: ColorSpace) -> &'static str {
+    match space {
+        ColorSpace::Srgb => "srgb",
+        ColorSpace::LinearSrgb => "srgb-linear",
+        ColorSpace::DisplayP3 => "display-p3",
+        ColorSpace::A98Rgb => "a98-rgb",
+        ColorSpace::ProphotoRgb => "prophoto-rgb",
+        ColorSpace::Rec2020 => "rec2020",
+        ColorSpace::Lab => "lab",
+        ColorSpace::Lch => "lch",
+        ColorSpace::Hsl => "hsl",
+        ColorSpace::Hwb => "hwb",
+        ColorSpace::Oklab => "oklab",
+        ColorSpace::Oklch => "oklch",
+        ColorSpace::XyzD50 => "xyz-d50",
+        ColorSpace::XyzD65 => "xyz-d65",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{color_name, NAMED_COLORS};
+    use crate::{
+        Brush, Color, ColorSpace, ColorStop, CssFormatError, DynamicColor, Extend, Gradient,
+        TransformedBrush,
+    };
+    use kurbo::{Affine, Point, Rect};
+
+    extern crate alloc;
+    use alloc::format;
+
+    const BOX: Rect = Rect::new(10.0, 20.0, 110.0, 70.0);
+
+    /// Formats a parsed gradient with its transform.
+    fn format(css: &str) -> String {
+        let (gradient, transform) = Gradient::parse_css(css, BOX).unwrap();
+        TransformedBrush::new(gradient, Some(transform))
+            .to_css(BOX)
+            .unwrap()
+    }
+
+    /// Checks that the CSS formatted from a parsed gradient parses back to a
+    /// gradient that paints the same colors.
+    #[track_caller]
+    fn assert_round_trip(css: &str) -> String {
+        let formatted = format(css);
+        let (original, original_transform) = Gradient::parse_css(css, BOX).unwrap();
+        assert_paints_same(css, &original, original_transform, &formatted);
+        formatted
+    }
+
+    /// Checks that formatted CSS parses to a gradient that paints the same
+    /// colors over the reference box as the original gradient.
+    #[track_caller]
+    fn assert_paints_same(
+        css: &str,
+        original: &Gradient,
+        original_transform: Affine,
+        formatted: &str,
+    ) {
+        let (parsed, parsed_transform) = Gradient::parse_css(formatted, BOX).unwrap();
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let point = Point::new(
+                    BOX.x0 + BOX.width() * f64::from(i) / 10.0,
+                    BOX.y0 + BOX.height() * f64::from(j) / 10.0,
+                );
+                let expected = original
+                    .sample(original_transform.inverse() * point)
+                    .to_color();
+                let actual = parsed.sample(parsed_transform.inverse() * point).to_color();
+                let diff = [
+                    expected.r.abs_diff(actual.r),
+                    expected.g.abs_diff(actual.g),
+                    expected.b.abs_diff(actual.b),
+                    expected.a.abs_diff(actual.a),
+                ];
+                assert!(
+                    diff.iter().all(|d| *d <= 1),
+                    "{css} formatted as {formatted} differs at {point:?}: {expected:?} != {actual:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn color_forms() {
+        let orange = Color::rgb8(0xff, 0x88, 0x00);
+        assert_eq!(orange.to_css_hex(), "#f80", "short hex");
+        assert_eq!(orange.to_css_rgb(), "rgb(255 136 0)", "opaque rgb");
+        assert_eq!(orange.to_css(), "#f80", "hex shorter than name");
+        let translucent = Color::rgba8(0x12, 0x34, 0x56, 0x80);
+        assert_eq!(translucent.to_css_hex(), "#12345680", "long hex");
+        assert_eq!(translucent.to_css(), "rgb(18 52 86 / 0.5)", "translucent");
+        assert_eq!(
+            Color::rgba8(0x11, 0x22, 0x33, 0x44).to_css_hex(),
+            "#1234",
+            "short hex with alpha"
+        );
+        assert_eq!(Color::RED.to_css(), "red", "name shorter than hex");
+        assert_eq!(Color::TRANSPARENT.to_css(), "transparent", "transparent");
+        assert_eq!(
+            Color::rgba8(255, 0, 0, 0).to_css(),
+            "rgb(255 0 0 / 0)",
+            "transparent color that is not black"
+        );
+    }
+
+    #[test]
+    fn named_colors() {
+        for &(name, color) in NAMED_COLORS {
+            let formatted = color.to_css();
+            assert_eq!(
+                Color::parse(&formatted),
+                Ok(color),
+                "{name} formatted as {formatted}"
+            );
+            if color.a == 255 {
+                let hex = color.to_css_hex();
+                assert!(
+                    formatted.len() <= hex.len(),
+                    "{name} formatted as {formatted}"
+                );
+                if formatted == hex {
+                    assert!(
+                        NAMED_COLORS
+                            .iter()
+                            .all(|(n, c)| *c != color || n.len() > hex.len()),
+                        "{name} has a name no longer than {hex}"
+                    );
+                } else {
+                    assert_eq!(
+                        color_name(color),
+                        Some(formatted.as_str()),
+                        "{name} formatted as the shortest name"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_alpha() {
+        for a in 0..=255 {
+            let color = Color::rgba8(10, 20, 30, a);
+            for formatted in [color.to_css_hex(), color.to_css_rgb(), color.to_css()] {
+                assert_eq!(
+                    Color::parse(&formatted),
+                    Ok(color),
+                    "alpha {a} formatted as {formatted}"
+                );
+            }
+            let rgb = color.to_css_rgb();
+            let Some(alpha) = rgb
+                .strip_prefix("rgb(10 20 30 / ")
+                .and_then(|rest| rest.strip_suffix(')'))
+            else {
+                assert_eq!(a, 255, "alpha is omitted only when opaque: {rgb}");
+                continue;
+            };
+            let decimals = alpha.split_once('.').map_or(0, |(_, d)| d.len());
+            assert!(decimals <= 3, "alpha {a} formatted as {rgb}");
+            if decimals > 1 {
+                // Rounding to fewer decimal places gives a different alpha.
+                let shorter = format!("rgb(10 20 30 / {:.*})", decimals - 1, f64::from(a) / 255.0);
+                assert_ne!(
+                    Color::parse(&shorter).map(|c| c.a),
+                    Ok(a),
+                    "alpha {a} formatted as {rgb}, but {shorter} is shorter"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dynamic_colors() {
+        let missing = DynamicColor::new(ColorSpace::Oklch, [0.5, f32::NAN, 120.0], f32::NAN);
+        let missing_css = missing.to_css();
+        assert_eq!(
+            missing_css, "oklch(0.5 none 120 / none)",
+            "missing components"
+        );
+        let parsed = DynamicColor::parse(&missing_css).unwrap();
+        assert!(
+            parsed.components[1].is_nan() && parsed.alpha.is_nan(),
+            "{missing_css} parsed as {parsed:?}"
+        );
+
+        let infinite =
+            DynamicColor::new(ColorSpace::Oklab, [0.5, f32::INFINITY, -f32::INFINITY], 1.0);
+        let infinite_css = infinite.to_css();
+        assert!(
+            !infinite_css.contains("inf"),
+            "infinity is clamped: {infinite_css}"
+        );
+        assert_eq!(
+            DynamicColor::parse(&infinite_css),
+            Ok(DynamicColor::new(
+                ColorSpace::Oklab,
+                [0.5, f32::MAX, -f32::MAX],
+                1.0
+            )),
+            "{infinite_css}"
+        );
+
+        let srgb = DynamicColor::from(Color::rgba8(255, 136, 0, 128));
+        assert_eq!(srgb.to_css(), "rgb(255 136 0 / 0.5)", "8-bit sRGB color");
+
+        for (space, prefix) in [
+            (ColorSpace::Srgb, "color(srgb "),
+            (ColorSpace::LinearSrgb, "color(srgb-linear "),
+            (ColorSpace::DisplayP3, "color(display-p3 "),
+            (ColorSpace::A98Rgb, "color(a98-rgb "),
+            (ColorSpace::ProphotoRgb, "color(prophoto-rgb "),
+            (ColorSpace::Rec2020, "color(rec2020 "),
+            (ColorSpace::Lab, "lab("),
+            (ColorSpace::Lch, "lch("),
+            (ColorSpace::Hsl, "hsl("),
+            (ColorSpace::Hwb, "hwb("),
+            (ColorSpace::Oklab, "oklab("),
+            (ColorSpace::Oklch, "oklch("),
+            (ColorSpace::XyzD50, "color(xyz-d50 "),
+            (ColorSpace::XyzD65, "color(xyz-d65 "),
+        ] {
+            let color = DynamicColor::new(space, [0.1, 0.2, 0.3], 0.4);
+            let formatted = color.to_css();
+            assert!(
+                formatted.starts_with(prefix),
+                "{space:?} formatted as {formatted}"
+            );
+            assert_eq!(DynamicColor::parse(&formatted), Ok(color), "{formatted}");
+        }
+    }
+
+    #[test]
+    fn reflect() {
+        let linear = Gradient::new_linear((20.0, 20.0), (50.0, 20.0))
+            .with_extend(Extend::Reflect)
+            .with_stops([Color::RED, Color::BLUE]);
+        let linear_css = linear.to_css(BOX).unwrap();
+        assert!(
+            linear_css.starts_with("repeating-linear-gradient("),
+            "{linear_css}"
+        );
+        assert_paints_same("reflected linear", &linear, Affine::IDENTITY, &linear_css);
+        let radial = Gradient::new_radial((40.0, 40.0), 15.0)
+            .with_extend(Extend::Reflect)
+            .with_stops([(0.2, Color::RED), (0.6, Color::LIME), (0.9, Color::BLUE)]);
+        let radial_css = radial.to_css(BOX).unwrap();
+        assert!(
+            radial_css.starts_with("repeating-radial-gradient("),
+            "{radial_css}"
+        );
+        assert_paints_same("reflected radial", &radial, Affine::IDENTITY, &radial_css);
+    }
+
+    #[test]
+    fn unsupported_hints() {
+        let stops = [
+            ColorStop::from((-0.5, Color::RED)).with_hint(0.25),
+            ColorStop::from((1.5, Color::BLUE)),
+        ];
+        let linear = Gradient::new_linear((20.0, 20.0), (50.0, 20.0)).with_stops(stops);
+        for extend in [Extend::Repeat, Extend::Reflect] {
+            assert_eq!(
+                linear.clone().with_extend(extend).to_css(BOX),
+                Err(CssFormatError::UnsupportedHint),
+                "hint on a cut segment with {extend:?}"
+            );
+        }
+        let inside = Gradient::new_linear((20.0, 20.0), (50.0, 20.0)).with_stops([
+            ColorStop::from((0.0, Color::RED)).with_hint(0.25),
+            ColorStop::from((1.0, Color::BLUE)),
+        ]);
+        assert!(inside.to_css(BOX).is_ok(), "hint on a whole segment");
+        assert_eq!(
+            inside.with_extend(Extend::Reflect).to_css(BOX),
+            Err(CssFormatError::UnsupportedHint),
+            "hint on a reflected segment"
+        );
+    }
+
+    #[test]
+    fn conic_from() {
+        let formatted = assert_round_trip("conic-gradient(from 30deg at 20px 10px, red, blue)");
+        assert!(
+            formatted.starts_with("conic-gradient(from 30"),
+            "{formatted}"
+        );
+        assert_round_trip("repeating-conic-gradient(from -135deg, red, blue 90deg)");
+        assert_round_trip("conic-gradient(red, blue)");
+    }
+
+    #[test]
+    fn ellipse() {
+        let formatted =
+            assert_round_trip("radial-gradient(ellipse 40px 20px at 50px 30px, red, blue)");
+        assert_eq!(
+            formatted, "radial-gradient(ellipse 40px 20px at 50px 30px, red 0px, blue 40px)",
+            "axis-aligned ellipse"
+        );
+        assert_round_trip("repeating-radial-gradient(ellipse, red, blue 10%, red 20%)");
+        assert_round_trip("radial-gradient(circle 30px, red, blue)");
+    }
+
+    #[test]
+    fn unsupported_transform() {
+        let (gradient, transform) =
+            Gradient::parse_css("radial-gradient(ellipse 40px 20px, red, blue)", BOX).unwrap();
+        let rotated = TransformedBrush::new(gradient, Some(Affine::rotate(0.5) * transform));
+        assert_eq!(
+            rotated.to_css(BOX),
+            Err(CssFormatError::UnsupportedTransform),
+            "rotated ellipse"
+        );
+        let sweep =
+            Gradient::new_sweep((50.0, 50.0), 0.0, 1.0).with_stops([Color::RED, Color::BLUE]);
+        let skewed = TransformedBrush::new(sweep.clone(), Some(Affine::skew(0.5, 0.0)));
+        assert_eq!(
+            skewed.to_css(BOX),
+            Err(CssFormatError::UnsupportedTransform),
+            "skewed sweep"
+        );
+        let mirrored = TransformedBrush::new(sweep, Some(Affine::FLIP_Y));
+        assert_eq!(
+            mirrored.to_css(BOX),
+            Err(CssFormatError::UnsupportedTransform),
+            "mirrored sweep"
+        );
+        let linear =
+            Gradient::new_linear((0.0, 0.0), (10.0, 0.0)).with_stops([Color::RED, Color::BLUE]);
+        let collapsed = TransformedBrush::new(linear, Some(Affine::scale(0.0)));
+        assert_eq!(
+            collapsed.to_css(BOX),
+            Err(CssFormatError::UnsupportedTransform),
+            "non-invertible transform"
+        );
+    }
+
+    #[test]
+    fn other_brushes() {
+        let solid = TransformedBrush::new(Color::RED, Some(Affine::rotate(1.0)));
+        assert_eq!(solid.to_css(BOX).as_deref(), Ok("red"), "solid color");
+        let linear =
+            Gradient::new_linear((10.0, 20.0), (110.0, 20.0)).with_stops([Color::RED, Color::BLUE]);
+        let identity = TransformedBrush::new(linear.clone(), None);
+        assert_eq!(
+            identity.to_css(BOX),
+            Brush::Gradient(linear).to_css(BOX),
+            "no transform"
+        );
+        assert_round_trip("linear-gradient(30deg, red, blue)");
+    }
+}
@@ -0,0 +1,1673 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Parsing of the gradient functions from CSS Images Module Level 4.
+//!
+//! See <https://www.w3.org/TR/css-images-4/#gradients> for the grammar. As
+//! for colors, math functions such as `calc()` are not supported, and lengths
+//! relative to fonts or the viewport cannot be resolved.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::color::{suggest, ColorParseErrorKind};
+use super::css::{lookup, Parser, PREDEFINED_SPACES};
+use super::{
+    ColorSpace, ColorStop, ColorStops, DynamicColor, Extend, Gradient, GradientKind,
+    HueInterpolation,
+};
+
+use kurbo::{Affine, Point, Rect, Vec2};
+use smallvec::SmallVec;
+
+use core::f64::consts::TAU;
+use core::fmt;
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+type Result<T> = core::result::Result<T, GradientParseError>;
+
+/// The kind of problem encountered when [parsing](Gradient::parse_css) a
+/// gradient.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum GradientParseErrorKind {
+    /// The input was empty or only contained whitespace.
+    Empty,
+    /// The input used a function that is not a known gradient function.
+    UnknownFunction,
+    /// The gradient was malformed, such as by a missing comma or parenthesis.
+    InvalidSyntax,
+    /// A keyword was not valid at its position.
+    UnknownKeyword,
+    /// An angle was missing or had an unknown unit.
+    InvalidAngle,
+    /// A length was negative where that is not allowed, or had a unit that
+    /// is unknown or cannot be resolved.
+    InvalidLength,
+    /// A position was malformed.
+    InvalidPosition,
+    /// A color stop had an invalid color.
+    InvalidColor(ColorParseErrorKind),
+    /// A color hint was not between two color stops.
+    MisplacedHint,
+    /// The gradient had fewer than two color stops.
+    TooFewStops,
+    /// The gradient was followed by unexpected input.
+    TrailingInput,
+}
+
+impl GradientParseErrorKind {
+    fn description(self) -> &'static str {
+        match self {
+            Self::Empty => "empty gradient string",
+            Self::UnknownFunction => "unknown gradient function",
+            Self::InvalidSyntax => "invalid gradient syntax",
+            Self::UnknownKeyword => "unexpected keyword",
+            Self::InvalidAngle => "invalid angle",
+            Self::InvalidLength => "invalid length",
+            Self::InvalidPosition => "invalid position",
+            Self::InvalidColor(kind) => kind.description(),
+            Self::MisplacedHint => "color hint must be between two color stops",
+            Self::TooFewStops => "gradient must have at least two color stops",
+            Self::TrailingInput => "unexpected input after gradient",
+        }
+    }
+}
+
+/// Error returned when [parsing](Gradient::parse_css) a gradient fails.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GradientParseError {
+    offset: usize,
+    kind: GradientParseErrorKind,
+    suggestion: Option<&'static str>,
+}
+
+impl GradientParseError {
+    fn new(offset: usize, kind: GradientParseErrorKind) -> Self {
+        Self {
+            offset,
+            kind,
+            suggestion: None,
+        }
+    }
+
+    #[must_use]
+    fn with_suggestion(mut self, suggestion: Option<&'static str>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+
+    /// Returns the byte offset in the input at which the problem was found.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the kind of problem.
+    #[must_use]
+    pub fn kind(&self) -> GradientParseErrorKind {
+        self.kind
+    }
+
+    /// Returns a similarly spelled known name that may have been intended.
+    ///
+    /// This is provided for unknown function names, keywords, color names
+    /// and color spaces.
+    #[must_use]
+    pub fn suggestion(&self) -> Option<&'static str> {
+        self.suggestion
+    }
+}
+
+impl fmt::Display for GradientParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind.description(), self.offset)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, "; did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GradientParseError {}
+
+impl Gradient {
+    /// Parses a CSS gradient function, resolving it against a reference box,
+    /// and returns the gradient together with the brush transform that it
+    /// must be painted with.
+    ///
+    /// Accepts the `linear-gradient()`, `radial-gradient()` and
+    /// `conic-gradient()` functions of CSS Images Module Level 4, and their
+    /// `repeating-` variants, including:
+    /// - angles in `deg`, `grad`, `rad` and `turn`, and `to` side and corner
+    ///   keywords,
+    /// - `circle` and `ellipse` shapes with extent keywords or explicit
+    ///   sizes, and `at` positions,
+    /// - `from` angles of conic gradients,
+    /// - color stops with zero, one or two positions, and color hints,
+    /// - the `in` color interpolation method.
+    ///
+    /// Percentages and keywords are resolved against `reference_box`, and
+    /// lengths are in `px` or absolute units such as `in` and `pt`. Stop
+    /// positions follow the CSS fix-up rules, and the gradient geometry is
+    /// adjusted so that the stops lie within `0.0` to `1.0`. Without an
+    /// interpolation method, colors are interpolated in sRGB if all of them
+    /// use the legacy sRGB syntax, and in Oklab otherwise. Colors are parsed
+    /// as described for [`DynamicColor::parse`], so they keep their precision
+    /// and color space.
+    ///
+    /// The transform is the identity, except for elliptical radial gradients,
+    /// which are [circles mapped onto the ellipse](Self::new_elliptical), and
+    /// conic gradients, which are rotated so that they start at the `from`
+    /// angle. Use it as the transform of a
+    /// [`TransformedBrush`](crate::TransformedBrush), which
+    /// [formats](crate::TransformedBrush::to_css) back to CSS.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GradientParseError`] describing the location and kind of
+    /// the problem if the string is not a valid gradient.
+    pub fn parse_css(s: &str, reference_box: Rect) -> Result<(Self, Affine)> {
+        let start = s.len() - s.trim_start().len();
+        if s.trim().is_empty() {
+            return Err(GradientParseError::new(
+                start,
+                GradientParseErrorKind::Empty,
+            ));
+        }
+        let mut p = Parser::new(s, start);
+        let name = p.ident().unwrap_or_default();
+        // Whitespace is not permitted between the function name and parenthesis.
+        if name.is_empty() || p.peek() != Some(b'(') {
+            return Err(error(&p, GradientParseErrorKind::InvalidSyntax));
+        }
+        let (kind, repeating) = lookup(FUNCTIONS, name).ok_or_else(|| {
+            GradientParseError::new(start, GradientParseErrorKind::UnknownFunction)
+                .with_suggestion(suggest(name, FUNCTIONS.iter().map(|(n, _)| *n)))
+        })?;
+        p.pos += 1;
+        let prelude = parse_prelude(&mut p, kind, reference_box)?;
+        let items = parse_stop_list(&mut p, kind)?;
+        if !p.eat(b')') {
+            return Err(error(&p, GradientParseErrorKind::InvalidSyntax));
+        }
+        if !p.at_end() {
+            return Err(error(&p, GradientParseErrorKind::TrailingInput));
+        }
+        Ok(build(kind, repeating, &prelude, &items, reference_box))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Kind {
+    Linear,
+    Radial,
+    Conic,
+}
+
+const FUNCTIONS: &[(&str, (Kind, bool))] = &[
+    ("linear-gradient", (Kind::Linear, false)),
+    ("radial-gradient", (Kind::Radial, false)),
+    ("conic-gradient", (Kind::Conic, false)),
+    ("repeating-linear-gradient", (Kind::Linear, true)),
+    ("repeating-radial-gradient", (Kind::Radial, true)),
+    ("repeating-conic-gradient", (Kind::Conic, true)),
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Keyword {
+    To,
+    In,
+    At,
+    From,
+    Circle,
+    Ellipse,
+    Extent(Extent),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Extent {
+    ClosestSide,
+    ClosestCorner,
+    FarthestSide,
+    FarthestCorner,
+}
+
+const LINEAR_KEYWORDS: &[(&str, Keyword)] = &[("to", Keyword::To), ("in", Keyword::In)];
+
+const RADIAL_KEYWORDS: &[(&str, Keyword)] = &[
+    ("circle", Keyword::Circle),
+    ("ellipse", Keyword::Ellipse),
+    ("closest-side", Keyword::Extent(Extent::ClosestSide)),
+    ("closest-corner", Keyword::Extent(Extent::ClosestCorner)),
+    ("farthest-side", Keyword::Extent(Extent::FarthestSide)),
+    ("farthest-corner", Keyword::Extent(Extent::FarthestCorner)),
+    ("at", Keyword::At),
+    ("in", Keyword::In),
+];
+
+const CONIC_KEYWORDS: &[(&str, Keyword)] = &[
+    ("from", Keyword::From),
+    ("at", Keyword::At),
+    ("in", Keyword::In),
+];
+
+fn keywords(kind: Kind) -> &'static [(&'static str, Keyword)] {
+    match kind {
+        Kind::Linear => LINEAR_KEYWORDS,
+        Kind::Radial => RADIAL_KEYWORDS,
+        Kind::Conic => CONIC_KEYWORDS,
+    }
+}
+
+/// Horizontal and vertical keywords of positions and `to` directions.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Center,
+}
+
+const SIDES: &[(&str, Side)] = &[
+    ("left", Side::Left),
+    ("right", Side::Right),
+    ("top", Side::Top),
+    ("bottom", Side::Bottom),
+    ("center", Side::Center),
+];
+
+impl Side {
+    fn is_horizontal(self) -> bool {
+        matches!(self, Self::Left | Self::Right | Self::Center)
+    }
+
+    fn is_vertical(self) -> bool {
+        matches!(self, Self::Top | Self::Bottom | Self::Center)
+    }
+
+    /// Returns the position of the side as a fraction of the box size.
+    fn fraction(self) -> f64 {
+        match self {
+            Self::Left | Self::Top => 0.0,
+            Self::Center => 0.5,
+            Self::Right | Self::Bottom => 1.0,
+        }
+    }
+}
+
+/// Color spaces accepted by the `in` interpolation method, in addition to
+/// those accepted by the `color()` function.
+const POLAR_AND_LAB_SPACES: &[(&str, ColorSpace)] = &[
+    ("lab", ColorSpace::Lab),
+    ("lch", ColorSpace::Lch),
+    ("oklab", ColorSpace::Oklab),
+    ("oklch", ColorSpace::Oklch),
+    ("hsl", ColorSpace::Hsl),
+    ("hwb", ColorSpace::Hwb),
+];
+
+const HUE_METHODS: &[(&str, HueInterpolation)] = &[
+    ("shorter", HueInterpolation::Shorter),
+    ("longer", HueInterpolation::Longer),
+    ("increasing", HueInterpolation::Increasing),
+    ("decreasing", HueInterpolation::Decreasing),
+];
+
+/// Color functions that use the legacy sRGB syntax.
+const LEGACY_FUNCTIONS: &[&str] = &["rgb", "rgba", "hsl", "hsla", "hwb"];
+
+#[derive(Copy, Clone, Debug)]
+enum LengthPercentage {
+    Px(f64),
+    Percent(f64),
+}
+
+impl LengthPercentage {
+    fn resolve(self, basis: f64) -> f64 {
+        match self {
+            Self::Px(px) => px,
+            Self::Percent(percent) => percent * 0.01 * basis,
+        }
+    }
+
+    fn is_negative(self) -> bool {
+        match self {
+            Self::Px(v) | Self::Percent(v) => v < 0.0,
+        }
+    }
+}
+
+/// Position of a color stop or hint along the gradient ray.
+#[derive(Copy, Clone, Debug)]
+enum StopPosition {
+    Length(LengthPercentage),
+    /// An angle in degrees.
+    Angle(f64),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Direction {
+    /// An angle in degrees, clockwise from the top.
+    Angle(f64),
+    /// A side or corner, as signs of the horizontal and vertical direction.
+    To(f64, f64),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Size {
+    Extent(Extent),
+    Circle(f64),
+    Ellipse(LengthPercentage, LengthPercentage),
+}
+
+#[derive(Default)]
+struct Prelude {
+    direction: Option<Direction>,
+    circle: Option<bool>,
+    size: Option<Size>,
+    /// Offset of the explicit size, for errors.
+    size_offset: usize,
+    position: Option<Point>,
+    from: Option<f64>,
+    interpolation: Option<(ColorSpace, HueInterpolation)>,
+}
+
+impl Prelude {
+    /// Checks that an explicit size has one length for a `circle` and two
+    /// for an `ellipse`.
+    fn check_shape(&self) -> Result<()> {
+        let mismatched = matches!(
+            (self.circle, self.size),
+            (Some(true), Some(Size::Ellipse(..))) | (Some(false), Some(Size::Circle(_)))
+        );
+        if mismatched {
+            return Err(GradientParseError::new(
+                self.size_offset,
+                GradientParseErrorKind::InvalidLength,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// An item of the color stop list.
+enum Item {
+    Stop {
+        color: DynamicColor,
+        legacy: bool,
+        position: Option<StopPosition>,
+    },
+    Hint(StopPosition),
+}
+
+fn error(p: &Parser<'_>, kind: GradientParseErrorKind) -> GradientParseError {
+    GradientParseError::new(p.pos, kind)
+}
+
+/// Returns true if the next token is a number.
+fn at_number(p: &mut Parser<'_>) -> bool {
+    p.skip_ws();
+    let bytes = p.s.as_bytes();
+    let digit_at = |i: usize| {
+        bytes
+            .get(i)
+            .is_some_and(|b| b.is_ascii_digit() || *b == b'.')
+    };
+    match bytes.get(p.pos) {
+        Some(b'+' | b'-') => digit_at(p.pos + 1),
+        _ => digit_at(p.pos),
+    }
+}
+
+/// A numeric token.
+enum Numeric<'a> {
+    Number(f64),
+    Percentage(f64),
+    Dimension(f64, &'a str),
+}
+
+fn numeric<'a>(p: &mut Parser<'a>, kind: GradientParseErrorKind) -> Result<Numeric<'a>> {
+    p.skip_ws();
+    let Some(n) = p.number() else {
+        return Err(error(p, kind));
+    };
+    if p.peek() == Some(b'%') {
+        p.pos += 1;
+        return Ok(Numeric::Percentage(n));
+    }
+    if p.peek().is_some_and(|b| b.is_ascii_alphabetic()) {
+        return Ok(Numeric::Dimension(n, p.ident().unwrap_or_default()));
+    }
+    Ok(Numeric::Number(n))
+}
+
+fn angle_from(value: &Numeric<'_>) -> Option<f64> {
+    match *value {
+        Numeric::Number(0.0) => Some(0.0),
+        Numeric::Dimension(n, unit) => {
+            let scale = if unit.eq_ignore_ascii_case("deg") {
+                1.0
+            } else if unit.eq_ignore_ascii_case("grad") {
+                0.9
+            } else if unit.eq_ignore_ascii_case("rad") {
+                360.0 / TAU
+            } else if unit.eq_ignore_ascii_case("turn") {
+                360.0
+            } else {
+                return None;
+            };
+            Some(n * scale)
+        }
+        _ => None,
+    }
+}
+
+fn length_from(value: &Numeric<'_>) -> Option<LengthPercentage> {
+    match *value {
+        Numeric::Number(0.0) => Some(LengthPercentage::Px(0.0)),
+        Numeric::Percentage(n) => Some(LengthPercentage::Percent(n)),
+        Numeric::Dimension(n, unit) => {
+            const UNITS: &[(&str, f64)] = &[
+                ("px", 1.0),
+                ("in", 96.0),
+                ("cm", 96.0 / 2.54),
+                ("mm", 96.0 / 25.4),
+                ("q", 96.0 / 101.6),
+                ("pt", 96.0 / 72.0),
+                ("pc", 16.0),
+            ];
+            lookup(UNITS, unit).map(|scale| LengthPercentage::Px(n * scale))
+        }
+        Numeric::Number(_) => None,
+    }
+}
+
+/// Parses an angle in degrees.
+fn angle(p: &mut Parser<'_>) -> Result<f64> {
+    p.skip_ws();
+    let offset = p.pos;
+    let value = numeric(p, GradientParseErrorKind::InvalidAngle)?;
+    angle_from(&value)
+        .ok_or_else(|| GradientParseError::new(offset, GradientParseErrorKind::InvalidAngle))
+}
+
+fn length_percentage(p: &mut Parser<'_>) -> Result<LengthPercentage> {
+    p.skip_ws();
+    let offset = p.pos;
+    let value = numeric(p, GradientParseErrorKind::InvalidLength)?;
+    length_from(&value)
+        .ok_or_else(|| GradientParseError::new(offset, GradientParseErrorKind::InvalidLength))
+}
+
+/// Parses the keyword at the cursor if it is in the table, and otherwise
+/// leaves the cursor unchanged.
+fn keyword<T: Copy>(p: &mut Parser<'_>, table: &[(&'static str, T)]) -> Option<T> {
+    let start = p.pos;
+    let value = p.ident().and_then(|word| lookup(table, word));
+    if value.is_none() {
+        p.pos = start;
+    }
+    value
+}
+
+/// Parses a keyword from the table, or fails with a suggestion.
+fn expect_keyword<T: Copy>(p: &mut Parser<'_>, table: &[(&'static str, T)]) -> Result<T> {
+    p.skip_ws();
+    let offset = p.pos;
+    let word = p.ident().unwrap_or_default();
+    lookup(table, word).ok_or_else(|| {
+        GradientParseError::new(offset, GradientParseErrorKind::UnknownKeyword)
+            .with_suggestion(suggest(word, table.iter().map(|(n, _)| *n)))
+    })
+}
+
+fn parse_prelude(p: &mut Parser<'_>, kind: Kind, bounds: Rect) -> Result<Prelude> {
+    let table = keywords(kind);
+    let mut prelude = Prelude::default();
+    let mut first = true;
+    loop {
+        p.skip_ws();
+        let offset = p.pos;
+        if !first && p.eat(b',') {
+            return Ok(prelude);
+        }
+        let duplicate = || GradientParseError::new(offset, GradientParseErrorKind::InvalidSyntax);
+        if at_number(p) {
+            match kind {
+                Kind::Linear if prelude.direction.is_none() => {
+                    prelude.direction = Some(Direction::Angle(angle(p)?));
+                }
+                Kind::Radial if prelude.size.is_none() => {
+                    prelude.size = Some(radial_size(p, offset)?);
+                    prelude.size_offset = offset;
+                    prelude.check_shape()?;
+                }
+                _ => return Err(duplicate()),
+            }
+            first = false;
+            continue;
+        }
+        let word = p.ident().unwrap_or_default();
+        match lookup(table, word) {
+            Some(Keyword::In) if prelude.interpolation.is_none() => {
+                prelude.interpolation = Some(interpolation_method(p)?);
+            }
+            Some(Keyword::To) if prelude.direction.is_none() => {
+                prelude.direction = Some(to_direction(p)?);
+            }
+            Some(Keyword::Circle | Keyword::Ellipse) if prelude.circle.is_none() => {
+                prelude.circle = Some(word.eq_ignore_ascii_case("circle"));
+                prelude.check_shape()?;
+            }
+            Some(Keyword::Extent(extent)) if prelude.size.is_none() => {
+                prelude.size = Some(Size::Extent(extent));
+            }
+            Some(Keyword::At) if prelude.position.is_none() => {
+                prelude.position = Some(position(p, bounds)?);
+            }
+            Some(Keyword::From) if prelude.from.is_none() => {
+                prelude.from = Some(angle(p)?);
+            }
+            Some(_) => return Err(duplicate()),
+            None if first => {
+                // This is the first color stop.
+                p.pos = offset;
+                return Ok(prelude);
+            }
+            None => {
+                let suggestion = suggest(word, table.iter().map(|(n, _)| *n));
+                let error_kind = if suggestion.is_some() {
+                    GradientParseErrorKind::UnknownKeyword
+                } else {
+                    GradientParseErrorKind::InvalidSyntax
+                };
+                return Err(GradientParseError::new(offset, error_kind).with_suggestion(suggestion));
+            }
+        }
+        first = false;
+    }
+}
+
+/// Parses the sides after the `to` keyword.
+fn to_direction(p: &mut Parser<'_>) -> Result<Direction> {
+    const TO_SIDES: &[(&str, Side)] = &[
+        ("left", Side::Left),
+        ("right", Side::Right),
+        ("top", Side::Top),
+        ("bottom", Side::Bottom),
+    ];
+    let first = expect_keyword(p, TO_SIDES)?;
+    p.skip_ws();
+    let offset = p.pos;
+    let second = keyword(p, TO_SIDES);
+    let (mut x, mut y) = (0.0, 0.0);
+    for side in [Some(first), second].into_iter().flatten() {
+        let (axis, sign) = match side {
+            Side::Left => (&mut x, -1.0),
+            Side::Right => (&mut x, 1.0),
+            Side::Top => (&mut y, -1.0),
+            _ => (&mut y, 1.0),
+        };
+        if *axis != 0.0 {
+            return Err(GradientParseError::new(
+                offset,
+                GradientParseErrorKind::InvalidSyntax,
+            ));
+        }
+        *axis = sign;
+    }
+    Ok(Direction::To(x, y))
+}
+
+/// Parses the `in` color interpolation method after the keyword.
+fn interpolation_method(p: &mut Parser<'_>) -> Result<(ColorSpace, HueInterpolation)> {
+    p.skip_ws();
+    let offset = p.pos;
+    let word = p.ident().unwrap_or_default();
+    let space = lookup(PREDEFINED_SPACES, word)
+        .or_else(|| lookup(POLAR_AND_LAB_SPACES, word))
+        .ok_or_else(|| {
+            let names = PREDEFINED_SPACES.iter().chain(POLAR_AND_LAB_SPACES);
+            GradientParseError::new(offset, GradientParseErrorKind::UnknownKeyword)
+                .with_suggestion(suggest(word, names.map(|(n, _)| *n)))
+        })?;
+    p.skip_ws();
+    let method_offset = p.pos;
+    let Some(method) = keyword(p, HUE_METHODS) else {
+        return Ok((space, HueInterpolation::default()));
+    };
+    if !space.is_polar() {
+        return Err(GradientParseError::new(
+            method_offset,
+            GradientParseErrorKind::InvalidSyntax,
+        ));
+    }
+    expect_keyword(p, &[("hue", ())])?;
+    Ok((space, method))
+}
+
+/// Parses an explicit radial size of one or two lengths.
+fn radial_size(p: &mut Parser<'_>, offset: usize) -> Result<Size> {
+    let first = length_percentage(p)?;
+    let invalid = || GradientParseError::new(offset, GradientParseErrorKind::InvalidLength);
+    if first.is_negative() {
+        return Err(invalid());
+    }
+    if at_number(p) {
+        let second = length_percentage(p)?;
+        if second.is_negative() {
+            return Err(invalid());
+        }
+        return Ok(Size::Ellipse(first, second));
+    }
+    match first {
+        LengthPercentage::Px(px) => Ok(Size::Circle(px)),
+        // Circles cannot have a percentage size.
+        LengthPercentage::Percent(_) => Err(invalid()),
+    }
+}
+
+/// A component of a position.
+#[derive(Copy, Clone, Debug)]
+enum PositionPart {
+    Side(Side),
+    Length(LengthPercentage),
+}
+
+/// Parses a position, which has one, two or four components.
+fn position(p: &mut Parser<'_>, bounds: Rect) -> Result<Point> {
+    p.skip_ws();
+    let offset = p.pos;
+    let mut parts: SmallVec<[PositionPart; 4]> = SmallVec::new();
+    while parts.len() < 4 {
+        if at_number(p) {
+            parts.push(PositionPart::Length(length_percentage(p)?));
+        } else if let Some(side) = keyword(p, SIDES) {
+            parts.push(PositionPart::Side(side));
+        } else {
+            break;
+        }
+    }
+    let invalid = || GradientParseError::new(offset, GradientParseErrorKind::InvalidPosition);
+    let (width, height) = (bounds.width(), bounds.height());
+    let resolve = |part: PositionPart, size: f64| match part {
+        PositionPart::Side(side) => side.fraction() * size,
+        PositionPart::Length(length) => length.resolve(size),
+    };
+    let (x, y) = match parts[..] {
+        [PositionPart::Side(side)] if !side.is_horizontal() => {
+            (0.5 * width, resolve(parts[0], height))
+        }
+        [part] => (resolve(part, width), 0.5 * height),
+        [PositionPart::Side(a), PositionPart::Side(b)] => {
+            let (h, v) = if a.is_horizontal() && b.is_vertical() {
+                (a, b)
+            } else if a.is_vertical() && b.is_horizontal() {
+                (b, a)
+            } else {
+                return Err(invalid());
+            };
+            (h.fraction() * width, v.fraction() * height)
+        }
+        [a, b] => {
+            let horizontal =
+                |part| !matches!(part, PositionPart::Side(side) if !side.is_horizontal());
+            let vertical = |part| !matches!(part, PositionPart::Side(side) if !side.is_vertical());
+            if !horizontal(a) || !vertical(b) {
+                return Err(invalid());
+            }
+            (resolve(a, width), resolve(b, height))
+        }
+        [PositionPart::Side(a), PositionPart::Length(a_len), PositionPart::Side(b), PositionPart::Length(b_len)] =>
+        {
+            let offset_from = |side: Side, length: LengthPercentage, size: f64| match side {
+                Side::Left | Side::Top => Some(length.resolve(size)),
+                Side::Right | Side::Bottom => Some(size - length.resolve(size)),
+                Side::Center => None,
+            };
+            let (h, h_len, v, v_len) = if a.is_horizontal() && b.is_vertical() {
+                (a, a_len, b, b_len)
+            } else if a.is_vertical() && b.is_horizontal() {
+                (b, b_len, a, a_len)
+            } else {
+                return Err(invalid());
+            };
+            (
+                offset_from(h, h_len, width).ok_or_else(invalid)?,
+                offset_from(v, v_len, height).ok_or_else(invalid)?,
+            )
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(bounds.origin() + Vec2::new(x, y))
+}
+
+/// Parses the color at the cursor, returning it along with whether it uses
+/// the legacy sRGB syntax.
+fn color(p: &mut Parser<'_>, first: Option<Kind>) -> Result<(DynamicColor, bool)> {
+    p.skip_ws();
+    let start = p.pos;
+    let bytes = p.s.as_bytes();
+    let mut end = start;
+    while bytes
+        .get(end)
+        .is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'#' | b'-' | b'_'))
+    {
+        end += 1;
+    }
+    let name = &p.s[start..end];
+    let is_function = bytes.get(end) == Some(&b'(');
+    if is_function {
+        let mut depth = 0;
+        for (i, b) in bytes[end..].iter().enumerate() {
+            match b {
+                b'(' => depth += 1,
+                b')' => depth -= 1,
+                _ => continue,
+            }
+            if depth == 0 {
+                end += i + 1;
+                break;
+            }
+        }
+        if depth != 0 {
+            p.pos = p.s.len();
+            return Err(error(p, GradientParseErrorKind::InvalidSyntax));
+        }
+    }
+    let color = DynamicColor::parse(&p.s[start..end]).map_err(|e| {
+        // A misspelled keyword before the first stop is parsed as a color.
+        if let Some(kind) = first.filter(|_| !is_function && !name.starts_with('#')) {
+            let suggestion = suggest(name, keywords(kind).iter().map(|(n, _)| *n));
+            if suggestion.is_some() {
+                return GradientParseError::new(start, GradientParseErrorKind::UnknownKeyword)
+                    .with_suggestion(suggestion);
+            }
+        }
+        GradientParseError::new(
+            start + e.offset(),
+            GradientParseErrorKind::InvalidColor(e.kind()),
+        )
+        .with_suggestion(e.suggestion())
+    })?;
+    p.pos = end;
+    let legacy = !is_function
+        || LEGACY_FUNCTIONS
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(name));
+    Ok((color, legacy))
+}
+
+fn stop_position(p: &mut Parser<'_>, kind: Kind) -> Result<StopPosition> {
+    p.skip_ws();
+    let offset = p.pos;
+    let value = numeric(p, GradientParseErrorKind::InvalidLength)?;
+    let position = if kind == Kind::Conic {
+        match value {
+            Numeric::Percentage(n) => Some(StopPosition::Length(LengthPercentage::Percent(n))),
+            _ => angle_from(&value).map(StopPosition::Angle),
+        }
+    } else {
+        length_from(&value).map(StopPosition::Length)
+    };
+    position.ok_or_else(|| {
+        let kind = if kind == Kind::Conic {
+            GradientParseErrorKind::InvalidAngle
+        } else {
+            GradientParseErrorKind::InvalidLength
+        };
+        GradientParseError::new(offset, kind)
+    })
+}
+
+/// Parses the color stop list up to the closing parenthesis.
+fn parse_stop_list(p: &mut Parser<'_>, kind: Kind) -> Result<Vec<(Item, usize)>> {
+    let mut items = Vec::new();
+    let mut colors = 0;
+    loop {
+        p.skip_ws();
+        let offset = p.pos;
+        if at_number(p) {
+            items.push((Item::Hint(stop_position(p, kind)?), offset));
+        } else {
+            let first = (colors == 0).then_some(kind);
+            let (color, legacy) = color(p, first)?;
+            colors += 1;
+            let mut positions: SmallVec<[Option<StopPosition>; 2]> = SmallVec::new();
+            while positions.len() < 2 && at_number(p) {
+                positions.push(Some(stop_position(p, kind)?));
+            }
+            if positions.is_empty() {
+                positions.push(None);
+            }
+            // A stop with two positions is two stops of the same color.
+            for position in positions {
+                let stop = Item::Stop {
+                    color,
+                    legacy,
+                    position,
+                };
+                items.push((stop, offset));
+            }
+        }
+        if !p.eat(b',') {
+            break;
+        }
+    }
+    if colors < 2 {
+        return Err(error(p, GradientParseErrorKind::TooFewStops));
+    }
+    // Hints must be between two stops.
+    let is_stop = |item: Option<&(Item, usize)>| matches!(item, Some((Item::Stop { .. }, _)));
+    for (i, (item, offset)) in items.iter().enumerate() {
+        if matches!(item, Item::Hint(_))
+            && !(i > 0 && is_stop(items.get(i - 1)) && is_stop(items.get(i + 1)))
+        {
+            return Err(GradientParseError::new(
+                *offset,
+                GradientParseErrorKind::MisplacedHint,
+            ));
+        }
+    }
+    Ok(items)
+}
+
+/// A color stop with an offset and optional hint in units of the gradient
+/// ray.
+struct RawStop {
+    offset: f64,
+    color: DynamicColor,
+    hint: Option<f64>,
+}
+
+/// Resolves the positions of the stops with the CSS color stop fix-up
+/// rules, and converts hints to stop hints.
+fn resolve_stops(items: &[(Item, usize)], resolve: impl Fn(StopPosition) -> f64) -> Vec<RawStop> {
+    let mut positions: Vec<Option<f64>> = items
+        .iter()
+        .map(|(item, _)| match item {
+            Item::Stop { position, .. } => position.map(&resolve),
+            Item::Hint(position) => Some(resolve(*position)),
+        })
+        .collect();
+    // The first and last items are always stops.
+    let last = positions.len() - 1;
+    positions[0].get_or_insert(0.0);
+    positions[last].get_or_insert(1.0);
+    let mut max = f64::NEG_INFINITY;
+    for position in positions.iter_mut().flatten() {
+        *position = position.max(max);
+        max = *position;
+    }
+    // Spread runs of stops without positions evenly between the stops
+    // around them, ignoring hints.
+    let stop_indices: Vec<usize> = (0..items.len())
+        .filter(|&i| matches!(items[i].0, Item::Stop { .. }))
+        .collect();
+    let mut k = 0;
+    while k < stop_indices.len() {
+        if positions[stop_indices[k]].is_some() {
+            k += 1;
+            continue;
+        }
+        let start = k;
+        while positions[stop_indices[k]].is_none() {
+            k += 1;
+        }
+        let lo = positions[stop_indices[start - 1]].unwrap_or_default();
+        let hi = positions[stop_indices[k]].unwrap_or_default();
+        let count = (k - start + 1) as f64;
+        for (n, &index) in stop_indices[start..k].iter().enumerate() {
+            positions[index] = Some(lo + (hi - lo) * (n + 1) as f64 / count);
+        }
+    }
+    let mut stops: Vec<RawStop> = Vec::new();
+    for ((item, _), position) in items.iter().zip(positions) {
+        let position = position.unwrap_or_default();
+        match item {
+            Item::Stop { color, .. } => stops.push(RawStop {
+                offset: position,
+                color: *color,
+                hint: None,
+            }),
+            Item::Hint(_) => {
+                if let Some(stop) = stops.last_mut() {
+                    stop.hint = Some(position);
+                }
+            }
+        }
+    }
+    // Convert hints to positions relative to the next stop.
+    for i in 0..stops.len().saturating_sub(1) {
+        let (a, b) = (stops[i].offset, stops[i + 1].offset);
+        stops[i].hint = stops[i]
+            .hint
+            .filter(|_| b > a)
+            .map(|hint| ((hint - a) / (b - a)).clamp(0.0, 1.0));
+    }
+    stops
+}
+
+/// Builds a gradient from the parsed function.
+fn build(
+    kind: Kind,
+    repeating: bool,
+    prelude: &Prelude,
+    items: &[(Item, usize)],
+    bounds: Rect,
+) -> (Gradient, Affine) {
+    let legacy = items
+        .iter()
+        .all(|(item, _)| !matches!(item, Item::Stop { legacy: false, .. }));
+    let default_space = if legacy {
+        ColorSpace::Srgb
+    } else {
+        ColorSpace::Oklab
+    };
+    let (space, hue) = prelude
+        .interpolation
+        .unwrap_or((default_space, HueInterpolation::default()));
+    let center = prelude.position.unwrap_or_else(|| bounds.center());
+    // Length of the gradient ray and the geometry of the gradient.
+    let (ray, geometry) = match kind {
+        Kind::Linear => {
+            let (width, height) = (bounds.width(), bounds.height());
+            let dir = match prelude.direction.unwrap_or(Direction::Angle(180.0)) {
+                Direction::Angle(degrees) => {
+                    let (sin, cos) = degrees.to_radians().sin_cos();
+                    Vec2::new(sin, -cos)
+                }
+                // For corners, the gradient line is perpendicular to the
+                // line between the two neighboring corners.
+                Direction::To(x, y) => {
+                    let dir = Vec2::new(x * height, y * width);
+                    if dir.hypot2() > 0.0 {
+                        dir.normalize()
+                    } else {
+                        Vec2::new(x, y).normalize()
+                    }
+                }
+            };
+            let length = (width * dir.x).abs() + (height * dir.y).abs();
+            let start = bounds.center() - dir * (0.5 * length);
+            let line = dir * length;
+            (length, Geometry::Linear { start, line })
+        }
+        Kind::Radial => {
+            let (rx, ry) = radial_radii(prelude, center, bounds);
+            if prelude.circle == Some(true) || rx == ry {
+                (rx, Geometry::Circle { center, radius: rx })
+            } else {
+                // Zero radii are replaced as described by the specification.
+                const SMALL: f64 = 1e-5;
+                const LARGE: f64 = 1e5;
+                let (ex, ey) = if rx == 0.0 {
+                    (SMALL, LARGE)
+                } else if ry == 0.0 {
+                    (LARGE, SMALL)
+                } else {
+                    (rx, ry)
+                };
+                let transform =
+                    Affine::translate(center.to_vec2()) * Affine::scale_non_uniform(ex, ey);
+                (rx, Geometry::Ellipse(transform))
+            }
+        }
+        Kind::Conic => {
+            let from = prelude.from.unwrap_or_default();
+            let transform = Affine::rotate_about((from - 90.0).to_radians(), center);
+            (1.0, Geometry::Conic { center, transform })
+        }
+    };
+    let stops = resolve_stops(items, |position| match position {
+        StopPosition::Length(LengthPercentage::Percent(p)) => p * 0.01,
+        StopPosition::Length(LengthPercentage::Px(px)) if ray > 0.0 => px / ray,
+        StopPosition::Length(LengthPercentage::Px(_)) => 0.0,
+        StopPosition::Angle(degrees) => degrees / 360.0,
+    });
+    let mut gradient = Gradient::default()
+        .with_interpolation_space(space)
+        .with_hue_interpolation(hue)
+        .with_extend(if repeating {
+            Extend::Repeat
+        } else {
+            Extend::Pad
+        });
+    let (lo, hi) = fit_stops(&mut gradient, stops, kind, repeating);
+    let (gradient_kind, transform) = geometry.resolve(lo, hi);
+    gradient.kind = gradient_kind;
+    (gradient, transform)
+}
+
+/// Geometry of a gradient, before the range of the gradient ray covered by
+/// the stops is known.
+enum Geometry {
+    Linear {
+        start: Point,
+        line: Vec2,
+    },
+    Circle {
+        center: Point,
+        radius: f64,
+    },
+    /// A unit circle at the origin and the transform to the ellipse.
+    Ellipse(Affine),
+    Conic {
+        center: Point,
+        transform: Affine,
+    },
+}
+
+impl Geometry {
+    /// Returns the gradient kind and transform for the range `lo` to `hi` of
+    /// the gradient ray.
+    fn resolve(&self, lo: f64, hi: f64) -> (GradientKind, Affine) {
+        match *self {
+            Self::Linear { start, line } => {
+                let kind = GradientKind::Linear {
+                    start: start + line * lo,
+                    end: start + line * hi,
+                };
+                (kind, Affine::IDENTITY)
+            }
+            Self::Circle { center, radius } => {
+                let kind = GradientKind::Radial {
+                    start_center: center,
+                    start_radius: (lo * radius) as f32,
+                    end_center: center,
+                    end_radius: (hi * radius) as f32,
+                };
+                (kind, Affine::IDENTITY)
+            }
+            Self::Ellipse(transform) => {
+                let kind = GradientKind::Radial {
+                    start_center: Point::ORIGIN,
+                    start_radius: lo as f32,
+                    end_center: Point::ORIGIN,
+                    end_radius: hi as f32,
+                };
+                (kind, transform)
+            }
+            Self::Conic { center, transform } => {
+                let kind = GradientKind::Sweep {
+                    center,
+                    start_angle: (lo * TAU) as f32,
+                    end_angle: (hi * TAU) as f32,
+                };
+                (kind, transform)
+            }
+        }
+    }
+}
+
+/// Returns the horizontal and vertical radii of a radial gradient.
+fn radial_radii(prelude: &Prelude, center: Point, bounds: Rect) -> (f64, f64) {
+    let dx = [center.x - bounds.x0, bounds.x1 - center.x].map(f64::abs);
+    let dy = [center.y - bounds.y0, bounds.y1 - center.y].map(f64::abs);
+    let (closest, farthest) = (
+        (dx[0].min(dx[1]), dy[0].min(dy[1])),
+        (dx[0].max(dx[1]), dy[0].max(dy[1])),
+    );
+    let circle = match prelude.circle {
+        Some(circle) => circle,
+        None => matches!(prelude.size, Some(Size::Circle(_))),
+    };
+    match prelude.size.unwrap_or(Size::Extent(Extent::FarthestCorner)) {
+        Size::Circle(r) => (r, r),
+        Size::Ellipse(x, y) => (x.resolve(bounds.width()), y.resolve(bounds.height())),
+        Size::Extent(extent) => {
+            let (x, y) = match extent {
+                Extent::ClosestSide | Extent::ClosestCorner => closest,
+                Extent::FarthestSide | Extent::FarthestCorner => farthest,
+            };
+            let corner = matches!(extent, Extent::ClosestCorner | Extent::FarthestCorner);
+            match (circle, corner) {
+                (true, true) => {
+                    let r = x.hypot(y);
+                    (r, r)
+                }
+                (true, false) => {
+                    let r = if extent == Extent::ClosestSide {
+                        x.min(y)
+                    } else {
+                        x.max(y)
+                    };
+                    (r, r)
+                }
+                // An ellipse through the corner with the aspect ratio of the
+                // ellipse that touches the sides.
+                (false, true) => (x * core::f64::consts::SQRT_2, y * core::f64::consts::SQRT_2),
+                (false, false) => (x, y),
+            }
+        }
+    }
+}
+
+/// Sets the stops of the gradient with offsets in the range `0.0` to `1.0`,
+/// and returns the range of the gradient ray that they cover.
+///
+/// Non-repeating linear gradients extend the range to include the stops,
+/// while radial and conic gradients cannot extend before the start of the
+/// ray (or after a full turn), so stops outside of it are cut off.
+/// Repeating gradients are shifted by whole periods instead.
+fn fit_stops(
+    gradient: &mut Gradient,
+    stops: Vec<RawStop>,
+    kind: Kind,
+    repeating: bool,
+) -> (f64, f64) {
+    let mut stops = stops;
+    let first = stops.first().map_or(0.0, |stop| stop.offset);
+    let last = stops.last().map_or(1.0, |stop| stop.offset);
+    let (lo, hi) = if repeating {
+        let period = last - first;
+        let shift = if kind != Kind::Linear && first < 0.0 && period > 0.0 {
+            (-first / period).ceil() * period
+        } else {
+            0.0
+        };
+        for stop in &mut stops {
+            stop.offset += shift;
+        }
+        (first + shift, last + shift)
+    } else {
+        match kind {
+            Kind::Linear => (first.min(0.0), last.max(1.0)),
+            Kind::Radial => {
+                cut_stops(gradient, &mut stops, 0.0, true);
+                (0.0, last.max(1.0))
+            }
+            Kind::Conic => {
+                cut_stops(gradient, &mut stops, 0.0, true);
+                cut_stops(gradient, &mut stops, 1.0, false);
+                (0.0, 1.0)
+            }
+        }
+    };
+    let scale = if hi > lo { 1.0 / (hi - lo) } else { 0.0 };
+    gradient.stops = stops
+        .iter()
+        .map(|stop| ColorStop {
+            offset: ((stop.offset - lo) * scale) as f32,
+            color: stop.color,
+            hint: stop.hint.map(|hint| hint as f32),
+        })
+        .collect::<ColorStops>();
+    (lo, hi)
+}
+
+/// Removes the stops before (or after) `at`, replacing them with a stop with
+/// the color at `at`.
+fn cut_stops(gradient: &Gradient, stops: &mut Vec<RawStop>, at: f64, before: bool) {
+    let outside = |offset: f64| if before { offset < at } else { offset > at };
+    if !stops.iter().any(|stop| outside(stop.offset)) {
+        return;
+    }
+    let mut temp = gradient.clone();
+    temp.stops = stops
+        .iter()
+        .map(|stop| ColorStop {
+            offset: stop.offset as f32,
+            color: stop.color,
+            hint: stop.hint.map(|hint| hint as f32),
+        })
+        .collect();
+    let color = temp.interpolate_stops(at as f32);
+    stops.retain(|stop| !outside(stop.offset));
+    let boundary = RawStop {
+        offset: at,
+        color,
+        hint: None,
+    };
+    if before {
+        if stops.first().map_or(true, |stop| stop.offset != at) {
+            stops.insert(0, boundary);
+        }
+    } else {
+        if let Some(stop) = stops.last_mut() {
+            if stop.offset != at {
+                stop.hint = None;
+            }
+        }
+        if stops.last().map_or(true, |stop| stop.offset != at) {
+            stops.push(boundary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GradientParseError, GradientParseErrorKind};
+    use crate::{ColorParseErrorKind, Extend, Gradient, GradientKind};
+    use kurbo::{Affine, Point, Rect};
+
+    /// A reference box that is not square, so that side and corner
+    /// directions differ from angles.
+    const BOX: Rect = Rect::new(0.0, 0.0, 200.0, 100.0);
+
+    fn parse(css: &str) -> (Gradient, Affine) {
+        match Gradient::parse_css(css, BOX) {
+            Ok(parsed) => parsed,
+            Err(e) => panic!("failed to parse {css}: {e}"),
+        }
+    }
+
+    fn parse_error(css: &str) -> GradientParseError {
+        match Gradient::parse_css(css, BOX) {
+            Ok(_) => panic!("{css} should not parse"),
+            Err(e) => e,
+        }
+    }
+
+    /// Checks the color of a gradient from black to white at a point, which
+    /// is the gradient parameter.
+    #[track_caller]
+    fn assert_param(css: &str, x: f64, y: f64, expected: f32) {
+        let (gradient, transform) = parse(css);
+        let color = gradient.sample(transform.inverse() * Point::new(x, y));
+        let value = color.components[0];
+        assert!(
+            (value - expected).abs() < 1e-4,
+            "{css} at ({x}, {y}) was {value}, expected {expected}"
+        );
+    }
+
+    #[track_caller]
+    fn assert_close(actual: f64, expected: f64, what: &str) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{what} was {actual}, expected {expected}"
+        );
+    }
+
+    #[track_caller]
+    fn assert_error(css: &str, kind: GradientParseErrorKind, offset: usize) {
+        let e = parse_error(css);
+        assert_eq!((e.kind(), e.offset()), (kind, offset), "error for {css}");
+    }
+
+    /// Returns the center in the reference box and the start and end radii
+    /// before the transform of a radial gradient.
+    fn radial(css: &str) -> (Point, f64, f64) {
+        let (gradient, transform) = parse(css);
+        match gradient.kind {
+            GradientKind::Radial {
+                start_center,
+                start_radius,
+                end_radius,
+                ..
+            } => (
+                transform * start_center,
+                start_radius.into(),
+                end_radius.into(),
+            ),
+            kind => panic!("{css} is {kind:?}"),
+        }
+    }
+
+    fn offsets(css: &str) -> Vec<f32> {
+        parse(css).0.stops.iter().map(|stop| stop.offset).collect()
+    }
+
+    #[test]
+    fn directions() {
+        let right = "linear-gradient(to right, black, white)";
+        assert_param(right, 0.0, 0.0, 0.0);
+        assert_param(right, 50.0, 100.0, 0.25);
+        assert_param(right, 200.0, 50.0, 1.0);
+        let down = "linear-gradient(black, white)";
+        assert_param(down, 150.0, 25.0, 0.25);
+        // The gradient line of a corner is perpendicular to the diagonal
+        // between the other two corners, which are at the middle.
+        let corner = "linear-gradient(to top right, black, white)";
+        assert_param(corner, 0.0, 100.0, 0.0);
+        assert_param(corner, 200.0, 0.0, 1.0);
+        assert_param(corner, 0.0, 0.0, 0.5);
+        assert_param(corner, 200.0, 100.0, 0.5);
+        let reversed = "linear-gradient(to left bottom, black, white)";
+        assert_param(reversed, 200.0, 0.0, 0.0);
+        assert_param(reversed, 0.0, 100.0, 1.0);
+        assert_eq!(
+            parse("linear-gradient(to bottom left, black, white)").0,
+            parse(reversed).0,
+            "order of sides"
+        );
+        // An angle of 45 degrees points at the top right, but is not
+        // perpendicular to the other diagonal of a box that is not square.
+        let angle = "linear-gradient(45deg, black, white)";
+        assert_param(angle, 100.0, 50.0, 0.5);
+        let (gradient, _) = parse(angle);
+        let at = |x, y| gradient.sample(Point::new(x, y)).components[0];
+        assert!(at(0.0, 0.0) < 0.5, "top left is before the middle");
+        assert_error(
+            "linear-gradient(to left right, black, white)",
+            GradientParseErrorKind::InvalidSyntax,
+            24,
+        );
+    }
+
+    #[test]
+    fn angle_units() {
+        let expected = parse("linear-gradient(90deg, black, white)").0;
+        assert_param("linear-gradient(90deg, black, white)", 50.0, 0.0, 0.25);
+        for css in [
+            "linear-gradient(100grad, black, white)",
+            "linear-gradient(0.25turn, black, white)",
+            "linear-gradient(1.5707963267948966rad, black, white)",
+            "linear-gradient(90DEG, black, white)",
+            "linear-gradient(to right, black, white)",
+        ] {
+            let GradientKind::Linear { start, end } = parse(css).0.kind else {
+                panic!("{css} is not linear");
+            };
+            let GradientKind::Linear {
+                start: expected_start,
+                end: expected_end,
+            } = expected.kind
+            else {
+                unreachable!();
+            };
+            assert!(
+                (start - expected_start).hypot() < 1e-9 && (end - expected_end).hypot() < 1e-9,
+                "{css} has a different direction"
+            );
+        }
+        // Zero is the only angle that may omit the unit.
+        assert_param("linear-gradient(0, black, white)", 0.0, 25.0, 0.75);
+        assert_error(
+            "linear-gradient(90, black, white)",
+            GradientParseErrorKind::InvalidAngle,
+            16,
+        );
+        assert_error(
+            "linear-gradient(90px, black, white)",
+            GradientParseErrorKind::InvalidAngle,
+            16,
+        );
+    }
+
+    #[test]
+    fn positions() {
+        let center = |css: &str| radial(css).0;
+        let cases = [
+            ("radial-gradient(black, white)", (100.0, 50.0)),
+            ("radial-gradient(at left, black, white)", (0.0, 50.0)),
+            ("radial-gradient(at top, black, white)", (100.0, 0.0)),
+            (
+                "radial-gradient(at bottom right, black, white)",
+                (200.0, 100.0),
+            ),
+            (
+                "radial-gradient(at right bottom, black, white)",
+                (200.0, 100.0),
+            ),
+            ("radial-gradient(at 25% 75%, black, white)", (50.0, 75.0)),
+            ("radial-gradient(at 10px top, black, white)", (10.0, 0.0)),
+            (
+                "radial-gradient(at right 20px bottom 30px, black, white)",
+                (180.0, 70.0),
+            ),
+            (
+                "radial-gradient(at top 10% left 1in, black, white)",
+                (96.0, 10.0),
+            ),
+        ];
+        for (css, (x, y)) in cases {
+            assert_eq!(center(css), Point::new(x, y), "{css}");
+        }
+        assert_param(
+            "conic-gradient(at 50px 50px, black, white)",
+            50.0,
+            100.0,
+            0.5,
+        );
+        assert_param(
+            "conic-gradient(at 50px 50px, black, white)",
+            100.0,
+            50.0,
+            0.25,
+        );
+        assert_error(
+            "radial-gradient(at top left top, black, white)",
+            GradientParseErrorKind::InvalidPosition,
+            19,
+        );
+        assert_error(
+            "radial-gradient(at 10px left, black, white)",
+            GradientParseErrorKind::InvalidPosition,
+            19,
+        );
+    }
+
+    #[test]
+    fn extents() {
+        // The center is 50px from the left and 25px from the top.
+        let circle = |extent: &str| {
+            let css = format!("radial-gradient(circle {extent} at 50px 25px, black, white)");
+            radial(&css).2
+        };
+        assert_close(circle("closest-side"), 25.0, "closest-side");
+        assert_close(circle("farthest-side"), 150.0, "farthest-side");
+        assert_close(
+            circle("closest-corner"),
+            50.0_f64.hypot(25.0),
+            "closest-corner",
+        );
+        assert_close(
+            circle("farthest-corner"),
+            150.0_f64.hypot(75.0),
+            "farthest-corner",
+        );
+        assert_close(circle(""), 150.0_f64.hypot(75.0), "default");
+        // Ellipses are unit circles scaled to the radii.
+        let ellipse = |extent: &str| {
+            let css = format!("radial-gradient(ellipse {extent} at 50px 25px, black, white)");
+            let [a, _, _, d, e, f] = parse(&css).1.as_coeffs();
+            assert_eq!((e, f), (50.0, 25.0), "center of {extent}");
+            (a, d)
+        };
+        let sqrt2 = core::f64::consts::SQRT_2;
+        let cases = [
+            ("closest-side", (50.0, 25.0)),
+            ("farthest-side", (150.0, 75.0)),
+            ("closest-corner", (50.0 * sqrt2, 25.0 * sqrt2)),
+            ("farthest-corner", (150.0 * sqrt2, 75.0 * sqrt2)),
+        ];
+        for (extent, (rx, ry)) in cases {
+            let (a, d) = ellipse(extent);
+            assert_close(a, rx, extent);
+            assert_close(d, ry, extent);
+        }
+        // Explicit sizes.
+        assert_close(
+            radial("radial-gradient(10px, black, white)").2,
+            10.0,
+            "circle",
+        );
+        let (a, d) = ellipse("40px 50%");
+        assert_close(a, 40.0, "ellipse width");
+        assert_close(d, 50.0, "ellipse height");
+    }
+
+    #[test]
+    fn sizes_match_shape() {
+        assert_error(
+            "radial-gradient(ellipse 10px, black, white)",
+            GradientParseErrorKind::InvalidLength,
+            24,
+        );
+        assert_error(
+            "radial-gradient(10px ellipse, black, white)",
+            GradientParseErrorKind::InvalidLength,
+            16,
+        );
+        assert_error(
+            "radial-gradient(circle 10px 20px, black, white)",
+            GradientParseErrorKind::InvalidLength,
+            23,
+        );
+        assert_error(
+            "radial-gradient(circle 10%, black, white)",
+            GradientParseErrorKind::InvalidLength,
+            23,
+        );
+        assert_error(
+            "radial-gradient(-10px, black, white)",
+            GradientParseErrorKind::InvalidLength,
+            16,
+        );
+    }
+
+    #[test]
+    fn hints() {
+        let (gradient, _) = parse("linear-gradient(to right, black, 25%, white)");
+        assert_eq!(gradient.stops[0].hint, Some(0.25), "hint as a percentage");
+        assert_eq!(gradient.stops[1].hint, None, "no hint after the last stop");
+        // Hints are relative to the stops around them.
+        let (between, _) = parse("linear-gradient(to right, black 50px, 75px, white 150px)");
+        assert_eq!(between.stops[0].hint, Some(0.25), "hint in pixels");
+        assert_param(
+            "linear-gradient(to right, black, 25%, white)",
+            50.0,
+            0.0,
+            0.5,
+        );
+        assert_error(
+            "linear-gradient(black, 50%, 60%, white)",
+            GradientParseErrorKind::MisplacedHint,
+            23,
+        );
+        assert_error(
+            "linear-gradient(10%, black, white)",
+            GradientParseErrorKind::InvalidAngle,
+            16,
+        );
+        assert_error(
+            "linear-gradient(black, white, 50%)",
+            GradientParseErrorKind::MisplacedHint,
+            30,
+        );
+    }
+
+    #[test]
+    fn stop_fixup() {
+        // Unpositioned stops are spread evenly between positioned stops, and
+        // stops that go backwards are moved to the largest earlier position.
+        assert_eq!(
+            offsets("linear-gradient(to right, red, lime, blue 80%, white 50%, black)"),
+            [0.0, 0.4, 0.8, 0.8, 1.0],
+            "fix-up"
+        );
+        assert_eq!(
+            offsets("linear-gradient(to right, red, lime 20% 40%, blue)"),
+            [0.0, 0.2, 0.4, 1.0],
+            "two positions"
+        );
+        // Stops outside of the gradient line extend a linear gradient.
+        let (extended, _) = parse("linear-gradient(to right, black -50%, white 150%)");
+        let GradientKind::Linear { start, end } = extended.kind else {
+            panic!("not linear");
+        };
+        assert_eq!((start.x, end.x), (-100.0, 300.0), "extended line");
+        // Radial gradients cannot start before the center, so earlier stops
+        // are cut off.
+        let cut = "radial-gradient(circle 100px at 0px 0px, black -100px, white 100px)";
+        assert_eq!(offsets(cut), [0.0, 1.0], "cut stops");
+        assert_param(cut, 0.0, 0.0, 0.5);
+    }
+
+    #[test]
+    fn repeating() {
+        let linear = "repeating-linear-gradient(to right, black, white 50px)";
+        assert_eq!(parse(linear).0.extend, Extend::Repeat, "repeating");
+        assert_eq!(parse(&linear[10..]).0.extend, Extend::Pad, "not repeating");
+        assert_param(linear, 25.0, 0.0, 0.5);
+        assert_param(linear, 125.0, 0.0, 0.5);
+        // Repeating radial gradients are shifted by whole periods to start
+        // after the center.
+        let radial_css = "repeating-radial-gradient(circle 100px, black -10px, white 10px)";
+        let (_, start, end) = radial(radial_css);
+        assert_close(start, 10.0, "shifted start");
+        assert_close(end, 30.0, "shifted end");
+        assert_param(radial_css, 100.0, 50.0, 0.5);
+        assert_param(radial_css, 115.0, 50.0, 0.25);
+        assert_param(radial_css, 125.0, 50.0, 0.75);
+        let conic = "repeating-conic-gradient(black, white 90deg)";
+        assert_param(conic, 100.0, 0.0, 0.0);
+        assert_param(conic, 150.0, 0.0, 0.5);
+        assert_param(conic, 100.0, 100.0, 0.0);
+    }
+
+    #[test]
+    fn errors() {
+        assert_error("", GradientParseErrorKind::Empty, 0);
+        assert_error("  ", GradientParseErrorKind::Empty, 2);
+        let unknown = parse_error("lineer-gradient(red, blue)");
+        assert_eq!(
+            (unknown.kind(), unknown.offset(), unknown.suggestion()),
+            (
+                GradientParseErrorKind::UnknownFunction,
+                0,
+                Some("linear-gradient")
+            ),
+            "unknown function"
+        );
+        assert_error(
+            "linear-gradient (red, blue)",
+            GradientParseErrorKind::InvalidSyntax,
+            15,
+        );
+        assert_error(
+            "linear-gradient(red)",
+            GradientParseErrorKind::TooFewStops,
+            19,
+        );
+        assert_error(
+            "linear-gradient(red, blue",
+            GradientParseErrorKind::InvalidSyntax,
+            25,
+        );
+        assert_error(
+            "linear-gradient(red, blue) x",
+            GradientParseErrorKind::TrailingInput,
+            27,
+        );
+        let keyword = parse_error("linear-gradient(to rigth, red, blue)");
+        assert_eq!(
+            (keyword.kind(), keyword.offset(), keyword.suggestion()),
+            (GradientParseErrorKind::UnknownKeyword, 19, Some("right")),
+            "misspelled side"
+        );
+        let color = parse_error("linear-gradient(red, bleu)");
+        assert_eq!(
+            (color.kind(), color.offset(), color.suggestion()),
+            (
+                GradientParseErrorKind::InvalidColor(ColorParseErrorKind::UnknownName),
+                21,
+                Some("blue")
+            ),
+            "misspelled color"
+        );
+        assert_error(
+            "linear-gradient(in srgb longer hue, red, blue)",
+            GradientParseErrorKind::InvalidSyntax,
+            24,
+        );
+        assert_error(
+            "conic-gradient(from 10px, red, blue)",
+            GradientParseErrorKind::InvalidAngle,
+            20,
+        );
+        assert_eq!(
+            parse_error("linear-gradient(red)").to_string(),
+            "gradient must have at least two color stops at offset 19",
+            "display"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+use super::colorspace::{gamut_map_srgb, interpolate};
+use super::{css, Color, ColorParseError, ColorSpace, HueInterpolation};
+
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+
+/// Floating point color with an associated [color space](ColorSpace).
+///
+/// Unlike [`Color`], the components are not limited to 8 bits of precision or
+/// to the sRGB gamut, which makes this suitable for high dynamic range and
+/// wide gamut content. Conversions between color spaces do not clamp, so
+/// converting to another space and back only loses floating point precision.
+/// Use [`to_color`](Self::to_color) to explicitly map the color into the sRGB
+/// gamut.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DynamicColor {
+    /// Color space of the components.
+    pub space: ColorSpace,
+    /// Components of the color, interpreted according to the color space.
+    pub components: [f32; 3],
+    /// Alpha component, in the range `0.0` to `1.0`.
+    pub alpha: f32,
+}
+
+impl Hash for DynamicColor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.space.hash(state);
+        self.components.map(f32::to_bits).hash(state);
+        self.alpha.to_bits().hash(state);
+    }
+}
+
+// Override PartialEq to use to_bits for the components to match with the Hash impl
+impl PartialEq for DynamicColor {
+    fn eq(&self, other: &Self) -> bool {
+        self.space == other.space
+            && self.components.map(f32::to_bits) == other.components.map(f32::to_bits)
+            && self.alpha.to_bits() == other.alpha.to_bits()
+    }
+}
+
+impl Eq for DynamicColor {}
+
+// Order by the bit patterns as well, so that the order agrees with PartialEq
+impl PartialOrd for DynamicColor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let key = |c: &Self| (c.space, c.components.map(f32::to_bits), c.alpha.to_bits());
+        Some(key(self).cmp(&key(other)))
+    }
+}
+
+impl Default for DynamicColor {
+    fn default() -> Self {
+        Color::default().into()
+    }
+}
+
+impl DynamicColor {
+    /// Creates a new color from components in the given color space and alpha.
+    #[must_use]
+    pub const fn new(space: ColorSpace, components: [f32; 3], alpha: f32) -> Self {
+        Self {
+            space,
+            components,
+            alpha,
+        }
+    }
+
+    /// Parses a color from a string, keeping the components of color
+    /// functions in their own color space.
+    ///
+    /// This accepts the same syntax as [`Color::parse`], but colors such as
+    /// `oklch(70% 0.3 120)` or `color(display-p3 1 0 0)` are neither gamut
+    /// mapped nor quantized to 8 bits. Hexadecimal and named colors are in
+    /// sRGB.
+    ///
+    /// Components given as `none` are missing, and are NaN in the result,
+    /// while [`Color::parse`] treats them as zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ColorParseError`] describing the location and kind of the
+    /// problem if the string is not a valid color.
+    pub fn parse(s: &str) -> Result<Self, ColorParseError> {
+        let trimmed = s.trim_start();
+        if trimmed.starts_with('#') || !trimmed.contains('(') {
+            return Color::parse(s).map(Into::into);
+        }
+        css::parse_dynamic_color_function(s, s.len() - trimmed.len())
+    }
+
+    /// Converts the color to the given color space.
+    ///
+    /// Components are not clamped, so colors outside of the gamut of the
+    /// target space are preserved.
+    #[must_use]
+    pub fn convert(self, space: ColorSpace) -> Self {
+        if self.space == space {
+            return self;
+        }
+        let components = self
+            .space
+            .convert(space, self.components.map(f64::from))
+            .map(|c| c as f32);
+        Self {
+            space,
+            components,
+            alpha: self.alpha,
+        }
+    }
+
+    /// Returns true if the color is within the sRGB gamut, and so can be
+    /// represented by a [`Color`] without gamut mapping.
+    #[must_use]
+    pub fn is_in_srgb_gamut(self) -> bool {
+        const EPSILON: f32 = 1e-4;
+        self.convert(ColorSpace::Srgb)
+            .components
+            .iter()
+            .all(|c| (-EPSILON..=1.0 + EPSILON).contains(c))
+    }
+
+    /// Converts the color to an 8-bit sRGB [`Color`].
+    ///
+    /// Colors outside of the sRGB gamut are mapped into it with the CSS gamut
+    /// mapping algorithm, which reduces chroma in Oklch to preserve lightness
+    /// and hue.
+    #[must_use]
+    pub fn to_color(self) -> Color {
+        let [r, g, b] = gamut_map_srgb(self.space, self.components.map(f64::from));
+        Color::rgba(r, g, b, self.alpha.into())
+    }
+
+    /// Converts the color to an 8-bit sRGB [`Color`] by clamping each
+    /// component to the sRGB gamut.
+    ///
+    /// This is cheaper than [`to_color`](Self::to_color), but can shift the
+    /// hue of colors that are outside of the gamut.
+    #[must_use]
+    pub fn to_color_clipped(self) -> Color {
+        let [r, g, b] = self.convert(ColorSpace::Srgb).components;
+        Color::rgba(r.into(), g.into(), b.into(), self.alpha.into())
+    }
+
+    /// Interpolates between this color and `other` in the given color space.
+    ///
+    /// A `t` of `0.0` produces this color and `1.0` produces `other`, and the
+    /// result is in `space`. This follows the rules of CSS Color Module Level
+    /// 4: components are interpolated with premultiplied alpha, the hue of a
+    /// gray is taken from the other color, and `hue_interpolation` selects the
+    /// direction around the hue circle for polar spaces.
+    #[must_use]
+    pub fn interpolate(
+        self,
+        other: Self,
+        t: f32,
+        space: ColorSpace,
+        hue_interpolation: HueInterpolation,
+    ) -> Self {
+        let a = self.convert(space);
+        let b = other.convert(space);
+        let (components, alpha) = interpolate(
+            space,
+            hue_interpolation,
+            (a.components.map(f64::from), a.alpha.into()),
+            (b.components.map(f64::from), b.alpha.into()),
+            t.into(),
+        );
+        Self {
+            space,
+            components: components.map(|c| c as f32),
+            alpha: alpha as f32,
+        }
+    }
+
+    /// Returns the color with the alpha component multiplied by the specified
+    /// factor.
+    #[must_use]
+    pub fn with_alpha_factor(self, alpha: f32) -> Self {
+        Self {
+            alpha: self.alpha * alpha,
+            ..self
+        }
+    }
+}
+
+impl From<Color> for DynamicColor {
+    fn from(color: Color) -> Self {
+        let scale = 1.0 / 255.0;
+        Self {
+            space: ColorSpace::Srgb,
+            components: [
+                color.r as f32 * scale,
+                color.g as f32 * scale,
+                color.b as f32 * scale,
+            ],
+            alpha: color.a as f32 * scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynamicColor;
+    use crate::{Color, ColorSpace};
+    use core::cmp::Ordering;
+
+    #[test]
+    fn parse_missing_components() {
+        let color = DynamicColor::parse("oklch(50% none 120 / none)").unwrap();
+        assert_eq!(color.space, ColorSpace::Oklch, "space");
+        assert_eq!(color.components[0], 0.5, "lightness");
+        assert!(color.components[1].is_nan(), "missing chroma");
+        assert_eq!(color.components[2], 120.0, "hue");
+        assert!(color.alpha.is_nan(), "missing alpha");
+        let hue = DynamicColor::parse("hsl(none 50% 50%)").unwrap();
+        assert!(hue.components[0].is_nan(), "missing hue");
+        let rgb = DynamicColor::parse("color(srgb 1 none 0)").unwrap();
+        assert!(rgb.components[1].is_nan(), "missing channel");
+        assert_eq!(
+            Color::parse("oklch(50% none 120 / none)").unwrap(),
+            Color::parse("oklch(50% 0 120 / 0)").unwrap(),
+            "Color treats missing components as zero"
+        );
+        assert_eq!(
+            Color::parse("color(srgb 1 none 0)").unwrap(),
+            Color::rgb8(255, 0, 0),
+            "missing channel in Color"
+        );
+    }
+
+    #[test]
+    fn order_matches_equality() {
+        let color = |c: f32| DynamicColor::new(ColorSpace::Srgb, [c, 0.5, 0.5], 1.0);
+        let nan = color(f32::NAN);
+        assert_eq!(nan, nan, "NaN components are equal to themselves");
+        assert_eq!(nan.partial_cmp(&nan), Some(Ordering::Equal), "NaN order");
+        let (zero, negative_zero) = (color(0.0), color(-0.0));
+        assert_ne!(zero, negative_zero, "signed zeros are distinct");
+        assert_ne!(
+            zero.partial_cmp(&negative_zero),
+            Some(Ordering::Equal),
+            "signed zero order"
+        );
+        for (a, b) in [(zero, color(1.0)), (nan, zero), (color(0.25), color(0.75))] {
+            assert_eq!(
+                a.partial_cmp(&b) == Some(Ordering::Equal),
+                a == b,
+                "{a:?} and {b:?}"
+            );
+        }
+    }
+}
@@ -1,34 +1,60 @@
 // Copyright 2022 the Peniko Authors
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
-use super::{Color, Extend};
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
 
-use kurbo::Point;
+use super::{
+    Color, ColorSpace, DynamicColor, Extend, HueInterpolation, PremulColor, PremulDynamicColor,
+    TransformedBrush,
+};
+
+use kurbo::{Affine, Point, Vec2};
 use smallvec::SmallVec;
 
+use core::fmt;
 use core::hash::{Hash, Hasher};
 
 /// Offset and color of a transition point in a [gradient](Gradient).
+///
+/// A stop can also carry a color hint for the transition to the next stop,
+/// which corresponds to a transition hint in CSS gradients and to the
+/// midpoint sliders of design tools.
 #[derive(Copy, Clone, PartialOrd, Default, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ColorStop {
     /// Normalized offset of the stop.
     pub offset: f32,
     /// Color at the specified offset.
-    pub color: Color,
+    pub color: DynamicColor,
+    /// Position of the color hint between this stop and the next one.
+    ///
+    /// The hint is the point at which the color is halfway between the colors
+    /// of the two stops, relative to the distance between them: `0.0` is at
+    /// this stop and `1.0` at the next stop. `None` is the same as `0.5`,
+    /// which is a linear transition. The hint of the last stop is ignored.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub hint: Option<f32>,
 }
 
 impl Hash for ColorStop {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.offset.to_bits().hash(state);
         self.color.hash(state);
+        self.hint.map(f32::to_bits).hash(state);
     }
 }
 
 // Override PartialEq to use to_bits for the offset to match with the Hash impl
 impl PartialEq for ColorStop {
     fn eq(&self, other: &Self) -> bool {
-        self.offset.to_bits() == other.offset.to_bits() && self.color == other.color
+        self.offset.to_bits() == other.offset.to_bits()
+            && self.color == other.color
+            && self.hint.map(f32::to_bits) == other.hint.map(f32::to_bits)
     }
 }
 
@@ -40,17 +66,80 @@ impl ColorStop {
     #[must_use]
     pub fn with_alpha_factor(self, alpha: f32) -> Self {
         Self {
-            offset: self.offset,
             color: self.color.with_alpha_factor(alpha),
+            ..self
+        }
+    }
+
+    /// Builder method for setting the position of the color hint between
+    /// this stop and the next one, relative to the distance between them.
+    #[must_use]
+    pub fn with_hint(mut self, hint: f32) -> Self {
+        self.hint = Some(hint);
+        self
+    }
+
+    /// Returns the weight of the color of the next stop at the position `t`
+    /// between this stop and the next one, relative to the distance between
+    /// them.
+    ///
+    /// Without a hint, this is `t`. With a hint `h`, the weight follows the
+    /// CSS transition hint curve `t^(ln(0.5) / ln(h))`, so that it is `0.5` at
+    /// the hint. A hint at `0.0` or `1.0` produces a hard transition.
+    #[must_use]
+    pub fn hint_weight(&self, t: f32) -> f32 {
+        match self.hint {
+            None => t,
+            Some(hint) if hint.is_nan() => t,
+            Some(hint) if hint <= 0.0 => 1.0,
+            Some(hint) if hint >= 1.0 => {
+                if t >= 1.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Some(hint) => t.max(0.0).powf(0.5_f32.ln() / hint.ln()),
         }
     }
 }
 
 impl From<(f32, Color)> for ColorStop {
     fn from(pair: (f32, Color)) -> Self {
+        Self {
+            offset: pair.0,
+            color: pair.1.into(),
+            hint: None,
+        }
+    }
+}
+
+impl From<(f32, DynamicColor)> for ColorStop {
+    fn from(pair: (f32, DynamicColor)) -> Self {
         Self {
             offset: pair.0,
             color: pair.1,
+            hint: None,
+        }
+    }
+}
+
+impl From<(f32, PremulColor)> for ColorStop {
+    fn from(pair: (f32, PremulColor)) -> Self {
+        Self {
+            offset: pair.0,
+            color: pair.1.into(),
+            hint: None,
+        }
+    }
+}
+
+impl From<(f32, PremulDynamicColor)> for ColorStop {
+    fn from(pair: (f32, PremulDynamicColor)) -> Self {
+        Self {
+            offset: pair.0,
+            color: pair.1.into(),
+            hint: None,
         }
     }
 }
@@ -58,6 +147,95 @@ impl From<(f32, Color)> for ColorStop {
 /// Collection of color stops.
 pub type ColorStops = SmallVec<[ColorStop; 4]>;
 
+/// Summary of the changes made by [`Gradient::normalize_stops`].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct StopNormalization {
+    /// Number of offsets that were clamped to the range `0.0` to `1.0`.
+    pub clamped: usize,
+    /// Number of offsets that were raised to the offset of a previous stop.
+    pub raised: usize,
+    /// Number of NaN offsets that were replaced.
+    pub resolved: usize,
+    /// Number of color hints that were clamped to the range `0.0` to `1.0`,
+    /// or removed because they were NaN.
+    pub hints: usize,
+    /// Whether a single stop was expanded into two stops.
+    pub expanded_single_stop: bool,
+}
+
+impl StopNormalization {
+    /// Returns true if the stops were already normalized.
+    #[must_use]
+    pub fn is_unchanged(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Errors that can occur when validating the stops of a [gradient](Gradient).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum GradientError {
+    /// The gradient has no color stops.
+    NoStops,
+    /// The offset of the stop at the index is NaN or infinite.
+    NonFiniteOffset {
+        /// Index of the stop.
+        index: usize,
+    },
+    /// The offset of the stop at the index is outside of the range `0.0` to
+    /// `1.0`.
+    OffsetOutOfRange {
+        /// Index of the stop.
+        index: usize,
+    },
+    /// The offset of the stop at the index is smaller than the offset of the
+    /// previous stop.
+    DecreasingOffset {
+        /// Index of the stop.
+        index: usize,
+    },
+    /// The color of the stop at the index has a NaN or infinite component.
+    NonFiniteColor {
+        /// Index of the stop.
+        index: usize,
+    },
+    /// The color hint of the stop at the index is NaN or outside of the range
+    /// `0.0` to `1.0`.
+    InvalidHint {
+        /// Index of the stop.
+        index: usize,
+    },
+}
+
+impl fmt::Display for GradientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoStops => write!(f, "gradient has no color stops"),
+            Self::NonFiniteOffset { index } => {
+                write!(f, "offset of color stop {index} is not finite")
+            }
+            Self::OffsetOutOfRange { index } => {
+                write!(f, "offset of color stop {index} is outside of 0 to 1")
+            }
+            Self::DecreasingOffset { index } => {
+                write!(
+                    f,
+                    "offset of color stop {index} is smaller than the previous offset"
+                )
+            }
+            Self::NonFiniteColor { index } => {
+                write!(f, "color of color stop {index} is not finite")
+            }
+            Self::InvalidHint { index } => {
+                write!(f, "color hint of color stop {index} is outside of 0 to 1")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GradientError {}
+
 /// Properties for the supported [gradient](Gradient) types.
 #[derive(Copy, Clone, PartialEq, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -85,9 +263,9 @@ pub enum GradientKind {
     Sweep {
         /// Center point.
         center: Point,
-        /// Start angle of the sweep, counter-clockwise of the x-axis.
+        /// Start angle of the sweep in radians, counter-clockwise of the x-axis.
         start_angle: f32,
-        /// End angle of the sweep, counter-clockwise of the x-axis.
+        /// End angle of the sweep in radians, counter-clockwise of the x-axis.
         end_angle: f32,
     },
 }
@@ -100,10 +278,50 @@ pub struct Gradient {
     pub kind: GradientKind,
     /// Extend mode.
     pub extend: Extend,
+    /// Color space in which the color stops are interpolated.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub interpolation_space: ColorSpace,
+    /// Method for interpolating hues when the interpolation space is polar.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hue_interpolation: HueInterpolation,
     /// Color stop collection.
     pub stops: ColorStops,
 }
 
+/// A [gradient](Gradient) together with the brush transform that it must be
+/// painted with, as created by [`Gradient::new_elliptical`] and
+/// [`Gradient::new_elliptical_focal`].
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransformedGradient {
+    /// The gradient.
+    pub gradient: Gradient,
+    /// Transform from the space of the gradient to the space of the shape.
+    pub transform: Affine,
+}
+
+impl TransformedGradient {
+    /// Builder method for setting the color stop collection of the gradient.
+    #[must_use]
+    pub fn with_stops(mut self, stops: impl ColorStopsSource) -> Self {
+        self.gradient = self.gradient.with_stops(stops);
+        self
+    }
+
+    /// Builder method for setting the extend mode of the gradient.
+    #[must_use]
+    pub fn with_extend(mut self, mode: Extend) -> Self {
+        self.gradient = self.gradient.with_extend(mode);
+        self
+    }
+}
+
+impl From<TransformedGradient> for TransformedBrush {
+    fn from(gradient: TransformedGradient) -> Self {
+        Self::new(gradient.gradient, Some(gradient.transform))
+    }
+}
+
 impl Default for Gradient {
     fn default() -> Self {
         Self {
@@ -112,6 +330,8 @@ impl Default for Gradient {
                 end: Point::default(),
             },
             extend: Default::default(),
+            interpolation_space: Default::default(),
+            hue_interpolation: Default::default(),
             stops: Default::default(),
         }
     }
@@ -126,6 +346,8 @@ impl Gradient {
                 end: end.into(),
             },
             extend: Default::default(),
+            interpolation_space: Default::default(),
+            hue_interpolation: Default::default(),
             stops: Default::default(),
         }
     }
@@ -141,6 +363,8 @@ impl Gradient {
                 end_radius: radius,
             },
             extend: Default::default(),
+            interpolation_space: Default::default(),
+            hue_interpolation: Default::default(),
             stops: Default::default(),
         }
     }
@@ -160,10 +384,73 @@ impl Gradient {
                 end_radius,
             },
             extend: Default::default(),
+            interpolation_space: Default::default(),
+            hue_interpolation: Default::default(),
             stops: Default::default(),
         }
     }
 
+    /// Creates a new radial gradient with a focal point, as defined by the
+    /// `cx`, `cy`, `r`, `fx`, `fy` and `fr` attributes of an SVG 2
+    /// `radialGradient` element.
+    ///
+    /// The gradient starts at the focal circle and ends at the circle around
+    /// the center. As in SVG 2, a focal circle that is not inside of the end
+    /// circle produces a cone touching both circles, and points outside of
+    /// the cone are transparent.
+    pub fn new_focal(
+        center: impl Into<Point>,
+        radius: f32,
+        focal: impl Into<Point>,
+        focal_radius: f32,
+    ) -> Self {
+        Self::new_two_point_radial(focal, focal_radius, center, radius)
+    }
+
+    /// Creates a new elliptical radial gradient for the specified center
+    /// point and horizontal and vertical radii, as in CSS `ellipse` radial
+    /// gradients, together with the brush transform that it must be painted
+    /// with.
+    ///
+    /// The gradient is a circle of radius `1.0` around the origin, which the
+    /// transform maps onto the ellipse. Convert the result into a
+    /// [`TransformedBrush`](crate::TransformedBrush) to paint it, which also
+    /// allows combining the transform with an SVG `gradientTransform`. A zero
+    /// radius produces a transform that is not invertible, which paints
+    /// nothing.
+    #[must_use]
+    pub fn new_elliptical(center: impl Into<Point>, radii: impl Into<Vec2>) -> TransformedGradient {
+        Self::new_elliptical_focal(center, radii, Point::ORIGIN, 0.0)
+    }
+
+    /// Creates a new elliptical radial gradient with a focal point, together
+    /// with the brush transform that it must be painted with.
+    ///
+    /// This is the focal form of [`new_elliptical`](Self::new_elliptical),
+    /// which corresponds to an SVG `radialGradient` with a non-uniform
+    /// `gradientTransform` or with `objectBoundingBox` units on a box that is
+    /// not square. The `focal` point is relative to the center, and both it
+    /// and the `focal_radius` are in units of the radii: a focal point of
+    /// `(0.5, 0.0)` lies halfway between the center and the right edge of
+    /// the ellipse, and a focal radius of `0.5` is an ellipse with half of
+    /// the radii.
+    #[must_use]
+    pub fn new_elliptical_focal(
+        center: impl Into<Point>,
+        radii: impl Into<Vec2>,
+        focal: impl Into<Point>,
+        focal_radius: f32,
+    ) -> TransformedGradient {
+        let radii = radii.into();
+        let transform = Affine::translate(center.into().to_vec2())
+            * Affine::scale_non_uniform(radii.x, radii.y);
+        let gradient = Self::new_focal(Point::ORIGIN, 1.0, focal, focal_radius);
+        TransformedGradient {
+            gradient,
+            transform,
+        }
+    }
+
     /// Creates a new sweep gradient for the specified center point, start and
     /// end angles.
     pub fn new_sweep(center: impl Into<Point>, start_angle: f32, end_angle: f32) -> Self {
@@ -174,6 +461,8 @@ impl Gradient {
                 end_angle,
             },
             extend: Default::default(),
+            interpolation_space: Default::default(),
+            hue_interpolation: Default::default(),
             stops: Default::default(),
         }
     }
@@ -185,6 +474,27 @@ impl Gradient {
         self
     }
 
+    /// Builder method for setting the color space in which the color stops are
+    /// interpolated.
+    ///
+    /// This corresponds to `in <colorspace>` in CSS gradients. The default is
+    /// [`ColorSpace::Srgb`].
+    #[must_use]
+    pub fn with_interpolation_space(mut self, space: ColorSpace) -> Self {
+        self.interpolation_space = space;
+        self
+    }
+
+    /// Builder method for setting the method for interpolating hues.
+    ///
+    /// This only has an effect when the interpolation space
+    /// [is polar](ColorSpace::is_polar).
+    #[must_use]
+    pub fn with_hue_interpolation(mut self, method: HueInterpolation) -> Self {
+        self.hue_interpolation = method;
+        self
+    }
+
     /// Builder method for setting the color stop collection.
     #[must_use]
     pub fn with_stops(mut self, stops: impl ColorStopsSource) -> Self {
@@ -192,6 +502,267 @@ impl Gradient {
         stops.collect_stops(&mut self.stops);
         self
     }
+
+    /// Builder method for setting the color stop collection, which fails if
+    /// the stops are not [valid](Self::validate).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first invalid stop that is found.
+    pub fn try_with_stops(self, stops: impl ColorStopsSource) -> Result<Self, GradientError> {
+        let gradient = self.with_stops(stops);
+        gradient.validate()?;
+        Ok(gradient)
+    }
+
+    /// Checks that the gradient has at least one stop, and that the offsets
+    /// are finite, within the range `0.0` to `1.0` and in non-decreasing
+    /// order, that the colors are finite, and that the color hints are within
+    /// the range `0.0` to `1.0`.
+    ///
+    /// Use [`normalize_stops`](Self::normalize_stops) to fix the offsets
+    /// instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first invalid stop that is found.
+    pub fn validate(&self) -> Result<(), GradientError> {
+        if self.stops.is_empty() {
+            return Err(GradientError::NoStops);
+        }
+        let mut previous = 0.0;
+        for (index, stop) in self.stops.iter().enumerate() {
+            let offset = stop.offset;
+            if !offset.is_finite() {
+                return Err(GradientError::NonFiniteOffset { index });
+            }
+            if !(0.0..=1.0).contains(&offset) {
+                return Err(GradientError::OffsetOutOfRange { index });
+            }
+            if offset < previous {
+                return Err(GradientError::DecreasingOffset { index });
+            }
+            let color = stop.color;
+            if !color.alpha.is_finite() || !color.components.iter().all(|c| c.is_finite()) {
+                return Err(GradientError::NonFiniteColor { index });
+            }
+            if stop.hint.is_some_and(|hint| !(0.0..=1.0).contains(&hint)) {
+                return Err(GradientError::InvalidHint { index });
+            }
+            previous = offset;
+        }
+        Ok(())
+    }
+
+    /// Fixes the offsets of the color stops following the rules of CSS and
+    /// SVG, and returns a summary of the changes.
+    ///
+    /// The rules are applied in order:
+    /// - A single stop is expanded into two stops with the same color at
+    ///   `0.0` and `1.0`, so the gradient paints a solid color. A gradient
+    ///   without stops is left unchanged, and is transparent.
+    /// - NaN offsets are treated as missing positions in CSS. A missing first
+    ///   or last offset becomes `0.0` or `1.0`.
+    /// - Offsets are clamped to the range `0.0` to `1.0`.
+    /// - An offset smaller than the offset of a previous stop is raised to
+    ///   that offset. The order of the stops is not changed, and stops with
+    ///   equal offsets are kept as hard transitions.
+    /// - Runs of other missing offsets are evenly distributed between the
+    ///   offsets of the surrounding stops.
+    ///
+    /// Color hints are clamped to the range `0.0` to `1.0`, and NaN hints are
+    /// removed.
+    pub fn normalize_stops(&mut self) -> StopNormalization {
+        let mut report = StopNormalization::default();
+        let stops = &mut self.stops;
+        for stop in stops.iter_mut() {
+            if let Some(hint) = stop.hint {
+                let fixed = (!hint.is_nan()).then(|| hint.clamp(0.0, 1.0));
+                if fixed != Some(hint) {
+                    stop.hint = fixed;
+                    report.hints += 1;
+                }
+            }
+        }
+        if let [stop] = stops.as_mut_slice() {
+            let mut end = *stop;
+            stop.offset = 0.0;
+            end.offset = 1.0;
+            stops.push(end);
+            report.expanded_single_stop = true;
+            return report;
+        }
+        let len = stops.len();
+        for (index, offset) in [(0, 0.0), (len.saturating_sub(1), 1.0)] {
+            if let Some(stop) = stops.get_mut(index).filter(|stop| stop.offset.is_nan()) {
+                stop.offset = offset;
+                report.resolved += 1;
+            }
+        }
+        let mut max = 0.0_f32;
+        for stop in stops.iter_mut().filter(|stop| !stop.offset.is_nan()) {
+            let clamped = stop.offset.clamp(0.0, 1.0);
+            if clamped != stop.offset {
+                stop.offset = clamped;
+                report.clamped += 1;
+            }
+            if stop.offset < max {
+                stop.offset = max;
+                report.raised += 1;
+            }
+            max = stop.offset;
+        }
+        let mut i = 0;
+        while i < len {
+            if !stops[i].offset.is_nan() {
+                i += 1;
+                continue;
+            }
+            // The first and last offsets are never NaN here.
+            let start = i;
+            while stops[i].offset.is_nan() {
+                i += 1;
+            }
+            let (lo, hi) = (stops[start - 1].offset, stops[i].offset);
+            let count = (i - start + 1) as f32;
+            for (k, stop) in stops[start..i].iter_mut().enumerate() {
+                stop.offset = lo + (hi - lo) * (k + 1) as f32 / count;
+                report.resolved += 1;
+            }
+        }
+        report
+    }
+
+    /// Replaces color hints with plain stops that approximate the transition
+    /// curve, for renderers that do not support hints.
+    ///
+    /// For each pair of stops with a hint (other than `0.5`), `count` stops
+    /// are inserted at evenly spaced offsets between them, with the colors
+    /// that the hinted transition produces at those offsets in the
+    /// interpolation space of the gradient. Hints between stops at the same
+    /// offset have no effect and are removed. Linear interpolation between
+    /// the resulting stops matches the hinted transition exactly at the
+    /// stops, and approaches it as `count` increases; browsers typically use
+    /// around 9 stops.
+    ///
+    /// In polar interpolation spaces with [`HueInterpolation::Longer`], each
+    /// pair of inserted stops would also be interpolated along the longer
+    /// arc, so the result is only equivalent with the other hue
+    /// interpolation methods.
+    pub fn expand_hints(&mut self, count: usize) {
+        if self.stops.iter().all(|stop| stop.hint.is_none()) {
+            return;
+        }
+        let (space, hue) = (self.interpolation_space, self.hue_interpolation);
+        let mut stops = ColorStops::new();
+        for (i, stop) in self.stops.iter().enumerate() {
+            stops.push(ColorStop {
+                hint: None,
+                ..*stop
+            });
+            let Some(next) = self.stops.get(i + 1) else {
+                continue;
+            };
+            let width = next.offset - stop.offset;
+            if stop.hint.is_none() || stop.hint == Some(0.5) || width.is_nan() || width <= 0.0 {
+                continue;
+            }
+            for k in 1..=count {
+                let t = k as f32 / (count + 1) as f32;
+                let weight = stop.hint_weight(t);
+                stops.push(ColorStop {
+                    offset: stop.offset + width * t,
+                    color: stop.color.interpolate(next.color, weight, space, hue),
+                    hint: None,
+                });
+            }
+        }
+        self.stops = stops;
+    }
+
+    /// Returns the gradient with the transform applied to its geometry, or
+    /// `None` if the transformed gradient cannot be represented exactly.
+    ///
+    /// Sampling the result at a point `p` gives the same color as sampling
+    /// this gradient at `transform.inverse() * p`. This is always possible
+    /// for linear gradients with an invertible transform. Radial gradients
+    /// require a transform that preserves circles, which is a combination of
+    /// uniform scaling, rotation, reflection and translation. Sweep gradients
+    /// only allow uniform positive scaling and translation, since rotating
+    /// or reflecting would move the point where the sweep starts.
+    ///
+    /// Other transforms can be kept as a
+    /// [brush transform](crate::TransformedBrush) instead.
+    #[must_use]
+    pub fn transformed(&self, transform: Affine) -> Option<Self> {
+        let [a, b, c, d, _, _] = transform.as_coeffs();
+        let det = transform.determinant();
+        // Tolerances are relative to the scale of the transform.
+        let tolerance = 1e-9 * (a * a + b * b + c * c + d * d).max(f64::MIN_POSITIVE);
+        if !transform.is_finite() || det.abs() <= tolerance {
+            return None;
+        }
+        let kind = match self.kind {
+            GradientKind::Linear { start, end } => {
+                let dir = end - start;
+                let len2 = dir.hypot2();
+                let new_start = transform * start;
+                let new_end = if len2 == 0.0 {
+                    transform * end
+                } else {
+                    // The parameter is an affine function of the point, with
+                    // gradient L^-T * dir / |dir|^2 for the linear part L.
+                    let g = Vec2::new(d * dir.x - b * dir.y, a * dir.y - c * dir.x) / (det * len2);
+                    new_start + g / g.hypot2()
+                };
+                GradientKind::Linear {
+                    start: new_start,
+                    end: new_end,
+                }
+            }
+            GradientKind::Radial {
+                start_center,
+                start_radius,
+                end_center,
+                end_radius,
+            } => {
+                let is_similarity = (a * a + b * b - c * c - d * d).abs() <= tolerance
+                    && (a * c + b * d).abs() <= tolerance;
+                if !is_similarity {
+                    return None;
+                }
+                let scale = det.abs().sqrt();
+                GradientKind::Radial {
+                    start_center: transform * start_center,
+                    start_radius: (f64::from(start_radius) * scale) as f32,
+                    end_center: transform * end_center,
+                    end_radius: (f64::from(end_radius) * scale) as f32,
+                }
+            }
+            GradientKind::Sweep {
+                center,
+                start_angle,
+                end_angle,
+            } => {
+                let is_scale = b.abs() <= tolerance.sqrt()
+                    && c.abs() <= tolerance.sqrt()
+                    && a > 0.0
+                    && (a - d).abs() <= tolerance.sqrt();
+                if !is_scale {
+                    return None;
+                }
+                GradientKind::Sweep {
+                    center: transform * center,
+                    start_angle,
+                    end_angle,
+                }
+            }
+        };
+        Some(Self {
+            kind,
+            ..self.clone()
+        })
+    }
 }
 
 /// Trait for types that represent a source of color stops.
@@ -222,20 +793,453 @@ where
     }
 }
 
+/// Appends stops with the given colors, evenly distributed from `0.0` to `1.0`.
+fn collect_evenly_spaced(colors: &[impl Into<DynamicColor> + Copy], vec: &mut ColorStops) {
+    if !colors.is_empty() {
+        let denom = (colors.len() - 1).max(1) as f32;
+        vec.extend(colors.iter().enumerate().map(|(i, c)| ColorStop {
+            offset: (i as f32) / denom,
+            color: (*c).into(),
+            hint: None,
+        }));
+    }
+}
+
 impl ColorStopsSource for &'_ [Color] {
     fn collect_stops(&self, vec: &mut SmallVec<[ColorStop; 4]>) {
-        if !self.is_empty() {
-            let denom = (self.len() - 1).max(1) as f32;
-            vec.extend(self.iter().enumerate().map(|(i, c)| ColorStop {
-                offset: (i as f32) / denom,
-                color: *c,
-            }));
-        }
+        collect_evenly_spaced(self, vec);
     }
 }
 
 impl<const N: usize> ColorStopsSource for [Color; N] {
     fn collect_stops(&self, vec: &mut SmallVec<[ColorStop; 4]>) {
-        (&self[..]).collect_stops(vec);
+        collect_evenly_spaced(self, vec);
+    }
+}
+
+impl ColorStopsSource for &'_ [DynamicColor] {
+    fn collect_stops(&self, vec: &mut SmallVec<[ColorStop; 4]>) {
+        collect_evenly_spaced(self, vec);
+    }
+}
+
+impl<const N: usize> ColorStopsSource for [DynamicColor; N] {
+    fn collect_stops(&self, vec: &mut SmallVec<[ColorStop; 4]>) {
+        collect_evenly_spaced(self, vec);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorStop, Gradient, GradientError, StopNormalization};
+    use crate::{Color, ColorSpace, DynamicColor, TransformedBrush};
+    use kurbo::{Affine, Point};
+
+    extern crate alloc;
+    use alloc::vec::Vec;
+
+    /// Gradient stops from black to white, so that the red component of a
+    /// sample is its parameter.
+    const RAMP: [Color; 2] = [Color::BLACK, Color::WHITE];
+
+    /// Returns the red component and alpha of the gradient painted with the
+    /// brush transform at `(x, y)`.
+    fn sample(gradient: &Gradient, transform: Affine, x: f64, y: f64) -> (f32, f32) {
+        let color = gradient.sample(transform.inverse() * Point::new(x, y));
+        (color.components[0], color.alpha)
+    }
+
+    #[track_caller]
+    fn assert_param(gradient: &Gradient, transform: Affine, x: f64, y: f64, expected: f32) {
+        let (value, alpha) = sample(gradient, transform, x, y);
+        assert!(
+            (value - expected).abs() < 1e-4 && alpha == 1.0,
+            "sample at ({x}, {y}) was {value} with alpha {alpha}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn focal() {
+        let gradient = Gradient::new_focal((50.0, 50.0), 40.0, (60.0, 50.0), 0.0).with_stops(RAMP);
+        let identity = Affine::IDENTITY;
+        assert_param(&gradient, identity, 60.0, 50.0, 0.0);
+        // The end circle is around the center.
+        for (x, y) in [(90.0, 50.0), (10.0, 50.0), (50.0, 10.0), (50.0, 90.0)] {
+            assert_param(&gradient, identity, x, y, 1.0);
+        }
+        // Halfway from the focal point to the end circle.
+        assert_param(&gradient, identity, 75.0, 50.0, 0.5);
+        assert_param(&gradient, identity, 35.0, 50.0, 0.5);
+        // A focal circle with a radius.
+        let ring = Gradient::new_focal((50.0, 50.0), 40.0, (50.0, 50.0), 20.0).with_stops(RAMP);
+        assert_param(&ring, identity, 60.0, 50.0, 0.0);
+        assert_param(&ring, identity, 80.0, 50.0, 0.5);
+    }
+
+    #[test]
+    fn focal_outside() {
+        // A focal circle outside of the end circle produces a cone, and
+        // points behind the focal circle are not covered.
+        let gradient = Gradient::new_focal((0.0, 0.0), 10.0, (100.0, 0.0), 5.0).with_stops(RAMP);
+        let identity = Affine::IDENTITY;
+        assert_param(&gradient, identity, 105.0, 0.0, 0.0);
+        assert_param(&gradient, identity, 10.0, 0.0, 1.0);
+        let (_, alpha) = sample(&gradient, identity, 300.0, 0.0);
+        assert_eq!(alpha, 0.0, "point behind the focal circle");
+    }
+
+    #[test]
+    fn elliptical() {
+        let elliptical = Gradient::new_elliptical((100.0, 50.0), (40.0, 20.0)).with_stops(RAMP);
+        let (gradient, transform) = (elliptical.gradient, elliptical.transform);
+        assert_param(&gradient, transform, 100.0, 50.0, 0.0);
+        // The edge of the ellipse.
+        for (x, y) in [(140.0, 50.0), (60.0, 50.0), (100.0, 70.0), (100.0, 30.0)] {
+            assert_param(&gradient, transform, x, y, 1.0);
+        }
+        // Halfway along each axis.
+        assert_param(&gradient, transform, 120.0, 50.0, 0.5);
+        assert_param(&gradient, transform, 100.0, 40.0, 0.5);
+    }
+
+    #[test]
+    fn elliptical_focal() {
+        let elliptical =
+            Gradient::new_elliptical_focal((100.0, 50.0), (40.0, 20.0), (0.5, 0.0), 0.0)
+                .with_stops(RAMP);
+        let (gradient, transform) = (&elliptical.gradient, elliptical.transform);
+        // The focal point is halfway between the center and the right edge.
+        assert_param(gradient, transform, 120.0, 50.0, 0.0);
+        for (x, y) in [(140.0, 50.0), (60.0, 50.0), (100.0, 70.0), (100.0, 30.0)] {
+            assert_param(gradient, transform, x, y, 1.0);
+        }
+        assert_param(gradient, transform, 130.0, 50.0, 0.5);
+        assert_param(gradient, transform, 90.0, 50.0, 0.5);
+        // A focal radius of `0.5` is an ellipse with half of the radii.
+        let ring = Gradient::new_elliptical_focal((100.0, 50.0), (40.0, 20.0), (0.0, 0.0), 0.5)
+            .with_stops(RAMP);
+        assert_param(&ring.gradient, ring.transform, 120.0, 50.0, 0.0);
+        assert_param(&ring.gradient, ring.transform, 100.0, 60.0, 0.0);
+        assert_param(&ring.gradient, ring.transform, 130.0, 50.0, 0.5);
+        assert_param(&ring.gradient, ring.transform, 100.0, 65.0, 0.5);
+
+        let brush = TransformedBrush::from(elliptical.clone());
+        assert_eq!(
+            brush,
+            TransformedBrush::new(elliptical.gradient, Some(elliptical.transform)),
+            "brush conversion"
+        );
+    }
+
+    fn linear(stops: &[ColorStop]) -> Gradient {
+        Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops(stops)
+    }
+
+    fn offsets(gradient: &Gradient) -> Vec<f32> {
+        gradient.stops.iter().map(|stop| stop.offset).collect()
+    }
+
+    /// Stops with the given offsets and distinct colors.
+    fn stops(offsets: &[f32]) -> Vec<ColorStop> {
+        offsets
+            .iter()
+            .enumerate()
+            .map(|(i, &offset)| ColorStop::from((offset, Color::rgb8(i as u8, 0, 0))))
+            .collect()
+    }
+
+    #[track_caller]
+    fn assert_normalized(
+        input: &[ColorStop],
+        expected_offsets: &[f32],
+        expected: StopNormalization,
+    ) {
+        let mut gradient = linear(input);
+        let report = gradient.normalize_stops();
+        assert_eq!(report, expected, "report for {input:?}");
+        assert_eq!(
+            offsets(&gradient),
+            expected_offsets,
+            "offsets for {input:?}"
+        );
+        if !input.is_empty() {
+            assert_eq!(gradient.validate(), Ok(()), "normalized {input:?}");
+        }
+        assert!(
+            gradient.normalize_stops().is_unchanged(),
+            "normalizing {input:?} twice"
+        );
+    }
+
+    #[test]
+    fn normalize_stops() {
+        let unchanged = StopNormalization::default();
+        assert_normalized(&[], &[], unchanged);
+        assert_normalized(
+            &stops(&[0.0, 0.5, 0.5, 1.0]),
+            &[0.0, 0.5, 0.5, 1.0],
+            unchanged,
+        );
+        assert_normalized(
+            &stops(&[0.3]),
+            &[0.0, 1.0],
+            StopNormalization {
+                expanded_single_stop: true,
+                ..unchanged
+            },
+        );
+        assert_normalized(
+            &stops(&[-0.5, 0.5, 1.5]),
+            &[0.0, 0.5, 1.0],
+            StopNormalization {
+                clamped: 2,
+                ..unchanged
+            },
+        );
+        assert_normalized(
+            &stops(&[0.5, 0.2, 0.1, 1.0]),
+            &[0.5, 0.5, 0.5, 1.0],
+            StopNormalization {
+                raised: 2,
+                ..unchanged
+            },
+        );
+        assert_normalized(
+            &stops(&[f32::NAN, 0.2, f32::NAN, f32::NAN, 0.8, f32::NAN]),
+            &[0.0, 0.2, 0.4, 0.6, 0.8, 1.0],
+            StopNormalization {
+                resolved: 4,
+                ..unchanged
+            },
+        );
+        // A missing offset after a raised one is placed after it.
+        assert_normalized(
+            &stops(&[0.6, 0.3, f32::NAN, 1.0]),
+            &[0.6, 0.6, 0.8, 1.0],
+            StopNormalization {
+                raised: 1,
+                resolved: 1,
+                ..unchanged
+            },
+        );
+        let mut hinted = stops(&[0.0, 0.5, 1.0]);
+        hinted[0].hint = Some(1.5);
+        hinted[1].hint = Some(f32::NAN);
+        let mut gradient = linear(&hinted);
+        let report = gradient.normalize_stops();
+        assert_eq!(
+            report,
+            StopNormalization {
+                hints: 2,
+                ..unchanged
+            },
+            "hints"
+        );
+        assert_eq!(gradient.stops[0].hint, Some(1.0), "clamped hint");
+        assert_eq!(gradient.stops[1].hint, None, "NaN hint");
+
+        // The order of the stops is kept, rather than sorted.
+        let mut unsorted = linear(&stops(&[0.8, 0.2]));
+        unsorted.normalize_stops();
+        let reds: Vec<f32> = unsorted
+            .stops
+            .iter()
+            .map(|stop| stop.color.components[0])
+            .collect();
+        assert_eq!(reds, [0.0, 1.0 / 255.0], "stop order");
+    }
+
+    #[test]
+    fn gradient_errors() {
+        let empty = Gradient::new_linear((0.0, 0.0), (1.0, 0.0));
+        assert_eq!(empty.validate(), Err(GradientError::NoStops), "no stops");
+        let mut hinted = stops(&[0.0, 1.0]);
+        hinted[0].hint = Some(f32::NAN);
+        let mut non_finite = stops(&[0.0, 0.5, 1.0]);
+        non_finite[1].color = DynamicColor::new(ColorSpace::Oklab, [0.5, f32::NAN, 0.0], 1.0);
+        let mut infinite_alpha = stops(&[0.0, 1.0]);
+        infinite_alpha[1].color.alpha = f32::INFINITY;
+        for (stops, expected) in [
+            (stops(&[]), GradientError::NoStops),
+            (
+                stops(&[0.0, f32::NAN]),
+                GradientError::NonFiniteOffset { index: 1 },
+            ),
+            (
+                stops(&[f32::INFINITY]),
+                GradientError::NonFiniteOffset { index: 0 },
+            ),
+            (
+                stops(&[0.0, 0.5, 1.5]),
+                GradientError::OffsetOutOfRange { index: 2 },
+            ),
+            (
+                stops(&[-0.1, 1.0]),
+                GradientError::OffsetOutOfRange { index: 0 },
+            ),
+            (
+                stops(&[0.0, 0.6, 0.4, 1.0]),
+                GradientError::DecreasingOffset { index: 2 },
+            ),
+            (non_finite, GradientError::NonFiniteColor { index: 1 }),
+            (infinite_alpha, GradientError::NonFiniteColor { index: 1 }),
+            (hinted, GradientError::InvalidHint { index: 0 }),
+        ] {
+            let gradient = Gradient::new_linear((0.0, 0.0), (1.0, 0.0));
+            assert_eq!(
+                gradient.try_with_stops(&stops[..]).err(),
+                Some(expected),
+                "{stops:?}"
+            );
+            assert_eq!(linear(&stops).validate(), Err(expected), "{stops:?}");
+        }
+        // Duplicate offsets are hard transitions, and are valid.
+        let duplicate = Gradient::new_linear((0.0, 0.0), (1.0, 0.0))
+            .try_with_stops(&stops(&[0.0, 0.5, 0.5, 1.0])[..]);
+        assert!(duplicate.is_ok(), "duplicate offsets");
+        assert_eq!(
+            GradientError::DecreasingOffset { index: 2 }.to_string(),
+            "offset of color stop 2 is smaller than the previous offset",
+            "display"
+        );
+    }
+
+    #[test]
+    fn hint_weight() {
+        let stop = |hint: f32| ColorStop::from((0.0, Color::BLACK)).with_hint(hint);
+        let plain = ColorStop::from((0.0, Color::BLACK));
+        for i in 0..=20 {
+            let t = i as f32 / 20.0;
+            assert_eq!(plain.hint_weight(t), t, "no hint at {t}");
+            assert_eq!(stop(0.5).hint_weight(t), t, "hint at 0.5 is linear at {t}");
+            assert_eq!(stop(f32::NAN).hint_weight(t), t, "NaN hint at {t}");
+            // Hints at the ends are hard transitions, as in CSS.
+            assert_eq!(stop(0.0).hint_weight(t), 1.0, "hint at 0 at {t}");
+            let step = if t < 1.0 { 0.0 } else { 1.0 };
+            assert_eq!(stop(1.0).hint_weight(t), step, "hint at 1 at {t}");
+        }
+        for hint in [0.1, 0.25, 0.75, 0.9] {
+            let weight = stop(hint).hint_weight(hint);
+            assert!((weight - 0.5).abs() < 1e-6, "midpoint at hint {hint}");
+            assert_eq!(stop(hint).hint_weight(0.0), 0.0, "start at hint {hint}");
+            assert_eq!(stop(hint).hint_weight(1.0), 1.0, "end at hint {hint}");
+        }
+    }
+
+    #[test]
+    fn expand_hints() {
+        let original = Gradient::new_linear((0.0, 0.0), (100.0, 0.0))
+            .with_interpolation_space(ColorSpace::Oklab)
+            .with_stops([
+                ColorStop::from((0.0, Color::RED)).with_hint(0.2),
+                ColorStop::from((0.4, Color::BLUE)).with_hint(0.5),
+                ColorStop::from((0.6, Color::LIME)).with_hint(0.9),
+                ColorStop::from((0.6, Color::WHITE)).with_hint(0.3),
+                ColorStop::from((1.0, Color::BLACK)),
+            ]);
+        let mut expanded = original.clone();
+        expanded.expand_hints(9);
+        assert!(
+            expanded.stops.iter().all(|stop| stop.hint.is_none()),
+            "hints are removed"
+        );
+        // Hints at `0.5` and between stops at the same offset add no stops.
+        assert_eq!(expanded.stops.len(), 5 + 9 + 9, "number of stops");
+        for stop in &expanded.stops {
+            let point = Point::new(f64::from(stop.offset) * 100.0, 0.0);
+            if stop.offset == 0.6 {
+                // The hard transition is ambiguous exactly at its offset.
+                continue;
+            }
+            let expected = original.sample(point).convert(ColorSpace::Oklab);
+            let actual = expanded.sample(point).convert(ColorSpace::Oklab);
+            let close = expected
+                .components
+                .iter()
+                .zip(actual.components)
+                .all(|(e, a)| (e - a).abs() < 1e-5);
+            assert!(close, "at {}: {expected:?} != {actual:?}", stop.offset);
+        }
+        let mut plain = Gradient::new_linear((0.0, 0.0), (1.0, 0.0)).with_stops(RAMP);
+        let before = plain.clone();
+        plain.expand_hints(9);
+        assert_eq!(plain, before, "no hints");
+    }
+
+    /// Checks that the transformed gradient paints the same colors as the
+    /// gradient with the brush transform.
+    #[track_caller]
+    fn assert_transformed(gradient: &Gradient, transform: Affine, message: &str) {
+        let Some(transformed) = gradient.transformed(transform) else {
+            panic!("{message}: not transformed");
+        };
+        for i in 0..=8 {
+            for j in 0..=8 {
+                let point = Point::new(f64::from(i) * 12.5 - 50.0, f64::from(j) * 12.5 - 50.0);
+                let expected = gradient.sample(transform.inverse() * point);
+                let actual = transformed.sample(point);
+                let close = expected
+                    .components
+                    .iter()
+                    .chain([&expected.alpha])
+                    .zip(actual.components.iter().chain([&actual.alpha]))
+                    .all(|(e, a)| (e - a).abs() < 1e-3);
+                assert!(close, "{message} at {point:?}: {expected:?} != {actual:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn transformed() {
+        let linear = Gradient::new_linear((-10.0, 5.0), (20.0, -5.0)).with_stops(RAMP);
+        let radial =
+            Gradient::new_two_point_radial((0.0, 0.0), 5.0, (10.0, 5.0), 30.0).with_stops(RAMP);
+        let sweep = Gradient::new_sweep((5.0, -5.0), 0.5, 4.0).with_stops(RAMP);
+        let translate = Affine::translate((7.0, -3.0));
+        let rotate = Affine::rotate(0.7);
+        let skew = Affine::skew(0.5, 0.0);
+        let stretch = Affine::scale_non_uniform(2.0, 0.5);
+        let scale = Affine::scale(1.5);
+        let singular = Affine::scale_non_uniform(1.0, 0.0);
+        let infinite = Affine::scale(f64::INFINITY);
+
+        // Linear gradients accept every invertible transform.
+        for (transform, message) in [
+            (translate * rotate * scale, "similarity"),
+            (skew, "skew"),
+            (stretch, "non-uniform scale"),
+            (Affine::FLIP_X, "reflection"),
+        ] {
+            assert_transformed(&linear, transform, &format!("linear, {message}"));
+        }
+
+        // Radial gradients accept transforms that preserve circles.
+        for (transform, message) in [
+            (translate * rotate * scale, "similarity"),
+            (Affine::FLIP_Y * scale, "reflection"),
+        ] {
+            assert_transformed(&radial, transform, &format!("radial, {message}"));
+        }
+        for (transform, message) in [(skew, "skew"), (stretch, "non-uniform scale")] {
+            assert_eq!(radial.transformed(transform), None, "radial, {message}");
+        }
+
+        // Sweep gradients only accept positive uniform scales and
+        // translations.
+        assert_transformed(&sweep, translate * scale, "sweep, scale");
+        for (transform, message) in [
+            (rotate, "rotation"),
+            (Affine::FLIP_X, "reflection"),
+            (Affine::scale(-1.0), "negative scale"),
+            (skew, "skew"),
+            (stretch, "non-uniform scale"),
+        ] {
+            assert_eq!(sweep.transformed(transform), None, "sweep, {message}");
+        }
+
+        for gradient in [&linear, &radial, &sweep] {
+            assert_eq!(gradient.transformed(singular), None, "singular transform");
+            assert_eq!(gradient.transformed(infinite), None, "infinite transform");
+        }
     }
 }
@@ -0,0 +1,363 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Classification and simplification of gradients.
+
+use super::gradient_sample::DEGENERATE_TOLERANCE;
+use super::{Brush, DynamicColor, Extend, Gradient, GradientKind, PremulDynamicColor};
+
+use kurbo::Point;
+
+/// Tolerance used to compare colors and offsets of color stops.
+const STOP_TOLERANCE: f32 = 1e-6;
+
+/// Properties of a [gradient](Gradient) that allow renderers to take fast
+/// paths, computed by [`Gradient::analyze`].
+///
+/// The analysis is conservative: a property is only reported if it holds for
+/// the colors produced by [`Gradient::sample`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GradientAnalysis {
+    /// The color painted at every point, if the gradient is equivalent to a
+    /// solid color.
+    pub solid_color: Option<DynamicColor>,
+    /// Whether every point is painted with a fully opaque color.
+    pub is_opaque: bool,
+    /// Whether the geometry is degenerate, such as a linear gradient with
+    /// equal start and end points.
+    pub is_degenerate: bool,
+    /// Whether every transition between stops of different colors is a hard
+    /// transition, so the gradient only paints bands of solid colors.
+    pub has_only_hard_stops: bool,
+}
+
+impl GradientAnalysis {
+    /// Returns true if the gradient is equivalent to a solid color.
+    #[must_use]
+    pub fn is_solid(&self) -> bool {
+        self.solid_color.is_some()
+    }
+}
+
+impl Gradient {
+    /// Classifies the gradient.
+    ///
+    /// Degenerate geometry follows the behavior described for
+    /// [`sample`](Self::sample). In particular, a degenerate linear gradient,
+    /// or a radial gradient with two equal circles of zero radius, paints the
+    /// color of the last stop with [`Extend::Pad`], as specified by SVG, and
+    /// is equivalent to a solid color. A gradient without stops is equivalent
+    /// to a transparent color.
+    #[must_use]
+    pub fn analyze(&self) -> GradientAnalysis {
+        let is_degenerate = self.has_degenerate_geometry();
+        let uniform_param = is_degenerate && self.has_uniform_param();
+        let covers_plane = !matches!(self.extend, Extend::Decal) && self.covers_plane();
+        let stops_color = self.uniform_stops_color();
+        let solid_color = if uniform_param {
+            Some(self.sample(Point::ZERO))
+        } else {
+            stops_color.filter(|color| covers_plane || color.alpha == 0.0)
+        };
+        let is_opaque = !self.stops.is_empty()
+            && covers_plane
+            && self
+                .stops
+                .iter()
+                .all(|stop| stop.color.alpha >= 1.0 - STOP_TOLERANCE);
+        let has_only_hard_stops = self.stops.windows(2).all(|pair| {
+            let (a, b) = (&pair[0], &pair[1]);
+            b.offset - a.offset <= STOP_TOLERANCE
+                || a.hint.is_some_and(|hint| hint <= 0.0 || hint >= 1.0)
+                || self.same_color(a.color, b.color)
+        });
+        GradientAnalysis {
+            solid_color,
+            is_opaque,
+            is_degenerate,
+            has_only_hard_stops,
+        }
+    }
+
+    /// Reduces the gradient to a [`Brush::DynamicSolid`] brush if it is
+    /// [equivalent](GradientAnalysis::solid_color) to a solid color, and
+    /// otherwise returns it as a gradient brush.
+    #[must_use]
+    pub fn simplify(self) -> Brush {
+        match self.analyze().solid_color {
+            Some(color) => Brush::DynamicSolid(color),
+            None => Brush::Gradient(self),
+        }
+    }
+
+    /// Returns true if the gradient geometry is degenerate.
+    fn has_degenerate_geometry(&self) -> bool {
+        match self.kind {
+            GradientKind::Linear { start, end } => (end - start).hypot2() <= DEGENERATE_TOLERANCE,
+            GradientKind::Radial {
+                start_center,
+                start_radius,
+                end_center,
+                end_radius,
+            } => {
+                (end_center - start_center).hypot2() <= DEGENERATE_TOLERANCE
+                    && f64::from(end_radius - start_radius).abs() <= DEGENERATE_TOLERANCE
+            }
+            GradientKind::Sweep {
+                start_angle,
+                end_angle,
+                ..
+            } => f64::from(end_angle - start_angle).abs() <= DEGENERATE_TOLERANCE,
+        }
+    }
+
+    /// Returns true if degenerate geometry maps every point to the same
+    /// color, rather than to a hard transition between the first and last
+    /// stops.
+    fn has_uniform_param(&self) -> bool {
+        match self.kind {
+            GradientKind::Linear { .. } => true,
+            GradientKind::Radial { start_radius, .. } => {
+                self.extend != Extend::Pad || start_radius <= 0.0
+            }
+            GradientKind::Sweep { .. } => self.extend != Extend::Pad,
+        }
+    }
+
+    /// Returns true if every point is painted when the extend mode is not
+    /// [`Extend::Decal`].
+    fn covers_plane(&self) -> bool {
+        match self.kind {
+            GradientKind::Linear { .. } | GradientKind::Sweep { .. } => true,
+            GradientKind::Radial {
+                start_center,
+                start_radius,
+                end_center,
+                end_radius,
+            } => {
+                // A two point conical gradient only covers the plane when one
+                // circle lies strictly inside of the other.
+                let distance = (end_center - start_center).hypot();
+                let dr = f64::from(end_radius - start_radius).abs();
+                self.has_degenerate_geometry() || distance + DEGENERATE_TOLERANCE < dr
+            }
+        }
+    }
+
+    /// Returns the color of the stops if they all have the same color.
+    fn uniform_stops_color(&self) -> Option<DynamicColor> {
+        let Some(first) = self.stops.first() else {
+            return Some(DynamicColor::new(self.interpolation_space, [0.0; 3], 0.0));
+        };
+        self.stops
+            .iter()
+            .all(|stop| self.same_color(first.color, stop.color))
+            .then(|| first.color.convert(self.interpolation_space))
+    }
+
+    /// Returns true if the colors are the same after premultiplying in the
+    /// interpolation space.
+    fn same_color(&self, a: DynamicColor, b: DynamicColor) -> bool {
+        let space = self.interpolation_space;
+        let a = PremulDynamicColor::from(a.convert(space));
+        let b = PremulDynamicColor::from(b.convert(space));
+        (a.alpha - b.alpha).abs() <= STOP_TOLERANCE
+            && a.components
+                .iter()
+                .zip(b.components)
+                .all(|(a, b)| (a - b).abs() <= STOP_TOLERANCE)
+    }
+}
+
+impl Brush {
+    /// Reduces gradients that are equivalent to a solid color to a solid
+    /// color brush, see [`Gradient::simplify`].
+    #[must_use]
+    pub fn simplify(self) -> Self {
+        match self {
+            Self::Gradient(gradient) => gradient.simplify(),
+            brush => brush,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Brush, Color, ColorStop, DynamicColor, Extend, Gradient};
+
+    /// Gradient stops from black to white, whose average is a middle gray.
+    const RAMP: [Color; 2] = [Color::BLACK, Color::WHITE];
+
+    #[track_caller]
+    fn assert_solid(gradient: &Gradient, expected: [f32; 4]) {
+        let color = gradient.analyze().solid_color;
+        let components =
+            color.map(|c| [c.components[0], c.components[1], c.components[2], c.alpha]);
+        assert!(
+            components.is_some_and(|c| c.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-4)),
+            "solid color was {components:?}, expected {expected:?}"
+        );
+    }
+
+    #[track_caller]
+    fn assert_not_solid(gradient: &Gradient) {
+        let analysis = gradient.analyze();
+        assert!(
+            !analysis.is_solid(),
+            "unexpected solid color {:?}",
+            analysis.solid_color
+        );
+    }
+
+    #[test]
+    fn ramp() {
+        let analysis = Gradient::new_linear((0.0, 0.0), (100.0, 0.0))
+            .with_stops(RAMP)
+            .analyze();
+        assert!(!analysis.is_solid(), "ramp is not solid");
+        assert!(analysis.is_opaque, "ramp is opaque");
+        assert!(!analysis.is_degenerate, "ramp is not degenerate");
+        assert!(!analysis.has_only_hard_stops, "ramp has a soft transition");
+    }
+
+    #[test]
+    fn uniform_stops() {
+        let red = Gradient::new_radial((0.0, 0.0), 10.0).with_stops([Color::RED, Color::RED]);
+        assert_solid(&red, [1.0, 0.0, 0.0, 1.0]);
+        assert!(red.analyze().is_opaque, "opaque stops");
+        assert!(red.analyze().has_only_hard_stops, "no transitions");
+        let translucent = red.with_stops([Color::RED.with_alpha_factor(0.5); 2]);
+        assert!(!translucent.analyze().is_opaque, "translucent stops");
+        // Stops that only differ in the color of transparent stops are the
+        // same after premultiplying.
+        let clear = Gradient::new_linear((0.0, 0.0), (100.0, 0.0))
+            .with_stops([Color::TRANSPARENT, Color::RED.with_alpha_factor(0.0)]);
+        assert_solid(&clear, [0.0, 0.0, 0.0, 0.0]);
+        let empty = Gradient::new_linear((0.0, 0.0), (100.0, 0.0));
+        assert_solid(&empty, [0.0, 0.0, 0.0, 0.0]);
+        assert!(!empty.analyze().is_opaque, "no stops");
+    }
+
+    #[test]
+    fn hard_stops() {
+        let bands = Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops([
+            (0.0, Color::RED),
+            (0.5, Color::RED),
+            (0.5, Color::BLUE),
+            (1.0, Color::BLUE),
+        ]);
+        let analysis = bands.analyze();
+        assert!(analysis.has_only_hard_stops, "bands of solid colors");
+        assert!(!analysis.is_solid(), "two colors");
+        for hint in [0.0, 1.0] {
+            let hinted = bands.clone().with_stops([
+                ColorStop::from((0.0, Color::RED)).with_hint(hint),
+                ColorStop::from((1.0, Color::BLUE)),
+            ]);
+            assert!(
+                hinted.analyze().has_only_hard_stops,
+                "hint {hint} is a hard transition"
+            );
+        }
+        let soft = bands.with_stops([
+            ColorStop::from((0.0, Color::RED)).with_hint(0.25),
+            ColorStop::from((1.0, Color::BLUE)),
+        ]);
+        assert!(!soft.analyze().has_only_hard_stops, "hint 0.25 is soft");
+    }
+
+    #[test]
+    fn degenerate_linear() {
+        let gradient = Gradient::new_linear((50.0, 50.0), (50.0, 50.0)).with_stops(RAMP);
+        assert!(gradient.analyze().is_degenerate, "equal points");
+        assert_solid(&gradient, [1.0, 1.0, 1.0, 1.0]);
+        assert_solid(
+            &gradient.clone().with_extend(Extend::Repeat),
+            [0.5, 0.5, 0.5, 1.0],
+        );
+        let decal = gradient.with_extend(Extend::Decal);
+        assert_solid(&decal, [0.0, 0.0, 0.0, 0.0]);
+        assert!(!decal.analyze().is_opaque, "decal is transparent");
+    }
+
+    #[test]
+    fn degenerate_radial() {
+        // Two circles of zero radius paint the last stop everywhere.
+        let point =
+            Gradient::new_two_point_radial((50.0, 50.0), 0.0, (50.0, 50.0), 0.0).with_stops(RAMP);
+        assert!(point.analyze().is_degenerate, "equal circles");
+        assert_solid(&point, [1.0, 1.0, 1.0, 1.0]);
+        // Two equal circles with a radius are a hard transition from the
+        // first stop inside to the last stop outside.
+        let ring =
+            Gradient::new_two_point_radial((50.0, 50.0), 10.0, (50.0, 50.0), 10.0).with_stops(RAMP);
+        assert!(ring.analyze().is_degenerate, "equal circles");
+        assert!(ring.analyze().is_opaque, "ring covers the plane");
+        assert_not_solid(&ring);
+        assert_solid(
+            &ring.clone().with_extend(Extend::Reflect),
+            [0.5, 0.5, 0.5, 1.0],
+        );
+        assert_solid(&ring.with_extend(Extend::Decal), [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn degenerate_sweep() {
+        // With `Pad`, a sweep with equal angles is a hard transition at the
+        // start angle.
+        let gradient = Gradient::new_sweep((50.0, 50.0), 1.0, 1.0).with_stops(RAMP);
+        assert!(gradient.analyze().is_degenerate, "equal angles");
+        assert!(gradient.analyze().is_opaque, "sweep covers the plane");
+        assert_not_solid(&gradient);
+        assert_solid(
+            &gradient.clone().with_extend(Extend::Repeat),
+            [0.5, 0.5, 0.5, 1.0],
+        );
+        assert_solid(&gradient.with_extend(Extend::Decal), [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn coverage() {
+        let red = [Color::RED, Color::RED];
+        // Nested circles cover the plane.
+        let nested =
+            Gradient::new_two_point_radial((10.0, 0.0), 5.0, (0.0, 0.0), 50.0).with_stops(red);
+        assert!(nested.analyze().is_opaque, "nested circles");
+        assert_solid(&nested, [1.0, 0.0, 0.0, 1.0]);
+        // Circles that are not nested form a cone, outside of which the
+        // gradient is transparent.
+        let cone =
+            Gradient::new_two_point_radial((0.0, 0.0), 5.0, (100.0, 0.0), 10.0).with_stops(red);
+        assert!(!cone.analyze().is_opaque, "cone");
+        assert!(!cone.analyze().is_degenerate, "cone is not degenerate");
+        assert_not_solid(&cone);
+        // A transparent cone is transparent everywhere.
+        let clear = cone.with_stops([Color::TRANSPARENT; 2]);
+        assert_solid(&clear, [0.0, 0.0, 0.0, 0.0]);
+        // Decal leaves points outside of the stops transparent.
+        let decal = Gradient::new_linear((0.0, 0.0), (100.0, 0.0))
+            .with_stops(red)
+            .with_extend(Extend::Decal);
+        assert!(!decal.analyze().is_opaque, "decal");
+        assert_not_solid(&decal);
+    }
+
+    #[test]
+    fn simplify() {
+        let solid = Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops([Color::RED; 2]);
+        assert!(
+            matches!(solid.simplify(), Brush::DynamicSolid(color) if color.to_color() == Color::RED),
+            "uniform stops simplify to a solid color"
+        );
+        let ramp = Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops(RAMP);
+        assert!(
+            matches!(Brush::Gradient(ramp).simplify(), Brush::Gradient(_)),
+            "a ramp remains a gradient"
+        );
+        let color = DynamicColor::from(Color::BLUE);
+        assert!(
+            matches!(Brush::DynamicSolid(color).simplify(), Brush::DynamicSolid(c) if c == color),
+            "solid brushes are unchanged"
+        );
+    }
+}
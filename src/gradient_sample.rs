@@ -0,0 +1,462 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! CPU reference evaluation of gradients.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::{ColorSpace, DynamicColor, Extend, Gradient, GradientKind};
+
+use kurbo::{Point, Vec2};
+
+/// Tolerance used to detect degenerate gradient geometry.
+pub(crate) const DEGENERATE_TOLERANCE: f64 = 1e-9;
+
+/// Result of mapping a point to a gradient parameter.
+enum Param {
+    /// The point maps to the given parameter, before the extend mode is applied.
+    Value(f64),
+    /// The point is not covered by the gradient.
+    Uncovered,
+    /// The gradient geometry is degenerate, so the color does not depend on
+    /// the point.
+    Degenerate,
+}
+
+impl Gradient {
+    /// Returns the color of the gradient at the given point.
+    ///
+    /// This is a reference implementation intended for testing renderers and
+    /// for CPU fallback rendering, and favors precision over speed. The
+    /// result is in the [interpolation space](Gradient::interpolation_space)
+    /// of the gradient.
+    ///
+    /// The color is determined as follows for each kind of gradient:
+    /// - Linear gradients project the point onto the line from the start to
+    ///   the end point.
+    /// - Radial gradients are two point conical gradients as defined by the
+    ///   HTML canvas and SVG specifications: the parameter is the largest `t`
+    ///   for which the point lies on the circle interpolated between the start
+    ///   and end circles with a non-negative radius. Points for which no such
+    ///   circle exists are transparent.
+    /// - Sweep gradients measure the angle of the point around the center in
+    ///   radians, in the range `0` to `2π` from the positive x-axis, and map
+    ///   the start angle to `0.0` and the end angle to `1.0`.
+    ///
+    /// Degenerate geometry, such as a linear gradient with equal start and end
+    /// points, follows the behavior of Skia: [`Extend::Pad`] produces the
+    /// color of the last stop (or a hard transition between the first and
+    /// last stops for radial and sweep gradients), [`Extend::Decal`] is
+    /// transparent, while the other extend modes produce the average color of
+    /// the stops.
+    #[must_use]
+    pub fn sample(&self, point: Point) -> DynamicColor {
+        match self.param(point) {
+            Param::Value(t) => self.sample_stops(t as f32),
+            Param::Uncovered => self.transparent(),
+            Param::Degenerate => match self.extend {
+                Extend::Pad => self.sample_stops(1.0),
+                Extend::Decal => self.transparent(),
+                Extend::Repeat | Extend::Reflect => self.average_color(),
+            },
+        }
+    }
+
+    /// Returns the color of the color stops at the parameter `t`, after
+    /// applying the extend mode of the gradient.
+    ///
+    /// With [`Extend::Decal`], parameters outside of the range `0.0` to `1.0`
+    /// are transparent.
+    ///
+    /// The stops are expected to be sorted by offset, see
+    /// [`Gradient::normalize_stops`]. Color hints shape the transition
+    /// between stops as described in
+    /// [`ColorStop::hint_weight`](crate::ColorStop::hint_weight). Between
+    /// two stops at the same offset, the later stop is used. The result is in
+    /// the [interpolation space](Gradient::interpolation_space) of the
+    /// gradient, and is transparent if there are no stops.
+    #[must_use]
+    pub fn sample_stops(&self, t: f32) -> DynamicColor {
+        match self.extend.apply(t) {
+            Some(t) => self.interpolate_stops(t),
+            None => self.transparent(),
+        }
+    }
+
+    /// Returns the color of the color stops at the parameter `t`, without
+    /// applying the extend mode.
+    pub(crate) fn interpolate_stops(&self, t: f32) -> DynamicColor {
+        let space = self.interpolation_space;
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return self.transparent(),
+        };
+        if t < first.offset {
+            return first.color.convert(space);
+        }
+        if t >= last.offset {
+            return last.color.convert(space);
+        }
+        for pair in self.stops.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if t < b.offset {
+                let width = b.offset - a.offset;
+                let local = if width > 0.0 {
+                    a.hint_weight((t - a.offset) / width)
+                } else {
+                    0.0
+                };
+                return a
+                    .color
+                    .interpolate(b.color, local, space, self.hue_interpolation);
+            }
+        }
+        last.color.convert(space)
+    }
+
+    fn transparent(&self) -> DynamicColor {
+        DynamicColor::new(self.interpolation_space, [0.0; 3], 0.0)
+    }
+
+    /// Maps a point to the gradient parameter.
+    fn param(&self, point: Point) -> Param {
+        match self.kind {
+            GradientKind::Linear { start, end } => {
+                let d = end - start;
+                let len2 = d.hypot2();
+                if len2 <= DEGENERATE_TOLERANCE {
+                    return Param::Degenerate;
+                }
+                Param::Value((point - start).dot(d) / len2)
+            }
+            GradientKind::Radial {
+                start_center,
+                start_radius,
+                end_center,
+                end_radius,
+            } => two_point_conical(
+                point,
+                start_center,
+                start_radius.into(),
+                end_center,
+                end_radius.into(),
+                self.extend,
+            ),
+            GradientKind::Sweep {
+                center,
+                start_angle,
+                end_angle,
+            } => {
+                let (start, end) = (f64::from(start_angle), f64::from(end_angle));
+                let v = point - center;
+                let tau = core::f64::consts::TAU;
+                let mut angle = v.y.atan2(v.x);
+                if angle < 0.0 {
+                    angle += tau;
+                }
+                if (end - start).abs() <= DEGENERATE_TOLERANCE {
+                    return match self.extend {
+                        // A hard transition from the first to the last stop.
+                        Extend::Pad => Param::Value(if angle < start { 0.0 } else { 1.0 }),
+                        _ => Param::Degenerate,
+                    };
+                }
+                Param::Value((angle - start) / (end - start))
+            }
+        }
+    }
+
+    /// Computes the average color of the stops over the range `0.0` to `1.0`.
+    ///
+    /// Averaging is done with premultiplied alpha in the interpolation space,
+    /// or in Oklab if the interpolation space is polar.
+    fn average_color(&self) -> DynamicColor {
+        let space = self.interpolation_space;
+        let average_space = if space.is_polar() {
+            ColorSpace::Oklab
+        } else {
+            space
+        };
+        let mut sum = [0.0_f64; 3];
+        let mut alpha_sum = 0.0_f64;
+        let mut accumulate = |color: DynamicColor, weight: f32| {
+            if weight <= 0.0 {
+                return;
+            }
+            let color = color.convert(average_space);
+            let alpha = f64::from(color.alpha) * f64::from(weight);
+            for (s, c) in sum.iter_mut().zip(color.components) {
+                *s += f64::from(c) * alpha;
+            }
+            alpha_sum += alpha;
+        };
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return self.transparent(),
+        };
+        accumulate(first.color, first.offset.min(1.0));
+        for pair in self.stops.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            let width = b.offset.min(1.0) - a.offset.max(0.0);
+            let mid =
+                a.color
+                    .interpolate(b.color, a.hint_weight(0.5), space, self.hue_interpolation);
+            accumulate(mid, width);
+        }
+        accumulate(last.color, 1.0 - last.offset.max(0.0));
+        if alpha_sum <= 0.0 {
+            return self.transparent();
+        }
+        let components = sum.map(|s| (s / alpha_sum) as f32);
+        DynamicColor::new(average_space, components, alpha_sum as f32).convert(space)
+    }
+}
+
+/// Computes the parameter of a two point conical gradient at `p`.
+///
+/// See <https://html.spec.whatwg.org/multipage/canvas.html#dom-context-2d-createradialgradient>.
+fn two_point_conical(p: Point, c0: Point, r0: f64, c1: Point, r1: f64, extend: Extend) -> Param {
+    let cd: Vec2 = c1 - c0;
+    let dr = r1 - r0;
+    let pd: Vec2 = p - c0;
+    if cd.hypot2() <= DEGENERATE_TOLERANCE && dr.abs() <= DEGENERATE_TOLERANCE {
+        // Both circles are the same, so the interpolation region is an
+        // infinitely thin ring.
+        return match extend {
+            Extend::Pad if r0 > 0.0 => Param::Value(if pd.hypot() < r0 { 0.0 } else { 1.0 }),
+            _ => Param::Degenerate,
+        };
+    }
+    let radius_ok = |t: f64| r0 + t * dr >= 0.0;
+    // Solve |pd - t * cd| = r0 + t * dr for t, which gives the quadratic
+    // a * t^2 - 2 * b * t + c = 0.
+    let a = cd.hypot2() - dr * dr;
+    let b = pd.dot(cd) + r0 * dr;
+    let c = pd.hypot2() - r0 * r0;
+    if a.abs() <= DEGENERATE_TOLERANCE {
+        // The quadratic term vanishes when one circle touches the other
+        // internally, leaving a linear equation.
+        if b.abs() <= DEGENERATE_TOLERANCE {
+            return Param::Uncovered;
+        }
+        let t = c / (2.0 * b);
+        return if radius_ok(t) {
+            Param::Value(t)
+        } else {
+            Param::Uncovered
+        };
+    }
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return Param::Uncovered;
+    }
+    let sqrt = discriminant.sqrt();
+    let t0 = (b + sqrt) / a;
+    let t1 = (b - sqrt) / a;
+    let (larger, smaller) = if t0 >= t1 { (t0, t1) } else { (t1, t0) };
+    if radius_ok(larger) {
+        Param::Value(larger)
+    } else if radius_ok(smaller) {
+        Param::Value(smaller)
+    } else {
+        Param::Uncovered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color, ColorStop, Extend, Gradient};
+    use core::f32::consts::PI;
+    use kurbo::Point;
+
+    /// Gradient stops from black to white, so that the red component of a
+    /// sample is its parameter.
+    const RAMP: [Color; 2] = [Color::BLACK, Color::WHITE];
+
+    /// Returns the red component and alpha of the gradient at `(x, y)`.
+    fn sample(gradient: &Gradient, x: f64, y: f64) -> (f32, f32) {
+        let color = gradient.sample(Point::new(x, y));
+        (color.components[0], color.alpha)
+    }
+
+    #[track_caller]
+    fn assert_param(gradient: &Gradient, x: f64, y: f64, expected: f32) {
+        let (value, alpha) = sample(gradient, x, y);
+        assert!(
+            (value - expected).abs() < 1e-4 && alpha == 1.0,
+            "sample at ({x}, {y}) was {value} with alpha {alpha}, expected {expected}"
+        );
+    }
+
+    #[track_caller]
+    fn assert_transparent(gradient: &Gradient, x: f64, y: f64) {
+        let (_, alpha) = sample(gradient, x, y);
+        assert_eq!(alpha, 0.0, "sample at ({x}, {y}) should be transparent");
+    }
+
+    #[test]
+    fn linear() {
+        let gradient = Gradient::new_linear((10.0, 0.0), (110.0, 0.0)).with_stops(RAMP);
+        assert_param(&gradient, 10.0, 0.0, 0.0);
+        assert_param(&gradient, 35.0, -40.0, 0.25);
+        assert_param(&gradient, 110.0, 80.0, 1.0);
+        let diagonal = Gradient::new_linear((0.0, 0.0), (100.0, 100.0)).with_stops(RAMP);
+        assert_param(&diagonal, 100.0, 0.0, 0.5);
+        assert_param(&diagonal, 25.0, 25.0, 0.25);
+    }
+
+    #[test]
+    fn extend_modes() {
+        let gradient = Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops(RAMP);
+        let cases: [(Extend, [Option<f32>; 5]); 4] = [
+            (
+                Extend::Pad,
+                [Some(0.0), Some(0.0), Some(0.5), Some(1.0), Some(1.0)],
+            ),
+            (
+                Extend::Repeat,
+                [Some(0.75), Some(0.75), Some(0.5), Some(0.25), Some(0.5)],
+            ),
+            (
+                Extend::Reflect,
+                [Some(0.75), Some(0.25), Some(0.5), Some(0.75), Some(0.5)],
+            ),
+            (Extend::Decal, [None, None, Some(0.5), None, None]),
+        ];
+        for (extend, params) in cases {
+            let extended = gradient.clone().with_extend(extend);
+            for (x, param) in [-125.0, -25.0, 50.0, 125.0, 250.0].into_iter().zip(params) {
+                match param {
+                    Some(t) => assert_param(&extended, x, 0.0, t),
+                    None => assert_transparent(&extended, x, 0.0),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn radial() {
+        let gradient = Gradient::new_radial((50.0, 50.0), 40.0).with_stops(RAMP);
+        assert_param(&gradient, 50.0, 50.0, 0.0);
+        assert_param(&gradient, 50.0, 70.0, 0.5);
+        assert_param(&gradient, 26.0, 82.0, 1.0);
+        assert_param(&gradient, 0.0, 0.0, 1.0);
+        let repeat = gradient.with_extend(Extend::Repeat);
+        assert_param(&repeat, 110.0, 50.0, 0.5);
+    }
+
+    #[test]
+    fn two_point_conical() {
+        // The start circle is inside of the end circle, so every point is
+        // covered.
+        let inside =
+            Gradient::new_two_point_radial((20.0, 0.0), 0.0, (0.0, 0.0), 50.0).with_stops(RAMP);
+        assert_param(&inside, 20.0, 0.0, 0.0);
+        assert_param(&inside, 50.0, 0.0, 1.0);
+        assert_param(&inside, -50.0, 0.0, 1.0);
+        assert_param(&inside, 35.0, 0.0, 0.5);
+        assert_param(&inside, 200.0, 200.0, 1.0);
+
+        // Two separate circles of the same radius form a cylinder, and points
+        // outside of it are not covered.
+        let cylinder =
+            Gradient::new_two_point_radial((0.0, 0.0), 10.0, (100.0, 0.0), 10.0).with_stops(RAMP);
+        assert_param(&cylinder, 50.0, 0.0, 0.6);
+        assert_param(&cylinder, 50.0, 10.0, 0.5);
+        assert_param(&cylinder, -50.0, 0.0, 0.0);
+        assert_transparent(&cylinder, 50.0, 20.0);
+        assert_transparent(&cylinder, -50.0, -11.0);
+
+        // A growing cone from a point, with the region behind the apex not
+        // covered.
+        let cone = Gradient::new_two_point_radial((0.0, 0.0), 0.0, (100.0, 0.0), 50.0)
+            .with_stops(RAMP)
+            .with_extend(Extend::Repeat);
+        assert_param(&cone, 0.0, 0.0, 0.0);
+        assert_param(&cone, 150.0, 0.0, 0.0);
+        assert_param(&cone, 75.0, 0.0, 0.5);
+        // The point is on the end circle, but the circle at `t = 5/3` also
+        // passes through it, and the larger parameter is used.
+        assert_param(&cone, 100.0, 50.0, 2.0 / 3.0);
+        assert_transparent(&cone, -10.0, 0.0);
+        assert_transparent(&cone, 10.0, 50.0);
+    }
+
+    #[test]
+    fn sweep() {
+        let gradient = Gradient::new_sweep((10.0, 10.0), 0.0, PI).with_stops(RAMP);
+        assert_param(&gradient, 20.0, 10.0, 0.0);
+        // The y-axis points down, so positive angles are clockwise.
+        assert_param(&gradient, 20.0, 20.0, 0.25);
+        assert_param(&gradient, 10.0, 20.0, 0.5);
+        assert_param(&gradient, 0.0, 10.0 + 1e-9, 1.0);
+        let quarter = Gradient::new_sweep((0.0, 0.0), 0.5 * PI, PI);
+        let behind = [
+            (Extend::Pad, Some(1.0)),
+            (Extend::Repeat, Some(0.5)),
+            (Extend::Reflect, Some(0.5)),
+            (Extend::Decal, None),
+        ];
+        // At 5π/4, the parameter is 1.5.
+        for (extend, expected) in behind {
+            let extended = quarter.clone().with_stops(RAMP).with_extend(extend);
+            match expected {
+                Some(t) => assert_param(&extended, -1.0, -1.0, t),
+                None => assert_transparent(&extended, -1.0, -1.0),
+            }
+        }
+    }
+
+    #[test]
+    fn hard_stops() {
+        let gradient = Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops([
+            (0.0, Color::BLACK),
+            (0.5, Color::BLACK),
+            (0.5, Color::WHITE),
+            (1.0, Color::WHITE),
+        ]);
+        assert_param(&gradient, 49.9, 0.0, 0.0);
+        assert_param(&gradient, 50.0, 0.0, 1.0);
+        assert_param(&gradient, 75.0, 0.0, 1.0);
+        // The later of several stops at the same offset is used.
+        let triple = Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops([
+            (0.0, Color::BLACK),
+            (0.5, Color::BLACK),
+            (0.5, Color::GRAY),
+            (0.5, Color::WHITE),
+            (1.0, Color::WHITE),
+        ]);
+        assert_param(&triple, 50.0, 0.0, 1.0);
+    }
+
+    #[test]
+    fn hints() {
+        let stops = [
+            ColorStop::from((0.0, Color::BLACK)).with_hint(0.25),
+            ColorStop::from((1.0, Color::WHITE)),
+        ];
+        let gradient = Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops(stops);
+        assert_param(&gradient, 0.0, 0.0, 0.0);
+        assert_param(&gradient, 25.0, 0.0, 0.5);
+        assert_param(&gradient, 50.0, 0.0, core::f32::consts::FRAC_1_SQRT_2);
+        assert_param(&gradient, 100.0, 0.0, 1.0);
+        let hard = Gradient::new_linear((0.0, 0.0), (100.0, 0.0)).with_stops([
+            ColorStop::from((0.0, Color::BLACK)).with_hint(1.0),
+            ColorStop::from((1.0, Color::WHITE)),
+        ]);
+        assert_param(&hard, 99.0, 0.0, 0.0);
+        assert_param(&hard, 100.0, 0.0, 1.0);
+    }
+
+    #[test]
+    fn degenerate() {
+        let gradient = Gradient::new_linear((50.0, 50.0), (50.0, 50.0)).with_stops(RAMP);
+        assert_param(&gradient, 0.0, 0.0, 1.0);
+        assert_param(&gradient.clone().with_extend(Extend::Repeat), 0.0, 0.0, 0.5);
+        assert_transparent(&gradient.with_extend(Extend::Decal), 0.0, 0.0);
+        let empty = Gradient::new_linear((0.0, 0.0), (100.0, 0.0));
+        assert_transparent(&empty, 50.0, 0.0);
+    }
+}
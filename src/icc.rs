@@ -0,0 +1,307 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Minimal support for ICC color profiles of decoded images.
+//!
+//! Only matrix/TRC profiles are supported, which covers RGB and grayscale
+//! display profiles such as Display P3 and Adobe RGB. Profiles based on
+//! lookup tables are ignored. See <https://www.color.org/specification/ICC.1-2022-05.pdf>.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use super::colorspace::{mul3, Mat3};
+use super::ColorSpace;
+
+/// Transfer curve that maps an encoded value to linear light.
+#[derive(Clone, Debug)]
+enum Curve {
+    /// Parametric curve with the parameters `[g, a, b, c, d, e, f]`, which
+    /// evaluates to `(a * x + b)^g + e` for `x >= d` and `c * x + f` below.
+    Parametric([f64; 7]),
+    /// Samples of the curve, evenly spaced from `0.0` to `1.0`.
+    Table(Vec<f64>),
+}
+
+impl Curve {
+    /// The sRGB transfer function.
+    const SRGB: Self = Self::Parametric([
+        2.4,
+        1.0 / 1.055,
+        0.055 / 1.055,
+        1.0 / 12.92,
+        0.04045,
+        0.0,
+        0.0,
+    ]);
+
+    fn gamma(gamma: f64) -> Self {
+        Self::Parametric([gamma, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn eval(&self, x: f64) -> f64 {
+        match self {
+            Self::Parametric([g, a, b, c, d, e, f]) => {
+                if x >= *d {
+                    (a * x + b).max(0.0).powf(*g) + e
+                } else {
+                    c * x + f
+                }
+            }
+            Self::Table(table) => {
+                let last = table.len() - 1;
+                let pos = x.clamp(0.0, 1.0) * last as f64;
+                let i = (pos as usize).min(last - 1);
+                let t = pos - i as f64;
+                table[i] + t * (table[i + 1] - table[i])
+            }
+        }
+    }
+
+    fn parse(tag: &[u8]) -> Option<Self> {
+        match tag.get(..4)? {
+            b"curv" => match read_u32(tag, 8)? {
+                0 => Some(Self::gamma(1.0)),
+                1 => Some(Self::gamma(f64::from(read_u16(tag, 12)?) / 256.0)),
+                n => {
+                    let table = (0..n as usize)
+                        .map(|i| read_u16(tag, 12 + 2 * i).map(|v| f64::from(v) / 65535.0))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some(Self::Table(table))
+                }
+            },
+            b"para" => {
+                let kind = read_u16(tag, 8)?;
+                let count = [1, 3, 4, 5, 7].get(usize::from(kind)).copied()?;
+                let mut p = [0.0; 7];
+                for (i, value) in p.iter_mut().take(count).enumerate() {
+                    *value = read_s15_fixed16(tag, 12 + 4 * i)?;
+                }
+                let [g, a, b, c, d, e, f] = p;
+                let params = match kind {
+                    0 => [g, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                    // The threshold `-b / a` is undefined without a slope.
+                    1 | 2 if a == 0.0 => return None,
+                    // Below `-b / a` the curve is zero, or `c` for type 2.
+                    1 => [g, a, b, 0.0, -b / a, 0.0, 0.0],
+                    2 => [g, a, b, 0.0, -b / a, c, c],
+                    _ => [g, a, b, c, d, e, f],
+                };
+                Some(Self::Parametric(params))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Color profile that maps the encoded RGB values of an image to linear
+/// sRGB.
+#[derive(Clone, Debug)]
+pub(crate) struct ColorProfile {
+    curves: [Curve; 3],
+    /// Matrix from linear RGB to CIE XYZ with a D50 white point, or `None`
+    /// for the sRGB primaries.
+    to_xyz_d50: Option<Mat3>,
+}
+
+impl ColorProfile {
+    /// Creates a profile with the sRGB primaries and the given gamma, such
+    /// that linear light is `x^gamma`.
+    #[cfg(feature = "png")]
+    pub(crate) fn gamma(gamma: f64) -> Self {
+        Self {
+            curves: [
+                Curve::gamma(gamma),
+                Curve::gamma(gamma),
+                Curve::gamma(gamma),
+            ],
+            to_xyz_d50: None,
+        }
+    }
+
+    /// Creates the sRGB profile.
+    pub(crate) fn srgb() -> Self {
+        Self {
+            curves: [Curve::SRGB, Curve::SRGB, Curve::SRGB],
+            to_xyz_d50: None,
+        }
+    }
+
+    /// Parses an ICC profile, returning `None` if it is invalid or not a
+    /// supported RGB or grayscale matrix/TRC profile.
+    pub(crate) fn parse(data: &[u8]) -> Option<Self> {
+        let tag_count = read_u32(data, 128)? as usize;
+        let tag = |signature: &[u8; 4]| {
+            (0..tag_count).find_map(|i| {
+                let entry = data.get(132 + 12 * i..144 + 12 * i)?;
+                if &entry[..4] != signature {
+                    return None;
+                }
+                let offset = read_u32(entry, 4)? as usize;
+                let size = read_u32(entry, 8)? as usize;
+                data.get(offset..offset.checked_add(size)?)
+            })
+        };
+        if data.get(20..24)? != b"XYZ " {
+            return None;
+        }
+        match data.get(16..20)? {
+            b"RGB " => {
+                let curves = [
+                    Curve::parse(tag(b"rTRC")?)?,
+                    Curve::parse(tag(b"gTRC")?)?,
+                    Curve::parse(tag(b"bTRC")?)?,
+                ];
+                let columns = [
+                    read_xyz(tag(b"rXYZ")?)?,
+                    read_xyz(tag(b"gXYZ")?)?,
+                    read_xyz(tag(b"bXYZ")?)?,
+                ];
+                let matrix = [0, 1, 2].map(|row| columns.map(|column| column[row]));
+                Some(Self {
+                    curves,
+                    to_xyz_d50: Some(matrix),
+                })
+            }
+            b"GRAY" => {
+                let curve = Curve::parse(tag(b"kTRC")?)?;
+                Some(Self {
+                    curves: [curve.clone(), curve.clone(), curve],
+                    to_xyz_d50: None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Converts linear components of the profile to linear sRGB.
+    fn linear_to_linear_srgb(&self, rgb: [f64; 3]) -> [f64; 3] {
+        match &self.to_xyz_d50 {
+            Some(matrix) => ColorSpace::XyzD50.convert(ColorSpace::LinearSrgb, mul3(matrix, rgb)),
+            None => rgb,
+        }
+    }
+
+    /// Converts encoded components in the range `0.0` to `1.0` to linear
+    /// sRGB, without clamping.
+    pub(crate) fn to_linear_srgb(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let linear = [0, 1, 2].map(|i| self.curves[i].eval(rgb[i]));
+        self.linear_to_linear_srgb(linear)
+    }
+
+    /// Converts straight alpha RGBA pixels with 8-bit channels to sRGB in
+    /// place, clamping colors that are outside of the sRGB gamut.
+    pub(crate) fn convert_rgba8_to_srgb(&self, pixels: &mut [u8]) {
+        let tables = [0, 1, 2].map(|i| {
+            let mut table = [0.0; 256];
+            for (v, entry) in table.iter_mut().enumerate() {
+                *entry = self.curves[i].eval(v as f64 / 255.0);
+            }
+            table
+        });
+        for pixel in pixels.chunks_exact_mut(4) {
+            let linear = [0, 1, 2].map(|i| tables[i][usize::from(pixel[i])]);
+            let srgb = ColorSpace::LinearSrgb
+                .convert(ColorSpace::Srgb, self.linear_to_linear_srgb(linear));
+            for (c, v) in pixel.iter_mut().zip(srgb) {
+                *c = (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_s15_fixed16(data: &[u8], offset: usize) -> Option<f64> {
+    Some(f64::from(read_u32(data, offset)? as i32) / 65536.0)
+}
+
+/// Reads the first value of an `XYZ ` tag.
+fn read_xyz(tag: &[u8]) -> Option<[f64; 3]> {
+    if tag.get(..4)? != b"XYZ " {
+        return None;
+    }
+    Some([
+        read_s15_fixed16(tag, 8)?,
+        read_s15_fixed16(tag, 12)?,
+        read_s15_fixed16(tag, 16)?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Curve;
+
+    /// Builds a `para` tag of the given type with the given parameters.
+    fn para(kind: u16, params: &[f64]) -> Vec<u8> {
+        let mut tag = b"para\0\0\0\0".to_vec();
+        tag.extend_from_slice(&kind.to_be_bytes());
+        tag.extend_from_slice(&[0, 0]);
+        for p in params {
+            tag.extend_from_slice(&((p * 65536.0).round() as i32).to_be_bytes());
+        }
+        tag
+    }
+
+    #[track_caller]
+    fn assert_eval(curve: &Curve, x: f64, expected: f64) {
+        let value = curve.eval(x);
+        assert!(
+            (value - expected).abs() < 1e-4,
+            "curve at {x} was {value}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn parametric() {
+        let gamma = Curve::parse(&para(0, &[2.0])).unwrap();
+        assert_eval(&gamma, 0.5, 0.25);
+        // Type 1 is zero below `-b / a`.
+        let offset = Curve::parse(&para(1, &[1.0, 2.0, -0.5])).unwrap();
+        assert_eval(&offset, 0.1, 0.0);
+        assert_eval(&offset, 0.5, 0.5);
+        // Type 2 is `c` below `-b / a`.
+        let raised = Curve::parse(&para(2, &[1.0, 2.0, -0.5, 0.25])).unwrap();
+        assert_eval(&raised, 0.1, 0.25);
+        assert_eval(&raised, 0.5, 0.75);
+        // Type 3 is the sRGB transfer function.
+        let srgb = para(3, &[2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045]);
+        let srgb = Curve::parse(&srgb).unwrap();
+        for x in [0.02, 0.5, 1.0] {
+            assert_eval(&srgb, x, Curve::SRGB.eval(x));
+        }
+    }
+
+    #[test]
+    fn parametric_without_slope() {
+        assert!(
+            Curve::parse(&para(1, &[2.2, 0.0, 0.5])).is_none(),
+            "type 1 with a == 0"
+        );
+        assert!(
+            Curve::parse(&para(2, &[2.2, 0.0, 0.5, 0.1])).is_none(),
+            "type 2 with a == 0"
+        );
+        // Type 3 has an explicit threshold, so a zero slope is valid.
+        let flat = Curve::parse(&para(3, &[1.0, 0.0, 0.5, 0.0, 0.0])).unwrap();
+        assert_eval(&flat, 0.75, 0.5);
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(Curve::parse(&para(1, &[2.2, 1.0])).is_none(), "missing b");
+        assert!(Curve::parse(&para(5, &[1.0])).is_none(), "unknown type");
+        assert!(
+            Curve::parse(b"curv\0\0\0\0\0\0\0\x02\0\0").is_none(),
+            "short table"
+        );
+    }
+}
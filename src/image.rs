@@ -7,33 +7,227 @@ use kurbo::common::FloatFuncs as _;
 
 use super::{Blob, Extend};
 
+use core::fmt;
+
 /// Defines the pixel format of an [image](Image).
+///
+/// Formats without an alpha channel are opaque. Unless the name says
+/// otherwise, color channels are not premultiplied by alpha. Integer formats
+/// store sRGB encoded color, while floating point formats store linear sRGB
+/// color (see [`Format::is_linear`]).
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Format {
     /// 32-bit RGBA with 8-bit channels.
     Rgba8,
+    /// 32-bit BGRA with 8-bit channels.
+    Bgra8,
+    /// 32-bit RGBA with 8-bit channels and premultiplied alpha.
+    Rgba8Premul,
+    /// 32-bit BGRA with 8-bit channels and premultiplied alpha.
+    Bgra8Premul,
+    /// 8-bit alpha channel, such as a coverage mask.
+    A8,
+    /// 8-bit red channel.
+    R8,
+    /// 16-bit RG with 8-bit channels.
+    Rg8,
+    /// 64-bit RGBA with 16-bit floating point channels.
+    Rgba16F,
+    /// 128-bit RGBA with 32-bit floating point channels.
+    Rgba32F,
+    /// 32-bit RGBA packed into a little endian integer, with 10-bit red, green
+    /// and blue channels and a 2-bit alpha channel, starting from the least
+    /// significant bit.
+    Rgb10A2,
+}
+
+/// Identifies a channel of a [pixel format](Format).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Channel {
+    /// Red channel.
+    Red,
+    /// Green channel.
+    Green,
+    /// Blue channel.
+    Blue,
+    /// Alpha channel.
+    Alpha,
+}
+
+/// Numeric representation of the channels of a [pixel format](Format).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum ChannelType {
+    /// 8-bit unsigned integer, normalized to the range `0.0` to `1.0`.
+    Unorm8,
+    /// 16-bit IEEE 754 floating point.
+    Float16,
+    /// 32-bit IEEE 754 floating point.
+    Float32,
+    /// 10-bit unsigned integer color channels and a 2-bit unsigned integer
+    /// alpha channel packed into 32 bits, normalized to the range `0.0` to
+    /// `1.0`.
+    Unorm10A2,
 }
 
 impl Format {
+    /// Returns the number of bytes used by a single pixel in this format.
+    #[must_use]
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::A8 | Self::R8 => 1,
+            Self::Rg8 => 2,
+            Self::Rgba8 | Self::Bgra8 | Self::Rgba8Premul | Self::Bgra8Premul | Self::Rgb10A2 => 4,
+            Self::Rgba16F => 8,
+            Self::Rgba32F => 16,
+        }
+    }
+
     /// Returns the required size in bytes for an image in this format
     /// of the given dimensions.
     ///
     /// A result of `None` indicates an overflow in the size calculation.
     #[must_use]
     pub fn size_in_bytes(self, width: u32, height: u32) -> Option<usize> {
+        self.bytes_per_pixel()
+            .checked_mul(width as usize)
+            .and_then(|x| x.checked_mul(height as usize))
+    }
+
+    /// Returns the channels of this format in memory order.
+    ///
+    /// For [`Format::Rgb10A2`], the order is from the least significant bit.
+    #[must_use]
+    pub fn channels(self) -> &'static [Channel] {
+        use Channel::{Alpha, Blue, Green, Red};
         match self {
-            Self::Rgba8 => 4_usize
-                .checked_mul(width as usize)
-                .and_then(|x| x.checked_mul(height as usize)),
+            Self::Rgba8 | Self::Rgba8Premul | Self::Rgba16F | Self::Rgba32F | Self::Rgb10A2 => {
+                &[Red, Green, Blue, Alpha]
+            }
+            Self::Bgra8 | Self::Bgra8Premul => &[Blue, Green, Red, Alpha],
+            Self::A8 => &[Alpha],
+            Self::R8 => &[Red],
+            Self::Rg8 => &[Red, Green],
         }
     }
+
+    /// Returns the numeric representation of the channels of this format.
+    #[must_use]
+    pub fn channel_type(self) -> ChannelType {
+        match self {
+            Self::Rgba8
+            | Self::Bgra8
+            | Self::Rgba8Premul
+            | Self::Bgra8Premul
+            | Self::A8
+            | Self::R8
+            | Self::Rg8 => ChannelType::Unorm8,
+            Self::Rgba16F => ChannelType::Float16,
+            Self::Rgba32F => ChannelType::Float32,
+            Self::Rgb10A2 => ChannelType::Unorm10A2,
+        }
+    }
+
+    /// Returns true if this format has an alpha channel.
+    #[must_use]
+    pub fn has_alpha(self) -> bool {
+        self.channels().contains(&Channel::Alpha)
+    }
+
+    /// Returns true if the color channels of this format are premultiplied by
+    /// alpha.
+    ///
+    /// This is false for formats without color channels or without an alpha
+    /// channel.
+    #[must_use]
+    pub fn is_premultiplied(self) -> bool {
+        matches!(self, Self::Rgba8Premul | Self::Bgra8Premul)
+    }
+
+    /// Returns true if the color channels of this format store linear light
+    /// values, and false if they are encoded with the sRGB transfer function.
+    #[must_use]
+    pub fn is_linear(self) -> bool {
+        matches!(
+            self.channel_type(),
+            ChannelType::Float16 | ChannelType::Float32
+        )
+    }
+}
+
+/// Errors that can occur when creating or converting an [image](Image).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum ImageError {
+    /// The size of the image in bytes overflows `usize`.
+    SizeOverflow,
+    /// The image data is shorter than required by the format, dimensions and
+    /// stride.
+    DataTooShort,
+    /// The stride is smaller than a row of pixels.
+    StrideTooSmall,
+    /// The sub-rectangle does not lie within the image.
+    SubRectOutOfBounds,
+    /// The images being compared have different dimensions.
+    SizeMismatch,
+    /// The conversion between the formats would lose data, and lossy
+    /// conversions were not allowed.
+    LossyConversion {
+        /// The format of the source image.
+        from: Format,
+        /// The requested format.
+        to: Format,
+    },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SizeOverflow => write!(f, "image size overflows usize"),
+            Self::DataTooShort => write!(f, "image data is too short"),
+            Self::StrideTooSmall => write!(f, "image stride is smaller than a row"),
+            Self::SubRectOutOfBounds => write!(f, "image sub-rectangle is out of bounds"),
+            Self::SizeMismatch => write!(f, "images have different dimensions"),
+            Self::LossyConversion { from, to } => {
+                write!(f, "conversion from {from:?} to {to:?} loses data")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ImageError {}
+
+/// Defines how an [image](Image) is filtered when sampled.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ImageQuality {
+    /// Uses the nearest pixel, which keeps hard edges, as in pixel art.
+    Nearest,
+    /// Interpolates linearly between the four nearest pixels.
+    #[default]
+    Bilinear,
+    /// Interpolates with a cubic filter over the sixteen nearest pixels, which
+    /// is sharper than bilinear filtering when the image is magnified.
+    Bicubic,
+    /// Interpolates bilinearly within and linearly between the two nearest
+    /// mipmap levels, which avoids aliasing when the image is minified.
+    Trilinear,
 }
 
 /// Owned shareable image resource.
+///
+/// Deserializing an image [validates](Self::validate) it, so it fails for
+/// images that [`new_with_stride`](Self::new_with_stride) would reject.
 #[derive(Clone, PartialEq, Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "ImageFields")
+)]
 pub struct Image {
     /// Blob containing the image data.
     pub data: Blob<u8>,
@@ -43,31 +237,111 @@ pub struct Image {
     pub width: u32,
     /// Height of the image.
     pub height: u32,
-    /// Extend mode.
-    pub extend: Extend,
+    /// Number of bytes from the start of one row to the start of the next.
+    ///
+    /// When deserializing, a missing stride means tightly packed rows.
+    pub stride: u32,
+    /// Region of the image that is used, or `None` to use the whole image.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sub_rect: Option<ImageRect>,
+    /// Extend mode in the horizontal direction.
+    ///
+    /// When deserializing, this falls back to the `extend` field written by
+    /// earlier versions, which applies to both directions.
+    pub x_extend: Extend,
+    /// Extend mode in the vertical direction.
+    ///
+    /// Like `x_extend`, this falls back to the legacy `extend` field when
+    /// deserializing.
+    pub y_extend: Extend,
+    /// Filtering used when sampling the image.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub quality: ImageQuality,
     /// An additional alpha multiplier to use with the image.
     pub alpha: u8,
 }
 
 impl Image {
-    /// Creates a new image with the given data, [format](Format) and dimensions.
-    #[must_use]
-    pub fn new(data: Blob<u8>, format: Format, width: u32, height: u32) -> Self {
-        Self {
+    /// Creates a new image with the given data, [format](Format) and
+    /// dimensions, with tightly packed rows.
+    ///
+    /// Use [`new_with_stride`](Self::new_with_stride) for padded rows.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ImageError::SizeOverflow`] if a row of pixels is larger than
+    /// `u32::MAX` bytes, or [`ImageError::DataTooShort`] if the data is too
+    /// short for the dimensions.
+    pub fn new(
+        data: Blob<u8>,
+        format: Format,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, ImageError> {
+        let stride = tight_stride(format, width).ok_or(ImageError::SizeOverflow)?;
+        Self::new_with_stride(data, format, width, height, stride)
+    }
+
+    /// Creates a new image with the given data, [format](Format), dimensions
+    /// and row stride in bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stride is smaller than a row of pixels, or if
+    /// the data is too short for the layout.
+    pub fn new_with_stride(
+        data: Blob<u8>,
+        format: Format,
+        width: u32,
+        height: u32,
+        stride: u32,
+    ) -> Result<Self, ImageError> {
+        let image = Self {
             data,
             format,
             width,
             height,
-            extend: Extend::Pad,
+            stride,
+            sub_rect: None,
+            x_extend: Extend::Pad,
+            y_extend: Extend::Pad,
+            quality: ImageQuality::default(),
             // Opaque
             alpha: u8::MAX,
-        }
+        };
+        image.validate()?;
+        Ok(image)
     }
 
-    /// Builder method for setting the image [extend mode](Extend).
+    /// Builder method for setting the image [extend mode](Extend) in both
+    /// directions.
     #[must_use]
     pub fn with_extend(mut self, mode: Extend) -> Self {
-        self.extend = mode;
+        self.x_extend = mode;
+        self.y_extend = mode;
+        self
+    }
+
+    /// Builder method for setting the image [extend mode](Extend) in the
+    /// horizontal direction.
+    #[must_use]
+    pub fn with_x_extend(mut self, mode: Extend) -> Self {
+        self.x_extend = mode;
+        self
+    }
+
+    /// Builder method for setting the image [extend mode](Extend) in the
+    /// vertical direction.
+    #[must_use]
+    pub fn with_y_extend(mut self, mode: Extend) -> Self {
+        self.y_extend = mode;
+        self
+    }
+
+    /// Builder method for setting the image [sampling quality](ImageQuality).
+    #[must_use]
+    pub fn with_quality(mut self, quality: ImageQuality) -> Self {
+        self.quality = quality;
         self
     }
 
@@ -77,4 +351,322 @@ impl Image {
         self.alpha = ((self.alpha as f32) * alpha).round() as u8;
         self
     }
+
+    /// Builder method for restricting the image to a region, such as an entry
+    /// in a texture atlas.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ImageError::SubRectOutOfBounds`] if the region does not lie
+    /// within the image.
+    pub fn with_sub_rect(mut self, rect: ImageRect) -> Result<Self, ImageError> {
+        if !rect.fits_within(self.width, self.height) {
+            return Err(ImageError::SubRectOutOfBounds);
+        }
+        self.sub_rect = Some(rect);
+        Ok(self)
+    }
+
+    /// Checks that the stride, sub-rectangle and data of the image are
+    /// consistent with the format and dimensions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first inconsistency that is found.
+    pub fn validate(&self) -> Result<(), ImageError> {
+        let row_len = self
+            .format
+            .size_in_bytes(self.width, 1)
+            .ok_or(ImageError::SizeOverflow)?;
+        if (self.stride as usize) < row_len {
+            return Err(ImageError::StrideTooSmall);
+        }
+        if let Some(rect) = self.sub_rect {
+            if !rect.fits_within(self.width, self.height) {
+                return Err(ImageError::SubRectOutOfBounds);
+            }
+        }
+        let required = match self.height {
+            0 => 0,
+            height => (self.stride as usize)
+                .checked_mul(height as usize - 1)
+                .and_then(|x| x.checked_add(row_len))
+                .ok_or(ImageError::SizeOverflow)?,
+        };
+        if self.data.len() < required {
+            return Err(ImageError::DataTooShort);
+        }
+        Ok(())
+    }
+
+    /// Returns the region of the image that is used.
+    ///
+    /// This is the sub-rectangle, if set, and otherwise the whole image.
+    #[must_use]
+    pub fn region(&self) -> ImageRect {
+        self.sub_rect
+            .unwrap_or(ImageRect::new(0, 0, self.width, self.height))
+    }
+
+    /// Returns the pixel data of row `y` of the [region](Self::region), or
+    /// `None` if the row is out of bounds or the data is too short.
+    #[must_use]
+    pub fn row(&self, y: u32) -> Option<&[u8]> {
+        let region = self.region();
+        if y >= region.height {
+            return None;
+        }
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+        let start = (self.stride as usize)
+            .checked_mul((region.y + y) as usize)?
+            .checked_add((region.x as usize).checked_mul(bytes_per_pixel)?)?;
+        let len = (region.width as usize).checked_mul(bytes_per_pixel)?;
+        self.data.data().get(start..start.checked_add(len)?)
+    }
+}
+
+/// Returns the stride of tightly packed rows, or `None` if it overflows.
+fn tight_stride(format: Format, width: u32) -> Option<u32> {
+    format
+        .size_in_bytes(width, 1)
+        .and_then(|len| u32::try_from(len).ok())
+}
+
+/// Serialized form of an [`Image`], which accepts data written by earlier
+/// versions.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct ImageFields {
+    data: Blob<u8>,
+    format: Format,
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    stride: Option<u32>,
+    #[serde(default)]
+    sub_rect: Option<ImageRect>,
+    #[serde(default)]
+    x_extend: Option<Extend>,
+    #[serde(default)]
+    y_extend: Option<Extend>,
+    /// Extend mode in both directions, from before it was split into
+    /// `x_extend` and `y_extend`.
+    #[serde(default)]
+    extend: Option<Extend>,
+    #[serde(default)]
+    quality: ImageQuality,
+    alpha: u8,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ImageFields> for Image {
+    type Error = ImageError;
+
+    fn try_from(fields: ImageFields) -> Result<Self, ImageError> {
+        let stride = match fields.stride {
+            Some(stride) => stride,
+            None => tight_stride(fields.format, fields.width).ok_or(ImageError::SizeOverflow)?,
+        };
+        let image = Self {
+            data: fields.data,
+            format: fields.format,
+            width: fields.width,
+            height: fields.height,
+            stride,
+            sub_rect: fields.sub_rect,
+            x_extend: fields.x_extend.or(fields.extend).unwrap_or_default(),
+            y_extend: fields.y_extend.or(fields.extend).unwrap_or_default(),
+            quality: fields.quality,
+            alpha: fields.alpha,
+        };
+        image.validate()?;
+        Ok(image)
+    }
+}
+
+/// Rectangle of pixels in an [image](Image).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageRect {
+    /// Horizontal offset of the left edge, in pixels.
+    pub x: u32,
+    /// Vertical offset of the top edge, in pixels.
+    pub y: u32,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+impl ImageRect {
+    /// Creates a new rectangle from its offset and size.
+    #[must_use]
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns true if the rectangle lies within an image of the given size.
+    fn fits_within(self, width: u32, height: u32) -> bool {
+        let right = self.x.checked_add(self.width);
+        let bottom = self.y.checked_add(self.height);
+        matches!((right, bottom), (Some(r), Some(b)) if r <= width && b <= height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Image, ImageError};
+    use crate::Blob;
+    #[cfg(feature = "serde")]
+    use crate::{Extend, ImageQuality, ImageRect};
+
+    fn blob(len: usize) -> Blob<u8> {
+        Blob::from(vec![0_u8; len])
+    }
+
+    #[test]
+    fn new_validates() {
+        let image = Image::new(blob(24), Format::Rgba8, 3, 2).unwrap();
+        assert_eq!(image.stride, 12, "rows are tightly packed");
+        assert_eq!(
+            Image::new(blob(23), Format::Rgba8, 3, 2),
+            Err(ImageError::DataTooShort),
+            "data too short"
+        );
+        assert_eq!(
+            Image::new(blob(0), Format::Rgba32F, u32::MAX, 1),
+            Err(ImageError::SizeOverflow),
+            "row larger than u32::MAX bytes"
+        );
+        assert!(
+            Image::new(blob(0), Format::Rgba8, 0, 0).is_ok(),
+            "empty image is valid"
+        );
+    }
+
+    #[test]
+    fn new_with_stride_validates() {
+        let image = Image::new_with_stride(blob(28), Format::Rgba8, 3, 2, 16).unwrap();
+        assert_eq!(image.row(1).map(<[u8]>::len), Some(12), "second row");
+        assert_eq!(
+            Image::new_with_stride(blob(28), Format::Rgba8, 3, 2, 11),
+            Err(ImageError::StrideTooSmall),
+            "stride smaller than a row"
+        );
+        assert_eq!(
+            Image::new_with_stride(blob(27), Format::Rgba8, 3, 2, 16),
+            Err(ImageError::DataTooShort),
+            "last row is not padded, but must be complete"
+        );
+    }
+
+    /// Asserts that two images are the same, with equal data in different
+    /// blobs.
+    #[cfg(feature = "serde")]
+    #[track_caller]
+    fn assert_same(actual: &Image, expected: &Image, json: &str) {
+        assert_eq!(actual.data.data(), expected.data.data(), "data of {json}");
+        let actual = Image {
+            data: expected.data.clone(),
+            ..actual.clone()
+        };
+        assert_eq!(&actual, expected, "fields of {json}");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let image = Image::new_with_stride(blob(28), Format::Rgba8, 3, 2, 16)
+            .unwrap()
+            .with_x_extend(Extend::Repeat)
+            .with_y_extend(Extend::Reflect)
+            .with_quality(ImageQuality::Nearest)
+            .with_sub_rect(ImageRect::new(1, 0, 2, 2))
+            .unwrap()
+            .with_alpha_factor(0.5);
+        let json = serde_json::to_string(&image).unwrap();
+        let decoded: Image = serde_json::from_str(&json).unwrap();
+        assert_same(&decoded, &image, &json);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_legacy_fields() {
+        // Images serialized before `stride`, `sub_rect`, `quality` and the
+        // split extend modes were added.
+        let legacy = r#"{
+            "data": [1, 2, 3, 4, 5, 6, 7, 8],
+            "format": "Rgba8",
+            "width": 2,
+            "height": 1,
+            "extend": "Repeat",
+            "alpha": 255
+        }"#;
+        let image: Image = serde_json::from_str(legacy).unwrap();
+        let expected = Image::new(
+            Blob::from(vec![1, 2, 3, 4, 5, 6, 7, 8]),
+            Format::Rgba8,
+            2,
+            1,
+        )
+        .unwrap()
+        .with_extend(Extend::Repeat);
+        assert_same(&image, &expected, legacy);
+
+        // The split fields take precedence over the legacy field.
+        let split = r#"{
+            "data": [],
+            "format": "R8",
+            "width": 0,
+            "height": 0,
+            "extend": "Repeat",
+            "y_extend": "Reflect",
+            "alpha": 255
+        }"#;
+        let overridden: Image = serde_json::from_str(split).unwrap();
+        assert_eq!(
+            (overridden.x_extend, overridden.y_extend),
+            (Extend::Repeat, Extend::Reflect),
+            "y_extend overrides extend"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_validates() {
+        let error = |json: &str| serde_json::from_str::<Image>(json).unwrap_err().to_string();
+        let cases = [
+            (
+                r#"{"data": [1, 2, 3], "format": "Rgba8", "width": 1, "height": 1, "alpha": 255}"#,
+                "image data is too short",
+            ),
+            (
+                r#"{"data": [0, 0, 0, 0, 0, 0, 0, 0], "format": "Rgba8", "width": 2, "height": 1,
+                    "stride": 4, "alpha": 255}"#,
+                "image stride is smaller than a row",
+            ),
+            (
+                r#"{"data": [0, 0, 0, 0], "format": "Rgba8", "width": 1, "height": 1,
+                    "sub_rect": {"x": 1, "y": 0, "width": 1, "height": 1}, "alpha": 255}"#,
+                "image sub-rectangle is out of bounds",
+            ),
+            (
+                r#"{"data": [], "format": "Rgba32F", "width": 4294967295, "height": 0,
+                    "alpha": 255}"#,
+                "image size overflows usize",
+            ),
+        ];
+        for (json, message) in cases {
+            let message_found = error(json);
+            assert!(
+                message_found.starts_with(message),
+                "{json} failed with {message_found}, expected {message}"
+            );
+        }
+    }
 }
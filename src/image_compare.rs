@@ -0,0 +1,212 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Comparison of images, for snapshot testing.
+
+use super::colorspace::linear_to_srgb;
+use super::{Blob, Format, Image, ImageError};
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+/// Result of [comparing](Image::compare) two images.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ImageDiff {
+    /// Largest difference of any channel of any pixel, in the range `0.0` to
+    /// `1.0`.
+    pub max_delta: f32,
+    /// Average difference over all channels of all pixels.
+    pub mean_delta: f32,
+    /// Number of pixels with a channel that differs by more than the
+    /// tolerance.
+    pub mismatched_pixels: u64,
+    /// Total number of pixels that were compared.
+    pub total_pixels: u64,
+    /// Visualization of the differences in [`Format::Rgba8`], with
+    /// mismatched pixels in red over a faded grayscale copy of the reference.
+    pub image: Image,
+}
+
+impl ImageDiff {
+    /// Returns true if no pixel differs by more than the tolerance.
+    #[must_use]
+    pub fn is_match(&self) -> bool {
+        self.mismatched_pixels == 0
+    }
+}
+
+impl Image {
+    /// Compares this image with a reference image.
+    ///
+    /// Pixels are compared after conversion to sRGB encoded color with
+    /// premultiplied alpha, so that differences in fully transparent pixels
+    /// are ignored. A pixel is mismatched if any channel differs by more than
+    /// `tolerance`, in the range `0.0` to `1.0`; for example, `2.0 / 255.0`
+    /// allows 8-bit channels to be off by two. Only the
+    /// [regions](Self::region) of the images are compared, and the alpha
+    /// multipliers are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ImageError::SizeMismatch`] if the regions of the images have
+    /// different sizes, or an error if either image fails
+    /// [validation](Self::validate).
+    pub fn compare(&self, reference: &Self, tolerance: f32) -> Result<ImageDiff, ImageError> {
+        let (region, reference_region) = (self.region(), reference.region());
+        if (region.width, region.height) != (reference_region.width, reference_region.height) {
+            return Err(ImageError::SizeMismatch);
+        }
+        let actual = self.convert(Format::Rgba32F)?;
+        let expected = reference.convert(Format::Rgba32F)?;
+        let total_pixels = u64::from(region.width) * u64::from(region.height);
+        let mut diff = Vec::with_capacity(total_pixels as usize * 4);
+        let mut max_delta = 0.0_f32;
+        let mut delta_sum = 0.0_f64;
+        let mut mismatched_pixels = 0;
+        let pixels = |image: &Self| {
+            image
+                .data
+                .data()
+                .chunks_exact(16)
+                .map(srgb_premul)
+                .collect::<Vec<_>>()
+        };
+        for (a, b) in pixels(&actual).into_iter().zip(pixels(&expected)) {
+            let mut pixel_delta = 0.0_f32;
+            for (x, y) in a.into_iter().zip(b) {
+                let delta = (x - y).abs();
+                pixel_delta = pixel_delta.max(delta);
+                delta_sum += f64::from(delta);
+            }
+            max_delta = max_delta.max(pixel_delta);
+            if pixel_delta > tolerance {
+                mismatched_pixels += 1;
+                diff.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                let luma = 0.2126 * b[0] + 0.7152 * b[1] + 0.0722 * b[2];
+                let faded = (191.0 + luma * 64.0) as u8;
+                diff.extend_from_slice(&[faded, faded, faded, 255]);
+            }
+        }
+        let mean_delta = if total_pixels == 0 {
+            0.0
+        } else {
+            (delta_sum / (total_pixels * 4) as f64) as f32
+        };
+        Ok(ImageDiff {
+            max_delta,
+            mean_delta,
+            mismatched_pixels,
+            total_pixels,
+            image: Self::new(Blob::from(diff), Format::Rgba8, region.width, region.height)?,
+        })
+    }
+}
+
+/// Converts a linear straight alpha [`Format::Rgba32F`] pixel to sRGB with
+/// premultiplied alpha.
+fn srgb_premul(bytes: &[u8]) -> [f32; 4] {
+    let channel = |i: usize| {
+        let b = &bytes[i * 4..i * 4 + 4];
+        f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+    };
+    let alpha = channel(3).clamp(0.0, 1.0);
+    let rgb = linear_to_srgb([channel(0), channel(1), channel(2)].map(f64::from));
+    let [r, g, b] = rgb.map(|c| c as f32 * alpha);
+    [r, g, b, alpha]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Blob, Format, Image, ImageError, ImageRect};
+
+    fn image(format: Format, width: u32, height: u32, data: &[u8]) -> Image {
+        Image::new(Blob::from(data.to_vec()), format, width, height).unwrap()
+    }
+
+    const PIXELS: [u8; 16] = [
+        255, 0, 0, 255, //
+        0, 128, 255, 255, //
+        10, 20, 30, 128, //
+        200, 100, 50, 255,
+    ];
+
+    #[test]
+    fn identical() {
+        let a = image(Format::Rgba8, 2, 2, &PIXELS);
+        let diff = a.compare(&a.clone(), 0.0).unwrap();
+        assert!(diff.is_match(), "an image matches itself");
+        assert_eq!(diff.max_delta, 0.0, "max delta");
+        assert_eq!(diff.mean_delta, 0.0, "mean delta");
+        assert_eq!(diff.total_pixels, 4, "total pixels");
+        assert_eq!(
+            (diff.image.format, diff.image.width, diff.image.height),
+            (Format::Rgba8, 2, 2),
+            "diff image"
+        );
+    }
+
+    #[test]
+    fn tolerance() {
+        let a = image(Format::Rgba8, 2, 2, &PIXELS);
+        let mut changed = PIXELS;
+        // Change one channel of the second pixel by two.
+        changed[5] += 2;
+        let b = image(Format::Rgba8, 2, 2, &changed);
+        let within = a.compare(&b, 2.5 / 255.0).unwrap();
+        assert!(within.is_match(), "within tolerance");
+        assert!(
+            (within.max_delta - 2.0 / 255.0).abs() < 1e-4,
+            "max delta was {}",
+            within.max_delta
+        );
+        assert!(
+            (within.mean_delta - 2.0 / 255.0 / 16.0).abs() < 1e-5,
+            "mean delta was {}",
+            within.mean_delta
+        );
+        let outside = a.compare(&b, 1.5 / 255.0).unwrap();
+        assert_eq!(outside.mismatched_pixels, 1, "outside of tolerance");
+        let marked = &outside.image.data.data()[4..8];
+        assert_eq!(marked, [255, 0, 0, 255], "mismatch is marked in red");
+        let unmarked = &outside.image.data.data()[..4];
+        assert_eq!(unmarked[0], unmarked[1], "match is gray");
+    }
+
+    #[test]
+    fn transparent_pixels_ignored() {
+        let a = image(Format::Rgba8, 1, 1, &[255, 0, 0, 0]);
+        let b = image(Format::Rgba8, 1, 1, &[0, 255, 0, 0]);
+        assert!(a.compare(&b, 0.0).unwrap().is_match(), "premultiplied");
+    }
+
+    #[test]
+    fn mismatched_format() {
+        let rgba = image(Format::Rgba8, 2, 2, &PIXELS);
+        let mut swapped = PIXELS;
+        for pixel in swapped.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        let bgra = image(Format::Bgra8, 2, 2, &swapped);
+        let same = rgba.compare(&bgra, 0.0).unwrap();
+        assert!(same.is_match(), "same pixels in different formats");
+        let different = rgba
+            .compare(&image(Format::Rgba8, 2, 2, &swapped), 0.0)
+            .unwrap();
+        assert_eq!(different.mismatched_pixels, 4, "swapped channels");
+    }
+
+    #[test]
+    fn mismatched_size() {
+        let a = image(Format::Rgba8, 2, 2, &PIXELS);
+        let b = image(Format::Rgba8, 4, 1, &PIXELS);
+        assert_eq!(a.compare(&b, 1.0), Err(ImageError::SizeMismatch), "size");
+        // Only the regions are compared.
+        let region = b.with_sub_rect(ImageRect::new(0, 0, 2, 1)).unwrap();
+        let top = image(Format::Rgba8, 2, 1, &PIXELS[..8]);
+        assert!(
+            region.compare(&top, 0.0).unwrap().is_match(),
+            "matching regions"
+        );
+    }
+}
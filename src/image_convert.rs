@@ -0,0 +1,486 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Conversion of images between pixel formats.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::colorspace::{linear_to_srgb, srgb_to_linear};
+use super::{Blob, Channel, ChannelType, Format, Image, ImageError};
+
+extern crate alloc;
+use alloc::vec;
+
+/// Number of pixels converted at a time.
+const CHUNK_PIXELS: usize = 256;
+
+impl Format {
+    /// Returns true if converting an image from this format to `target`
+    /// preserves all of the data.
+    ///
+    /// A conversion is lossy if `target` is missing a channel of this format,
+    /// stores a channel with less precision, or premultiplies color that is
+    /// not premultiplied in this format, as color is lost where alpha is zero.
+    /// Adding channels, unpremultiplying 8-bit color and converting integer
+    /// channels to floating point are lossless.
+    #[must_use]
+    pub fn converts_losslessly_to(self, target: Self) -> bool {
+        if self == target {
+            return true;
+        }
+        let has_color = self.channels().iter().any(|&c| c != Channel::Alpha);
+        if has_color && self.has_alpha() && !self.is_premultiplied() && target.is_premultiplied() {
+            return false;
+        }
+        self.channels()
+            .iter()
+            .all(|&c| target.channels().contains(&c) && self.precision(c) <= target.precision(c))
+    }
+
+    /// Returns a rank of the precision with which a channel is stored.
+    fn precision(self, channel: Channel) -> u8 {
+        match self.channel_type() {
+            ChannelType::Unorm8 => 8,
+            ChannelType::Unorm10A2 if channel == Channel::Alpha => 2,
+            ChannelType::Unorm10A2 => 10,
+            ChannelType::Float16 => 16,
+            ChannelType::Float32 => 32,
+        }
+    }
+}
+
+impl Image {
+    /// Converts the image to the given [format](Format).
+    ///
+    /// Channels that are missing from the source format are filled with zero
+    /// for color and one for alpha. Color is premultiplied or unpremultiplied
+    /// by alpha, and encoded with or decoded from the sRGB transfer function,
+    /// as required by the target format. The extend modes, quality and alpha
+    /// multiplier are kept.
+    ///
+    /// The result has tightly packed rows and contains only the
+    /// [region](Self::region) of this image.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ImageError::LossyConversion`] if the conversion would lose
+    /// data (see [`Format::converts_losslessly_to`]); use
+    /// [`convert_lossy`](Self::convert_lossy) to allow this. Returns an error
+    /// if the image fails [validation](Self::validate).
+    pub fn convert(&self, format: Format) -> Result<Self, ImageError> {
+        if !self.format.converts_losslessly_to(format) {
+            return Err(ImageError::LossyConversion {
+                from: self.format,
+                to: format,
+            });
+        }
+        self.convert_lossy(format)
+    }
+
+    /// Converts the image to the given [format](Format), allowing the
+    /// conversion to lose data.
+    ///
+    /// This is the same as [`convert`](Self::convert), except that channels
+    /// missing from the target format are dropped, and values are clamped and
+    /// rounded to the precision of the target format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image fails [validation](Self::validate).
+    pub fn convert_lossy(&self, format: Format) -> Result<Self, ImageError> {
+        self.validate()?;
+        let tight = self.sub_rect.is_none()
+            && self.format.size_in_bytes(self.width, 1) == Some(self.stride as usize);
+        if format == self.format && tight {
+            return Ok(self.clone());
+        }
+        let region = self.region();
+        let dst_row_len = format
+            .size_in_bytes(region.width, 1)
+            .ok_or(ImageError::SizeOverflow)?;
+        let dst_len = format
+            .size_in_bytes(region.width, region.height)
+            .ok_or(ImageError::SizeOverflow)?;
+        let mut dst = vec![0_u8; dst_len];
+        if dst_row_len != 0 {
+            for (y, dst_row) in (0..region.height).zip(dst.chunks_exact_mut(dst_row_len)) {
+                // Validation guarantees that every row of the region exists.
+                let src_row = self.row(y).ok_or(ImageError::DataTooShort)?;
+                convert_row(self.format, src_row, format, dst_row);
+            }
+        }
+        let mut image = Self::new(Blob::from(dst), format, region.width, region.height)?;
+        image.x_extend = self.x_extend;
+        image.y_extend = self.y_extend;
+        image.quality = self.quality;
+        image.alpha = self.alpha;
+        Ok(image)
+    }
+}
+
+fn convert_row(src_format: Format, src: &[u8], dst_format: Format, dst: &mut [u8]) {
+    if src_format == dst_format {
+        dst.copy_from_slice(src);
+    } else if src_format.channel_type() == ChannelType::Unorm8
+        && dst_format.channel_type() == ChannelType::Unorm8
+    {
+        convert_unorm8(src_format, src, dst_format, dst);
+    } else {
+        convert_float(src_format, src, dst_format, dst);
+    }
+}
+
+fn channel_index(channel: Channel) -> usize {
+    match channel {
+        Channel::Red => 0,
+        Channel::Green => 1,
+        Channel::Blue => 2,
+        Channel::Alpha => 3,
+    }
+}
+
+/// Converts between 8-bit formats with integer arithmetic, so that
+/// premultiplication matches [`PremulColor`](crate::PremulColor) exactly.
+fn convert_unorm8(src_format: Format, src: &[u8], dst_format: Format, dst: &mut [u8]) {
+    let src_channels = src_format.channels();
+    let dst_channels = dst_format.channels();
+    let unpremultiply = src_format.is_premultiplied() && !dst_format.is_premultiplied();
+    let premultiply = !src_format.is_premultiplied() && dst_format.is_premultiplied();
+    let src_pixels = src.chunks_exact(src_format.bytes_per_pixel());
+    let dst_pixels = dst.chunks_exact_mut(dst_format.bytes_per_pixel());
+    for (src_pixel, dst_pixel) in src_pixels.zip(dst_pixels) {
+        let mut rgba = [0, 0, 0, u8::MAX];
+        for (&channel, &value) in src_channels.iter().zip(src_pixel) {
+            rgba[channel_index(channel)] = value;
+        }
+        let a = u32::from(rgba[3]);
+        if unpremultiply {
+            for c in &mut rgba[..3] {
+                *c = match a {
+                    0 => 0,
+                    _ => ((u32::from(*c) * 255 + a / 2) / a).min(255) as u8,
+                };
+            }
+        } else if premultiply {
+            for c in &mut rgba[..3] {
+                *c = ((u32::from(*c) * a + 127) / 255) as u8;
+            }
+        }
+        for (&channel, value) in dst_channels.iter().zip(dst_pixel) {
+            *value = rgba[channel_index(channel)];
+        }
+    }
+}
+
+/// Converts between formats through straight alpha floating point pixels,
+/// a chunk at a time.
+fn convert_float(src_format: Format, src: &[u8], dst_format: Format, dst: &mut [u8]) {
+    let mut pixels = [[0.0_f32; 4]; CHUNK_PIXELS];
+    let src_chunks = src.chunks(CHUNK_PIXELS * src_format.bytes_per_pixel());
+    let dst_chunks = dst.chunks_mut(CHUNK_PIXELS * dst_format.bytes_per_pixel());
+    for (src_chunk, dst_chunk) in src_chunks.zip(dst_chunks) {
+        let pixels = &mut pixels[..src_chunk.len() / src_format.bytes_per_pixel()];
+        load(src_format, src_chunk, pixels);
+        transform(src_format, dst_format, pixels);
+        store(dst_format, pixels, dst_chunk);
+    }
+}
+
+pub(crate) fn load(format: Format, src: &[u8], pixels: &mut [[f32; 4]]) {
+    let channels = format.channels();
+    let src = src.chunks_exact(format.bytes_per_pixel());
+    for (pixel, bytes) in pixels.iter_mut().zip(src) {
+        *pixel = [0.0, 0.0, 0.0, 1.0];
+        match format.channel_type() {
+            ChannelType::Unorm8 => {
+                for (&channel, &byte) in channels.iter().zip(bytes) {
+                    pixel[channel_index(channel)] = f32::from(byte) * (1.0 / 255.0);
+                }
+            }
+            ChannelType::Float16 => {
+                for (&channel, b) in channels.iter().zip(bytes.chunks_exact(2)) {
+                    pixel[channel_index(channel)] = f16_to_f32(u16::from_le_bytes([b[0], b[1]]));
+                }
+            }
+            ChannelType::Float32 => {
+                for (&channel, b) in channels.iter().zip(bytes.chunks_exact(4)) {
+                    pixel[channel_index(channel)] = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+                }
+            }
+            ChannelType::Unorm10A2 => {
+                let x = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                let unorm10 = |shift: u32| ((x >> shift) & 0x3ff) as f32 * (1.0 / 1023.0);
+                *pixel = [
+                    unorm10(0),
+                    unorm10(10),
+                    unorm10(20),
+                    (x >> 30) as f32 * (1.0 / 3.0),
+                ];
+            }
+        }
+    }
+}
+
+fn transform(src_format: Format, dst_format: Format, pixels: &mut [[f32; 4]]) {
+    let unpremultiply = src_format.is_premultiplied();
+    let premultiply = dst_format.is_premultiplied();
+    let linearize = !src_format.is_linear() && dst_format.is_linear();
+    let encode = src_format.is_linear() && !dst_format.is_linear();
+    for pixel in pixels {
+        let alpha = pixel[3];
+        if unpremultiply {
+            let scale = if alpha > 0.0 { 1.0 / alpha } else { 0.0 };
+            for c in &mut pixel[..3] {
+                *c *= scale;
+            }
+        }
+        if linearize || encode {
+            let rgb = [pixel[0], pixel[1], pixel[2]].map(f64::from);
+            let rgb = if linearize {
+                srgb_to_linear(rgb)
+            } else {
+                linear_to_srgb(rgb)
+            };
+            for (c, v) in pixel.iter_mut().zip(rgb) {
+                *c = v as f32;
+            }
+        }
+        if premultiply {
+            for c in &mut pixel[..3] {
+                *c *= alpha;
+            }
+        }
+    }
+}
+
+fn store(format: Format, pixels: &[[f32; 4]], dst: &mut [u8]) {
+    let channels = format.channels();
+    let dst = dst.chunks_exact_mut(format.bytes_per_pixel());
+    for (pixel, bytes) in pixels.iter().zip(dst) {
+        match format.channel_type() {
+            ChannelType::Unorm8 => {
+                for (&channel, byte) in channels.iter().zip(bytes) {
+                    *byte = unorm(pixel[channel_index(channel)], 255.0) as u8;
+                }
+            }
+            ChannelType::Float16 => {
+                for (&channel, b) in channels.iter().zip(bytes.chunks_exact_mut(2)) {
+                    b.copy_from_slice(&f32_to_f16(pixel[channel_index(channel)]).to_le_bytes());
+                }
+            }
+            ChannelType::Float32 => {
+                for (&channel, b) in channels.iter().zip(bytes.chunks_exact_mut(4)) {
+                    b.copy_from_slice(&pixel[channel_index(channel)].to_le_bytes());
+                }
+            }
+            ChannelType::Unorm10A2 => {
+                let x = unorm(pixel[0], 1023.0)
+                    | unorm(pixel[1], 1023.0) << 10
+                    | unorm(pixel[2], 1023.0) << 20
+                    | unorm(pixel[3], 3.0) << 30;
+                bytes.copy_from_slice(&x.to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Clamps `value` to the range `0.0` to `1.0` and scales it to an integer
+/// in the range `0` to `max`.
+fn unorm(value: f32, max: f32) -> u32 {
+    (value.clamp(0.0, 1.0) * max).round() as u32
+}
+
+/// Converts an IEEE 754 half precision float to single precision.
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = u32::from(h & 0x8000) << 16;
+    let exp = u32::from((h >> 10) & 0x1f);
+    let mantissa = u32::from(h & 0x3ff);
+    match exp {
+        0 => {
+            // Zero or subnormal.
+            let magnitude = mantissa as f32 * (1.0 / 16_777_216.0);
+            f32::from_bits(sign | magnitude.to_bits())
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | mantissa << 13),
+        _ => f32::from_bits(sign | (exp + 112) << 23 | mantissa << 13),
+    }
+}
+
+/// Converts a single precision float to IEEE 754 half precision, rounding
+/// to the nearest value with ties to even.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exp == 0xff {
+        // Infinity or NaN, keeping NaNs quiet.
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let half_exp = exp - 127 + 15;
+    if half_exp >= 0x1f {
+        return sign | 0x7c00;
+    }
+    let (half, full, shift) = if half_exp <= 0 {
+        if half_exp < -10 {
+            return sign;
+        }
+        // Subnormal, including the implicit leading bit.
+        let full = mantissa | 0x80_0000;
+        let shift = (14 - half_exp) as u32;
+        (full >> shift, full, shift)
+    } else {
+        (((half_exp as u32) << 10) | (mantissa >> 13), mantissa, 13)
+    };
+    let remainder = full & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let round_up = remainder > halfway || (remainder == halfway && half & 1 == 1);
+    // A carry out of the mantissa correctly increments the exponent.
+    sign | (half + u32::from(round_up)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{f16_to_f32, f32_to_f16};
+    use crate::{Blob, Format, Image};
+
+    extern crate alloc;
+    use alloc::vec::Vec;
+
+    const FORMATS: [Format; 10] = [
+        Format::Rgba8,
+        Format::Bgra8,
+        Format::Rgba8Premul,
+        Format::Bgra8Premul,
+        Format::A8,
+        Format::R8,
+        Format::Rg8,
+        Format::Rgba16F,
+        Format::Rgba32F,
+        Format::Rgb10A2,
+    ];
+
+    fn image(format: Format, pixels: &[[u8; 4]]) -> Image {
+        let data: Vec<u8> = pixels.iter().flatten().copied().collect();
+        Image::new(Blob::from(data), format, pixels.len() as u32, 1).unwrap()
+    }
+
+    #[test]
+    fn f16_round_trip() {
+        for h in 0..=u16::MAX {
+            let value = f16_to_f32(h);
+            if value.is_nan() {
+                assert!(f32_to_f16(value) & 0x7fff > 0x7c00, "NaN {h:#06x}");
+            } else {
+                assert_eq!(f32_to_f16(value), h, "{h:#06x} as {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn f16_edge_cases() {
+        let tiny = 2.0_f32.powi(-24);
+        assert_eq!(f16_to_f32(0x0001), tiny, "smallest subnormal");
+        assert_eq!(f16_to_f32(0x03ff), 1023.0 * tiny, "largest subnormal");
+        assert_eq!(f16_to_f32(0x8001), -tiny, "negative subnormal");
+        assert_eq!(f16_to_f32(0x0400), 2.0_f32.powi(-14), "smallest normal");
+        assert_eq!(f16_to_f32(0x7bff), 65504.0, "largest normal");
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY, "infinity");
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY, "negative infinity");
+        assert!(f16_to_f32(0x7e00).is_nan(), "NaN");
+        assert_eq!(f16_to_f32(0x8000).to_bits(), (-0.0_f32).to_bits(), "-0");
+
+        for (value, expected, message) in [
+            (f32::INFINITY, 0x7c00, "infinity"),
+            (f32::NEG_INFINITY, 0xfc00, "negative infinity"),
+            (1e6, 0x7c00, "overflow"),
+            (65519.0, 0x7bff, "below the overflow threshold"),
+            (65520.0, 0x7c00, "overflow by rounding"),
+            (-0.0, 0x8000, "negative zero"),
+            (1e-10, 0x0000, "underflow"),
+            (tiny * 0.5, 0x0000, "halfway to the smallest subnormal"),
+            (tiny * 0.75, 0x0001, "rounds up to the smallest subnormal"),
+            (tiny * 1.5, 0x0002, "subnormal tie to even"),
+            (tiny * 2.5, 0x0002, "subnormal tie to even, down"),
+            (1023.5 * tiny, 0x0400, "subnormal rounds up to normal"),
+            (1.0 + 2.0_f32.powi(-11), 0x3c00, "tie to even, down"),
+            (1.0 + 3.0 * 2.0_f32.powi(-11), 0x3c02, "tie to even, up"),
+            (
+                1.0 + 2.0_f32.powi(-11) + 2.0_f32.powi(-20),
+                0x3c01,
+                "above tie",
+            ),
+            (2.0 - 2.0_f32.powi(-11), 0x4000, "mantissa carry"),
+        ] {
+            assert_eq!(f32_to_f16(value), expected, "{message}: {value}");
+        }
+        let nan = f32_to_f16(f32::NAN);
+        assert!(nan & 0x7c00 == 0x7c00 && nan & 0x3ff != 0, "NaN stays NaN");
+        let payload = f32_to_f16(f32::from_bits(0x7f80_0001));
+        assert!(payload & 0x3ff != 0, "NaN with a low payload stays NaN");
+    }
+
+    #[test]
+    fn lossless_round_trips() {
+        let pixels: Vec<[u8; 4]> = (0..=255_u8)
+            .flat_map(|a| (0..=255_u8).map(move |c| [c, 255 - c, c ^ 0x55, a]))
+            .collect();
+        let rgba8 = image(Format::Rgba8, &pixels);
+        for source_format in FORMATS {
+            if source_format.bytes_per_pixel() > 4 || source_format == Format::Rgb10A2 {
+                continue;
+            }
+            let source = rgba8.convert_lossy(source_format).unwrap();
+            for target in FORMATS {
+                if !source_format.converts_losslessly_to(target) {
+                    continue;
+                }
+                let converted = source.convert(target).unwrap();
+                let back = converted.convert_lossy(source_format).unwrap();
+                assert!(
+                    back.data.data() == source.data.data(),
+                    "{source_format:?} to {target:?} and back"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn premultiplied_zero_alpha() {
+        let straight = image(Format::Rgba8, &[[200, 100, 50, 0], [200, 100, 50, 255]]);
+        let premul = straight.convert_lossy(Format::Rgba8Premul).unwrap();
+        assert_eq!(
+            premul.data.data(),
+            [0, 0, 0, 0, 200, 100, 50, 255],
+            "color is lost where alpha is zero"
+        );
+        // Color channels that exceed a zero alpha are invalid, but must not
+        // divide by zero.
+        let invalid = image(Format::Rgba8Premul, &[[10, 20, 30, 0]]);
+        assert_eq!(
+            invalid.convert(Format::Rgba8).unwrap().data.data(),
+            [0, 0, 0, 0],
+            "8-bit unpremultiplication"
+        );
+        let float = invalid.convert(Format::Rgba32F).unwrap();
+        let values: Vec<f32> = float
+            .data
+            .data()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(values, [0.0; 4], "floating point unpremultiplication");
+        assert_eq!(
+            float
+                .convert_lossy(Format::Bgra8Premul)
+                .unwrap()
+                .data
+                .data(),
+            [0, 0, 0, 0],
+            "premultiplied again"
+        );
+    }
+}
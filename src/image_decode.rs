@@ -0,0 +1,587 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Decoding of image files, enabled by the `png` and `jpeg` features.
+
+use super::icc::ColorProfile;
+use super::{Blob, Format, Image, ImageError};
+
+use core::fmt;
+
+/// Errors that can occur when [decoding](Image::decode) an image.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The data is not in a supported image format.
+    UnsupportedFormat,
+    /// The decoded pixels do not form a valid image, such as when the size
+    /// of the image in bytes overflows.
+    Image(ImageError),
+    /// The PNG decoder failed.
+    #[cfg(feature = "png")]
+    Png(png::DecodingError),
+    /// The JPEG decoder failed.
+    #[cfg(feature = "jpeg")]
+    Jpeg(jpeg_decoder::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat => write!(f, "unsupported image format"),
+            Self::Image(e) => write!(f, "invalid decoded image: {e}"),
+            #[cfg(feature = "png")]
+            Self::Png(e) => write!(f, "failed to decode PNG image: {e}"),
+            #[cfg(feature = "jpeg")]
+            Self::Jpeg(e) => write!(f, "failed to decode JPEG image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::UnsupportedFormat => None,
+            Self::Image(e) => Some(e),
+            #[cfg(feature = "png")]
+            Self::Png(e) => Some(e),
+            #[cfg(feature = "jpeg")]
+            Self::Jpeg(e) => Some(e),
+        }
+    }
+}
+
+impl From<ImageError> for DecodeError {
+    fn from(e: ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<png::DecodingError> for DecodeError {
+    fn from(e: png::DecodingError) -> Self {
+        Self::Png(e)
+    }
+}
+
+#[cfg(feature = "jpeg")]
+impl From<jpeg_decoder::Error> for DecodeError {
+    fn from(e: jpeg_decoder::Error) -> Self {
+        Self::Jpeg(e)
+    }
+}
+
+impl Image {
+    /// Decodes an image file.
+    ///
+    /// PNG images are supported with the `png` feature and JPEG images with
+    /// the `jpeg` feature. The file type is detected from the data.
+    ///
+    /// Images with up to 8 bits per channel decode to [`Format::Rgba8`],
+    /// while images with 16 bits per channel decode to [`Format::Rgba32F`]
+    /// to keep their precision. Palette and grayscale images are expanded to
+    /// RGBA. Color is converted to sRGB according to an embedded ICC profile
+    /// (for RGB and grayscale matrix/TRC profiles) or PNG gamma, and the
+    /// image is rotated and flipped according to its EXIF orientation.
+    /// CMYK JPEG images are converted to RGB without a color profile.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DecodeError::UnsupportedFormat`] if the format of the data
+    /// is not recognized or its feature is not enabled, [`DecodeError::Image`]
+    /// if the image is too large, or an error from the decoder.
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        #[cfg(feature = "png")]
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            return Ok(decode_png(data)?.into_image()?);
+        }
+        #[cfg(feature = "jpeg")]
+        if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            return Ok(decode_jpeg(data)?.into_image()?);
+        }
+        Err(DecodeError::UnsupportedFormat)
+    }
+}
+
+/// Straight alpha RGBA pixels in the encoding of the file.
+enum Pixels {
+    Rgba8(Vec<u8>),
+    Rgba16(Vec<u16>),
+}
+
+/// Image decoded from a file, before color conversion and orientation.
+struct Decoded {
+    width: u32,
+    height: u32,
+    pixels: Pixels,
+    /// Color profile of the pixels, or `None` for sRGB.
+    profile: Option<ColorProfile>,
+    /// EXIF orientation, from `1` to `8`.
+    orientation: u16,
+}
+
+impl Decoded {
+    fn into_image(self) -> Result<Image, ImageError> {
+        let (format, data) = match self.pixels {
+            Pixels::Rgba8(mut data) => {
+                if let Some(profile) = &self.profile {
+                    profile.convert_rgba8_to_srgb(&mut data);
+                }
+                (Format::Rgba8, data)
+            }
+            Pixels::Rgba16(data) => {
+                let profile = self.profile.unwrap_or_else(ColorProfile::srgb);
+                let mut bytes = Vec::with_capacity(data.len() * 4);
+                for pixel in data.chunks_exact(4) {
+                    let rgb = [pixel[0], pixel[1], pixel[2]].map(|c| f64::from(c) / 65535.0);
+                    let [r, g, b] = profile.to_linear_srgb(rgb).map(|c| c as f32);
+                    let a = f32::from(pixel[3]) / 65535.0;
+                    for c in [r, g, b, a] {
+                        bytes.extend_from_slice(&c.to_le_bytes());
+                    }
+                }
+                (Format::Rgba32F, bytes)
+            }
+        };
+        let (data, width, height) = orient(
+            data,
+            format.bytes_per_pixel(),
+            self.width,
+            self.height,
+            self.orientation,
+        );
+        Image::new(Blob::from(data), format, width, height)
+    }
+}
+
+/// Expands pixels with the given number of gray, gray and alpha, RGB or RGBA
+/// channels to RGBA.
+fn expand_to_rgba<T: Copy>(samples: &[T], channels: usize, opaque: T) -> Vec<T> {
+    let mut rgba = Vec::with_capacity(samples.len() / channels * 4);
+    for pixel in samples.chunks_exact(channels) {
+        rgba.extend_from_slice(&match *pixel {
+            [l] => [l, l, l, opaque],
+            [l, a] => [l, l, l, a],
+            [r, g, b] => [r, g, b, opaque],
+            [r, g, b, a, ..] => [r, g, b, a],
+            [] => unreachable!("pixels have at least one channel"),
+        });
+    }
+    rgba
+}
+
+#[cfg(feature = "png")]
+fn decode_png(data: &[u8]) -> Result<Decoded, DecodeError> {
+    let mut decoder = png::Decoder::new(data);
+    // Expands palettes, transparency chunks and bit depths below 8.
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf)?;
+    buf.truncate(frame.buffer_size());
+    let info = reader.info();
+    // An sRGB chunk takes precedence, followed by an ICC profile and then
+    // gamma. Chromaticities are not used.
+    let profile = if info.srgb.is_some() {
+        None
+    } else if let Some(profile) = info.icc_profile.as_deref().and_then(ColorProfile::parse) {
+        Some(profile)
+    } else {
+        info.gama_chunk.map(|g| g.into_value()).and_then(|gamma| {
+            // Treat the gamma of sRGB as sRGB, as browsers do.
+            let srgb = (f64::from(gamma) * 2.2 - 1.0).abs() < 0.01;
+            (gamma > 0.0 && !srgb).then(|| ColorProfile::gamma(1.0 / f64::from(gamma)))
+        })
+    };
+    // The decoder does not read `eXIf` chunks, so find it separately.
+    let orientation = info
+        .exif_metadata
+        .as_deref()
+        .or_else(|| png_exif(data))
+        .and_then(exif_orientation)
+        .unwrap_or(1);
+    let channels = frame.color_type.samples();
+    let pixels = match frame.bit_depth {
+        png::BitDepth::Sixteen => {
+            let samples: Vec<u16> = buf
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect();
+            Pixels::Rgba16(expand_to_rgba(&samples, channels, u16::MAX))
+        }
+        _ => Pixels::Rgba8(expand_to_rgba(&buf, channels, u8::MAX)),
+    };
+    Ok(Decoded {
+        width: frame.width,
+        height: frame.height,
+        pixels,
+        profile,
+        orientation,
+    })
+}
+
+/// Returns the contents of the `eXIf` chunk of a PNG file.
+#[cfg(feature = "png")]
+fn png_exif(data: &[u8]) -> Option<&[u8]> {
+    // Skip the signature.
+    let mut pos = 8;
+    loop {
+        let len = data.get(pos..pos + 4)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let chunk_type = data.get(pos + 4..pos + 8)?;
+        let start = pos + 8;
+        match chunk_type {
+            b"eXIf" => return data.get(start..start.checked_add(len)?),
+            b"IEND" => return None,
+            // Skip the data and checksum.
+            _ => pos = start.checked_add(len)?.checked_add(4)?,
+        }
+    }
+}
+
+#[cfg(feature = "jpeg")]
+fn decode_jpeg(data: &[u8]) -> Result<Decoded, DecodeError> {
+    use jpeg_decoder::PixelFormat;
+
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let samples = decoder.decode()?;
+    let info = decoder
+        .info()
+        .ok_or(jpeg_decoder::Error::Format("missing image info".into()))?;
+    let profile = decoder
+        .icc_profile()
+        .as_deref()
+        .and_then(ColorProfile::parse);
+    let orientation = decoder.exif_data().and_then(exif_orientation).unwrap_or(1);
+    let pixels = match info.pixel_format {
+        PixelFormat::L8 => Pixels::Rgba8(expand_to_rgba(&samples, 1, u8::MAX)),
+        PixelFormat::L16 => {
+            // Samples range up to the maximum for the precision of the
+            // image, such as 4095 for 12 bits, so rescale them to 16 bits.
+            let precision = jpeg_precision(data).unwrap_or(16).clamp(9, 16);
+            let max = (1_u32 << precision) - 1;
+            let samples: Vec<u16> = samples
+                .chunks_exact(2)
+                .map(|b| {
+                    let sample = u32::from(u16::from_ne_bytes([b[0], b[1]])).min(max);
+                    ((sample * 65535 + max / 2) / max) as u16
+                })
+                .collect();
+            Pixels::Rgba16(expand_to_rgba(&samples, 1, u16::MAX))
+        }
+        PixelFormat::RGB24 => Pixels::Rgba8(expand_to_rgba(&samples, 3, u8::MAX)),
+        PixelFormat::CMYK32 => {
+            let rgba = samples
+                .chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let k = 255 - u32::from(cmyk[3]);
+                    let rgb = |c: u8| ((255 - u32::from(c)) * k / 255) as u8;
+                    [rgb(cmyk[0]), rgb(cmyk[1]), rgb(cmyk[2]), u8::MAX]
+                })
+                .collect();
+            Pixels::Rgba8(rgba)
+        }
+    };
+    Ok(Decoded {
+        width: info.width.into(),
+        height: info.height.into(),
+        pixels,
+        // CMYK profiles are not supported.
+        profile: profile.filter(|_| info.pixel_format != PixelFormat::CMYK32),
+        orientation,
+    })
+}
+
+/// Reads the sample precision in bits from the frame header of a JPEG file.
+#[cfg(feature = "jpeg")]
+fn jpeg_precision(data: &[u8]) -> Option<u8> {
+    // Skip the start of image marker.
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // Fill bytes.
+            0xff => pos += 1,
+            // Start of frame markers, which share their range with the
+            // markers for Huffman tables, arithmetic coding conditioning and
+            // a reserved marker.
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                return data.get(pos + 4).copied();
+            }
+            _ => {
+                let len = data.get(pos + 2..pos + 4)?;
+                pos += 2 + usize::from(u16::from_be_bytes([len[0], len[1]]));
+            }
+        }
+    }
+}
+
+/// Reads the orientation from EXIF data that starts with the TIFF header,
+/// optionally preceded by the `Exif` marker.
+fn exif_orientation(exif: &[u8]) -> Option<u16> {
+    const ORIENTATION_TAG: u16 = 0x0112;
+    let exif = exif.strip_prefix(b"Exif\0\0").unwrap_or(exif);
+    let big_endian = match exif.get(..4)? {
+        b"II*\0" => false,
+        b"MM\0*" => true,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| {
+        let b = exif.get(offset..offset + 2)?;
+        Some(if big_endian {
+            u16::from_be_bytes([b[0], b[1]])
+        } else {
+            u16::from_le_bytes([b[0], b[1]])
+        })
+    };
+    let b = exif.get(4..8)?;
+    let ifd = if big_endian {
+        u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+    } else {
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+    } as usize;
+    let count = usize::from(read_u16(ifd)?);
+    (0..count).find_map(|i| {
+        let entry = ifd + 2 + 12 * i;
+        if read_u16(entry)? != ORIENTATION_TAG {
+            return None;
+        }
+        read_u16(entry + 8).filter(|o| (1..=8).contains(o))
+    })
+}
+
+/// Applies an EXIF orientation to tightly packed pixels, returning the
+/// pixels and their new dimensions.
+fn orient(
+    data: Vec<u8>,
+    bytes_per_pixel: usize,
+    width: u32,
+    height: u32,
+    orientation: u16,
+) -> (Vec<u8>, u32, u32) {
+    if !(2..=8).contains(&orientation) {
+        return (data, width, height);
+    }
+    let (w, h) = (width as usize, height as usize);
+    // Orientations 5 to 8 swap the axes.
+    let (out_width, out_height) = if orientation >= 5 { (h, w) } else { (w, h) };
+    let mut out = vec![0; data.len()];
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let (sx, sy) = match orientation {
+                2 => (w - 1 - x, y),
+                3 => (w - 1 - x, h - 1 - y),
+                4 => (x, h - 1 - y),
+                5 => (y, x),
+                6 => (y, h - 1 - x),
+                7 => (w - 1 - y, h - 1 - x),
+                _ => (w - 1 - y, x),
+            };
+            let src = (sy * w + sx) * bytes_per_pixel;
+            let dst = (y * out_width + x) * bytes_per_pixel;
+            out[dst..dst + bytes_per_pixel].copy_from_slice(&data[src..src + bytes_per_pixel]);
+        }
+    }
+    (out, out_width as u32, out_height as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colorspace::srgb_to_linear;
+    use crate::{ColorSpace, Format, Image};
+
+    /// The colors of the labelled pixels or blocks of the orientation
+    /// fixtures, which are laid out in two rows of three.
+    const LABELS: [[u8; 3]; 6] = [
+        [255, 0, 0],
+        [0, 255, 0],
+        [0, 0, 255],
+        [255, 255, 0],
+        [255, 0, 255],
+        [0, 255, 255],
+    ];
+
+    /// The expected layout of the labels for each EXIF orientation.
+    const ORIENTED: [&[&[usize]]; 8] = [
+        &[&[0, 1, 2], &[3, 4, 5]],
+        // Mirrored horizontally.
+        &[&[2, 1, 0], &[5, 4, 3]],
+        // Rotated by 180°.
+        &[&[5, 4, 3], &[2, 1, 0]],
+        // Mirrored vertically.
+        &[&[3, 4, 5], &[0, 1, 2]],
+        // Transposed.
+        &[&[0, 3], &[1, 4], &[2, 5]],
+        // Rotated clockwise by 90°.
+        &[&[3, 0], &[4, 1], &[5, 2]],
+        // Transversed.
+        &[&[5, 2], &[4, 1], &[3, 0]],
+        // Rotated counterclockwise by 90°.
+        &[&[2, 5], &[1, 4], &[0, 3]],
+    ];
+
+    fn decode(name: &str) -> Image {
+        let path = format!("{}/testdata/images/{name}", env!("CARGO_MANIFEST_DIR"));
+        let data = std::fs::read(&path).unwrap_or_else(|e| panic!("reading {path}: {e}"));
+        Image::decode(&data).unwrap_or_else(|e| panic!("decoding {name}: {e}"))
+    }
+
+    fn rgba8(image: &Image, x: u32, y: u32) -> [u8; 4] {
+        assert_eq!(image.format, Format::Rgba8, "format");
+        let offset = x as usize * 4;
+        let p = &image.row(y).unwrap()[offset..offset + 4];
+        [p[0], p[1], p[2], p[3]]
+    }
+
+    fn rgba32f(image: &Image, x: u32, y: u32) -> [f32; 4] {
+        assert_eq!(image.format, Format::Rgba32F, "format");
+        let offset = x as usize * 16;
+        let p = &image.row(y).unwrap()[offset..offset + 16];
+        [0, 1, 2, 3]
+            .map(|i| f32::from_le_bytes([p[4 * i], p[4 * i + 1], p[4 * i + 2], p[4 * i + 3]]))
+    }
+
+    #[track_caller]
+    fn assert_close(actual: [u8; 4], expected: [u8; 4], tolerance: u8, context: &str) {
+        let close = actual
+            .iter()
+            .zip(expected)
+            .all(|(&a, e)| a.abs_diff(e) <= tolerance);
+        assert!(
+            close,
+            "{context}: {actual:?} is not within {tolerance} of {expected:?}"
+        );
+    }
+
+    /// Checks the layout of the labels in an oriented image, sampling the
+    /// center of each block of `block` pixels.
+    fn assert_oriented(image: &Image, orientation: usize, block: u32, tolerance: u8) {
+        let layout = ORIENTED[orientation - 1];
+        assert_eq!(
+            (image.width, image.height),
+            (layout[0].len() as u32 * block, layout.len() as u32 * block),
+            "size for orientation {orientation}"
+        );
+        for (y, row) in (0..).zip(layout) {
+            for (x, &label) in (0..).zip(*row) {
+                let [r, g, b] = LABELS[label];
+                let center = block / 2;
+                assert_close(
+                    rgba8(image, x * block + center, y * block + center),
+                    [r, g, b, 255],
+                    tolerance,
+                    &format!("block ({x}, {y}) for orientation {orientation}"),
+                );
+            }
+        }
+    }
+
+    /// Converts an 8-bit Display P3 color to 8-bit sRGB, clamping colors that
+    /// are outside of the sRGB gamut.
+    fn display_p3_to_srgb(rgb: [u8; 3]) -> [u8; 4] {
+        let p3 = rgb.map(|c| f64::from(c) / 255.0);
+        let [r, g, b] = ColorSpace::DisplayP3
+            .convert(ColorSpace::Srgb, p3)
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        [r, g, b, 255]
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_rgba8() {
+        let image = decode("rgba8.png");
+        assert_eq!((image.width, image.height), (3, 2), "size");
+        let expected = [
+            [[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 0]],
+            [[10, 20, 30, 40], [128, 128, 128, 255], [255, 255, 255, 255]],
+        ];
+        for (y, row) in (0..).zip(expected) {
+            for (x, pixel) in (0..).zip(row) {
+                assert_eq!(rgba8(&image, x, y), pixel, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_gray16() {
+        let image = decode("gray16.png");
+        assert_eq!((image.width, image.height), (3, 1), "size");
+        for (x, value) in (0..).zip([0_u16, 32768, 65535]) {
+            let [expected, ..] = srgb_to_linear([f64::from(value) / 65535.0; 3]);
+            let pixel = rgba32f(&image, x, 0);
+            for c in &pixel[..3] {
+                assert!(
+                    (f64::from(*c) - expected).abs() < 1e-6,
+                    "pixel {x} was {pixel:?}, expected {expected}"
+                );
+            }
+            assert_eq!(pixel[3], 1.0, "alpha of pixel {x}");
+        }
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_orientation() {
+        for orientation in 1..=8 {
+            let image = decode(&format!("orientation-{orientation}.png"));
+            assert_oriented(&image, orientation, 1, 0);
+        }
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_icc_profile() {
+        let image = decode("display-p3.png");
+        let colors = [[255, 0, 0], [200, 100, 50], [128, 128, 128], [20, 180, 90]];
+        for (x, color) in (0..).zip(colors) {
+            assert_close(
+                rgba8(&image, x, 0),
+                display_p3_to_srgb(color),
+                1,
+                &format!("Display P3 {color:?}"),
+            );
+        }
+        // Gray is the same in both spaces.
+        assert_eq!(rgba8(&image, 2, 0), [128, 128, 128, 255], "gray");
+    }
+
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn jpeg_orientation() {
+        for orientation in 1..=8 {
+            let image = decode(&format!("orientation-{orientation}.jpg"));
+            assert_oriented(&image, orientation, 8, 4);
+        }
+    }
+
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn jpeg_icc_profile() {
+        let image = decode("display-p3.jpg");
+        assert_close(
+            rgba8(&image, 4, 4),
+            display_p3_to_srgb([200, 100, 50]),
+            2,
+            "Display P3",
+        );
+    }
+
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn jpeg_lossless_12_bit() {
+        let image = decode("gray12-lossless.jpg");
+        assert_eq!((image.width, image.height), (4, 1), "size");
+        for (x, value) in (0..).zip([0_u16, 1000, 2048, 4095]) {
+            let [expected, ..] = srgb_to_linear([f64::from(value) / 4095.0; 3]);
+            let pixel = rgba32f(&image, x, 0);
+            assert!(
+                (f64::from(pixel[0]) - expected).abs() < 1e-4,
+                "sample {value} decoded to {pixel:?}, expected {expected}"
+            );
+        }
+    }
+}
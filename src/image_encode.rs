@@ -0,0 +1,154 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Encoding of images to PNG, enabled by the `png` feature.
+
+use super::colorspace::linear_to_srgb;
+use super::{ChannelType, Format, Image, ImageError};
+
+use core::fmt;
+
+/// Errors that can occur when [encoding](Image::encode_png) an image.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// The image is invalid.
+    Image(ImageError),
+    /// The PNG encoder failed.
+    Png(png::EncodingError),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Image(e) => write!(f, "invalid image: {e}"),
+            Self::Png(e) => write!(f, "failed to encode PNG image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Image(e) => Some(e),
+            Self::Png(e) => Some(e),
+        }
+    }
+}
+
+impl From<ImageError> for EncodeError {
+    fn from(e: ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+impl From<png::EncodingError> for EncodeError {
+    fn from(e: png::EncodingError) -> Self {
+        Self::Png(e)
+    }
+}
+
+impl Image {
+    /// Encodes the [region](Self::region) of the image as an sRGB PNG file.
+    ///
+    /// Formats with 8-bit channels are written as 8-bit RGBA, and other
+    /// formats as 16-bit RGBA, with alpha unpremultiplied. Colors outside of
+    /// the range `0.0` to `1.0` are clamped. The alpha multiplier of the
+    /// image is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image fails [validation](Self::validate) or
+    /// the encoder fails.
+    pub fn encode_png(&self) -> Result<Vec<u8>, EncodeError> {
+        let (data, bit_depth) = if self.format.channel_type() == ChannelType::Unorm8 {
+            let image = self.convert_lossy(Format::Rgba8)?;
+            (image.data.data().to_vec(), png::BitDepth::Eight)
+        } else {
+            let image = self.convert(Format::Rgba32F)?;
+            let mut data = Vec::with_capacity(image.data.len() / 2);
+            for pixel in image.data.data().chunks_exact(16) {
+                let channel = |i: usize| {
+                    let b = &pixel[i * 4..i * 4 + 4];
+                    f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                };
+                let [r, g, b] = linear_to_srgb([channel(0), channel(1), channel(2)]);
+                for c in [r, g, b, channel(3)] {
+                    let c = (c.clamp(0.0, 1.0) * 65535.0).round() as u16;
+                    data.extend_from_slice(&c.to_be_bytes());
+                }
+            }
+            (data, png::BitDepth::Sixteen)
+        };
+        let region = self.region();
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, region.width, region.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(bit_depth);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Blob, Format, Image};
+
+    #[test]
+    fn round_trip_rgba8() {
+        let data = [
+            255, 0, 0, 255, 0, 128, 255, 128, 10, 20, 30, 0, 200, 100, 50, 255,
+        ];
+        let image = Image::new(Blob::from(data.to_vec()), Format::Rgba8, 2, 2).unwrap();
+        let decoded = Image::decode(&image.encode_png().unwrap()).unwrap();
+        assert_eq!(decoded.format, Format::Rgba8, "format");
+        assert_eq!(decoded.data.data(), data, "pixels");
+        assert!(
+            decoded.compare(&image, 0.0).unwrap().is_match(),
+            "comparison"
+        );
+    }
+
+    #[test]
+    fn round_trip_premultiplied() {
+        let data = [128, 64, 0, 128, 0, 0, 0, 0];
+        let image = Image::new(Blob::from(data.to_vec()), Format::Bgra8Premul, 2, 1).unwrap();
+        let decoded = Image::decode(&image.encode_png().unwrap()).unwrap();
+        let diff = decoded.compare(&image, 1.0 / 255.0).unwrap();
+        assert!(diff.is_match(), "max delta was {}", diff.max_delta);
+    }
+
+    #[test]
+    fn round_trip_float() {
+        let pixels = [[0.0_f32, 0.25, 1.0, 1.0], [0.5, 0.001, 0.75, 0.5]];
+        let data: Vec<u8> = pixels
+            .iter()
+            .flatten()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        let image = Image::new(Blob::from(data), Format::Rgba32F, 2, 1).unwrap();
+        let decoded = Image::decode(&image.encode_png().unwrap()).unwrap();
+        assert_eq!(decoded.format, Format::Rgba32F, "16-bit PNG");
+        let diff = decoded.compare(&image, 1e-4).unwrap();
+        assert!(diff.is_match(), "max delta was {}", diff.max_delta);
+    }
+
+    #[test]
+    fn sub_rect() {
+        let data: Vec<u8> = (0..16).map(|i| i * 16).collect();
+        let image = Image::new(Blob::from(data), Format::Rgba8, 2, 2)
+            .unwrap()
+            .with_sub_rect(crate::ImageRect::new(1, 0, 1, 2))
+            .unwrap();
+        let decoded = Image::decode(&image.encode_png().unwrap()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (1, 2), "size");
+        assert_eq!(
+            decoded.data.data(),
+            [64, 80, 96, 112, 192, 208, 224, 240],
+            "pixels of the region"
+        );
+    }
+}
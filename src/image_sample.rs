@@ -0,0 +1,442 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! CPU reference sampling of images.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::image_convert::load;
+use super::{ColorSpace, DynamicColor, Extend, Image, ImageQuality, ImageRect};
+
+use kurbo::Point;
+
+use core::cell::OnceCell;
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+/// Premultiplied RGBA pixel.
+type Pixel = [f64; 4];
+
+impl Image {
+    /// Returns the filtered color of the image at the given point.
+    ///
+    /// This is a reference implementation intended for testing renderers and
+    /// for CPU fallback rendering, and favors precision over speed.
+    ///
+    /// The point is in the pixel coordinates of the [region](Self::region) of
+    /// the image, where the center of the top left pixel is at `(0.5, 0.5)`.
+    /// Pixels outside of the region are determined by the
+    /// [horizontal](Self::x_extend) and [vertical](Self::y_extend) extend
+    /// modes. `scale` is the number of image pixels covered by one pixel of
+    /// the output, and selects the mipmap level for
+    /// [`ImageQuality::Trilinear`]; it is ignored by the other qualities.
+    ///
+    /// Filtering is done with premultiplied alpha on the stored values, so
+    /// the result is in [`ColorSpace::Srgb`], or [`ColorSpace::LinearSrgb`]
+    /// for [linear](crate::Format::is_linear) formats. The alpha multiplier
+    /// of the image is applied. Images with no pixels, or with data that
+    /// fails [validation](Self::validate), are transparent.
+    ///
+    /// Trilinear filtering computes the mipmap levels that it reads on each
+    /// call. Use a [`sampler`](Self::sampler) to reuse them when sampling
+    /// many points.
+    #[must_use]
+    pub fn sample(&self, point: Point, scale: f64) -> DynamicColor {
+        self.sampler().sample(point, scale)
+    }
+
+    /// Returns a sampler for the image, which computes each mipmap level once
+    /// and reuses it for all of its samples.
+    #[must_use]
+    pub fn sampler(&self) -> ImageSampler<'_> {
+        let region = self.region();
+        let valid = region.width > 0 && region.height > 0 && self.validate().is_ok();
+        let level_count = 32 - region.width.max(region.height).leading_zeros();
+        ImageSampler {
+            image: self,
+            region,
+            valid,
+            levels: (1..level_count).map(|_| OnceCell::new()).collect(),
+        }
+    }
+}
+
+/// Samples an [image](Image) with the same results as [`Image::sample`],
+/// computing the mipmap levels used by [`ImageQuality::Trilinear`] once.
+///
+/// Pixels of mipmap levels are the average of the pixels of the region that
+/// they cover. Levels are computed when they are first used.
+#[derive(Debug)]
+pub struct ImageSampler<'a> {
+    image: &'a Image,
+    region: ImageRect,
+    valid: bool,
+    /// Premultiplied pixels of the mipmap levels after the first, in rows.
+    levels: Vec<OnceCell<Vec<Pixel>>>,
+}
+
+impl ImageSampler<'_> {
+    /// Returns the filtered color of the image at the given point, as
+    /// described for [`Image::sample`].
+    #[must_use]
+    pub fn sample(&self, point: Point, scale: f64) -> DynamicColor {
+        let space = if self.image.format.is_linear() {
+            ColorSpace::LinearSrgb
+        } else {
+            ColorSpace::Srgb
+        };
+        if !self.valid {
+            return DynamicColor::new(space, [0.0; 3], 0.0);
+        }
+        let pixel = match self.image.quality {
+            ImageQuality::Nearest => self.nearest(point),
+            ImageQuality::Bilinear => self.bilinear(0, point),
+            ImageQuality::Bicubic => self.bicubic(point),
+            ImageQuality::Trilinear => self.trilinear(point, scale),
+        };
+        let alpha = pixel[3] * f64::from(self.image.alpha) / 255.0;
+        let components = if pixel[3] > 0.0 {
+            [0, 1, 2].map(|i| (pixel[i] / pixel[3]) as f32)
+        } else {
+            [0.0; 3]
+        };
+        DynamicColor::new(space, components, alpha as f32)
+    }
+
+    /// Returns the number of mipmap levels, where each level halves the size
+    /// of the previous one, down to a single pixel.
+    fn level_count(&self) -> u32 {
+        self.levels.len() as u32 + 1
+    }
+
+    /// Returns the size of a mipmap level.
+    fn level_size(&self, level: u32) -> (u32, u32) {
+        (
+            (self.region.width >> level).max(1),
+            (self.region.height >> level).max(1),
+        )
+    }
+
+    /// Returns the pixel at the given coordinates of a mipmap level, after
+    /// applying the extend mode.
+    fn texel(&self, level: u32, x: i64, y: i64) -> Pixel {
+        let (width, height) = self.level_size(level);
+        let (Some(x), Some(y)) = (
+            extend_index(self.image.x_extend, x, width),
+            extend_index(self.image.y_extend, y, height),
+        ) else {
+            return [0.0; 4];
+        };
+        match level {
+            0 => self.fetch(x, y),
+            _ => {
+                let pixels = self.levels[level as usize - 1].get_or_init(|| self.build(level));
+                pixels[y as usize * width as usize + x as usize]
+            }
+        }
+    }
+
+    /// Computes a mipmap level by averaging the pixels of the region that
+    /// each of its pixels covers.
+    fn build(&self, level: u32) -> Vec<Pixel> {
+        let (width, height) = self.level_size(level);
+        let (region_width, region_height) =
+            (u64::from(self.region.width), u64::from(self.region.height));
+        let span = |i: u32, size: u32, full: u64| {
+            let (i, size) = (u64::from(i), u64::from(size));
+            i * full / size..(i + 1) * full / size
+        };
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            let y_range = span(y, height, region_height);
+            for x in 0..width {
+                let x_range = span(x, width, region_width);
+                let count = ((x_range.end - x_range.start) * (y_range.end - y_range.start)) as f64;
+                let mut sum = [0.0; 4];
+                for py in y_range.clone() {
+                    for px in x_range.clone() {
+                        let pixel = self.fetch(px as u32, py as u32);
+                        for (s, c) in sum.iter_mut().zip(pixel) {
+                            *s += c;
+                        }
+                    }
+                }
+                pixels.push(sum.map(|s| s / count));
+            }
+        }
+        pixels
+    }
+
+    /// Loads a pixel of the region with premultiplied alpha.
+    fn fetch(&self, x: u32, y: u32) -> Pixel {
+        let format = self.image.format;
+        let bytes_per_pixel = format.bytes_per_pixel();
+        let start = x as usize * bytes_per_pixel;
+        let mut pixel = [[0.0_f32; 4]];
+        if let Some(bytes) = self
+            .image
+            .row(y)
+            .and_then(|row| row.get(start..start + bytes_per_pixel))
+        {
+            load(format, bytes, &mut pixel);
+        }
+        let mut pixel = pixel[0].map(f64::from);
+        if !format.is_premultiplied() {
+            for i in 0..3 {
+                pixel[i] *= pixel[3];
+            }
+        }
+        pixel
+    }
+
+    fn nearest(&self, point: Point) -> Pixel {
+        self.texel(0, point.x.floor() as i64, point.y.floor() as i64)
+    }
+
+    fn bilinear(&self, level: u32, point: Point) -> Pixel {
+        let (width, height) = self.level_size(level);
+        let x = point.x * f64::from(width) / f64::from(self.region.width) - 0.5;
+        let y = point.y * f64::from(height) / f64::from(self.region.height) - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let mut result = [0.0; 4];
+        for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+            for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                let texel = self.texel(level, x0 + dx, y0 + dy);
+                for (r, c) in result.iter_mut().zip(texel) {
+                    *r += c * wx * wy;
+                }
+            }
+        }
+        result
+    }
+
+    fn bicubic(&self, point: Point) -> Pixel {
+        let x = point.x - 0.5;
+        let y = point.y - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let mut result = [0.0; 4];
+        for dy in -1..=2 {
+            let wy = mitchell(dy as f64 - fy);
+            for dx in -1..=2 {
+                let wx = mitchell(dx as f64 - fx);
+                let texel = self.texel(0, x0 + dx, y0 + dy);
+                for (r, c) in result.iter_mut().zip(texel) {
+                    *r += c * wx * wy;
+                }
+            }
+        }
+        // The filter has negative lobes, so keep the result a valid
+        // premultiplied color.
+        let alpha = result[3].clamp(0.0, 1.0);
+        [
+            result[0].clamp(0.0, alpha),
+            result[1].clamp(0.0, alpha),
+            result[2].clamp(0.0, alpha),
+            alpha,
+        ]
+    }
+
+    fn trilinear(&self, point: Point, scale: f64) -> Pixel {
+        let max_level = self.level_count() - 1;
+        let lod = if scale > 1.0 {
+            scale.log2().min(f64::from(max_level))
+        } else {
+            0.0
+        };
+        let level = lod.floor() as u32;
+        let lower = self.bilinear(level, point);
+        if level == max_level {
+            return lower;
+        }
+        let upper = self.bilinear(level + 1, point);
+        let t = lod - f64::from(level);
+        [0, 1, 2, 3].map(|i| lower[i] + t * (upper[i] - lower[i]))
+    }
+}
+
+/// Maps a pixel index onto the range `0` to `n - 1` according to the extend
+/// mode, or returns `None` if the pixel is transparent.
+fn extend_index(extend: Extend, i: i64, n: u32) -> Option<u32> {
+    let n = i64::from(n);
+    let i = match extend {
+        Extend::Decal if !(0..n).contains(&i) => return None,
+        Extend::Decal => i,
+        Extend::Pad => i.clamp(0, n - 1),
+        Extend::Repeat => i.rem_euclid(n),
+        Extend::Reflect => {
+            let i = i.rem_euclid(2 * n);
+            if i < n {
+                i
+            } else {
+                2 * n - 1 - i
+            }
+        }
+    };
+    Some(i as u32)
+}
+
+/// The Mitchell-Netravali cubic filter with `B = C = 1/3`.
+fn mitchell(x: f64) -> f64 {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+    let x = x.abs();
+    let x2 = x * x;
+    let x3 = x2 * x;
+    if x < 1.0 {
+        ((12.0 - 9.0 * B - 6.0 * C) * x3 + (-18.0 + 12.0 * B + 6.0 * C) * x2 + (6.0 - 2.0 * B))
+            / 6.0
+    } else if x < 2.0 {
+        ((-B - 6.0 * C) * x3
+            + (6.0 * B + 30.0 * C) * x2
+            + (-12.0 * B - 48.0 * C) * x
+            + (8.0 * B + 24.0 * C))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Extend, Image, ImageQuality};
+    use crate::{Blob, ColorSpace, DynamicColor, Format};
+    use kurbo::Point;
+
+    extern crate alloc;
+    use alloc::vec::Vec;
+
+    /// Returns an opaque gray image with the given 8-bit levels, in rows.
+    fn gray(width: u32, height: u32, levels: &[u8]) -> Image {
+        let data: Vec<u8> = levels.iter().flat_map(|&v| [v, v, v, 255]).collect();
+        Image::new(Blob::from(data), Format::Rgba8, width, height).unwrap()
+    }
+
+    #[track_caller]
+    fn assert_gray(color: DynamicColor, level: f32, message: &str) {
+        assert_eq!(color.space, ColorSpace::Srgb, "{message}: space");
+        assert!((color.alpha - 1.0).abs() < 1e-6, "{message}: alpha");
+        for c in color.components {
+            assert!(
+                (c - level).abs() < 1e-5,
+                "{message}: expected {level}, got {color:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn texel_centers() {
+        let levels = [0, 51, 102, 153, 204, 255];
+        for quality in [ImageQuality::Nearest, ImageQuality::Bilinear] {
+            let image = gray(6, 1, &levels).with_quality(quality);
+            for (i, &level) in levels.iter().enumerate() {
+                let point = Point::new(i as f64 + 0.5, 0.5);
+                assert_gray(
+                    image.sample(point, 1.0),
+                    f32::from(level) / 255.0,
+                    &format!("{quality:?} at texel {i}"),
+                );
+            }
+        }
+        // The filter is not interpolating, but reproduces linear ramps.
+        let ramp = gray(6, 1, &levels).with_quality(ImageQuality::Bicubic);
+        assert_gray(ramp.sample(Point::new(2.5, 0.5), 1.0), 0.4, "bicubic ramp");
+        let uniform = gray(3, 3, &[102; 9]).with_quality(ImageQuality::Bicubic);
+        for (x, y) in [(0.5, 0.5), (1.5, 1.5), (2.5, 0.5)] {
+            assert_gray(
+                uniform.sample(Point::new(x, y), 1.0),
+                0.4,
+                "bicubic uniform",
+            );
+        }
+        let between = gray(2, 1, &[0, 255]).with_quality(ImageQuality::Bilinear);
+        assert_gray(between.sample(Point::new(1.0, 0.5), 1.0), 0.5, "bilinear");
+    }
+
+    #[test]
+    fn extend_modes() {
+        let image = gray(2, 1, &[0, 255]).with_quality(ImageQuality::Nearest);
+        let sample = |extend, x| {
+            image
+                .clone()
+                .with_extend(extend)
+                .sample(Point::new(x, 0.5), 1.0)
+        };
+        assert_gray(sample(Extend::Pad, -1.5), 0.0, "pad before");
+        assert_gray(sample(Extend::Pad, 3.5), 1.0, "pad after");
+        assert_gray(sample(Extend::Repeat, 2.5), 0.0, "repeat after");
+        assert_gray(sample(Extend::Repeat, -0.5), 1.0, "repeat before");
+        assert_gray(sample(Extend::Reflect, 2.5), 1.0, "reflect after");
+        assert_gray(sample(Extend::Reflect, -0.5), 0.0, "reflect before");
+        assert_gray(sample(Extend::Decal, 1.5), 1.0, "decal inside");
+        for x in [-0.5, 2.5] {
+            assert_eq!(sample(Extend::Decal, x).alpha, 0.0, "decal outside at {x}");
+        }
+        let vertical = gray(1, 2, &[0, 255])
+            .with_x_extend(Extend::Decal)
+            .with_y_extend(Extend::Repeat)
+            .with_quality(ImageQuality::Nearest);
+        assert_gray(
+            vertical.sample(Point::new(0.5, 2.5), 1.0),
+            0.0,
+            "separate vertical extend",
+        );
+        assert_eq!(
+            vertical.sample(Point::new(1.5, 0.5), 1.0).alpha,
+            0.0,
+            "separate horizontal extend"
+        );
+    }
+
+    #[test]
+    fn mip_levels() {
+        #[rustfmt::skip]
+        let levels = [
+            0, 0, 255, 255,
+            0, 0, 255, 255,
+            0, 0, 255, 255,
+            0, 0, 255, 255,
+        ];
+        let image = gray(4, 4, &levels).with_quality(ImageQuality::Trilinear);
+        let sampler = image.sampler();
+        let point = Point::new(0.5, 0.5);
+        for (scale, level, message) in [
+            (0.5, 0.0, "magnified"),
+            (1.0, 0.0, "level 0"),
+            (2.0, 0.0, "level 1"),
+            (2.0_f64.powf(1.5), 0.25, "between levels 1 and 2"),
+            (4.0, 0.5, "level 2"),
+            (100.0, 0.5, "past the last level"),
+        ] {
+            let color = sampler.sample(point, scale);
+            assert_gray(color, level, message);
+            assert_eq!(color, image.sample(point, scale), "{message}: sampler");
+        }
+        // The left pixel of level 1 covers only black pixels, while bilinear
+        // filtering at its edge mixes in the right pixel.
+        assert_gray(
+            sampler.sample(Point::new(2.0, 1.0), 2.0),
+            0.5,
+            "level 1 edge",
+        );
+    }
+
+    #[test]
+    fn invalid_images_are_transparent() {
+        let empty = gray(0, 0, &[]);
+        assert_eq!(empty.sample(Point::new(0.5, 0.5), 1.0).alpha, 0.0, "empty");
+        let mut short = gray(2, 1, &[0, 255]);
+        short.height = 2;
+        assert_eq!(
+            short.sample(Point::new(0.5, 0.5), 1.0).alpha,
+            0.0,
+            "data too short"
+        );
+    }
+}
@@ -14,13 +14,38 @@
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 #![warn(unused_crate_dependencies)]
 
+// Only used by the serialization tests.
+#[cfg(all(test, not(feature = "serde")))]
+use serde_json as _;
+
 mod blend;
 mod blob;
 mod brush;
 mod color;
+mod color_ops;
+mod colorspace;
+mod css;
+mod css_format;
+mod css_gradient;
+mod dynamic_color;
 mod font;
 mod gradient;
+mod gradient_analysis;
+mod gradient_sample;
+#[cfg(any(feature = "png", feature = "jpeg"))]
+mod icc;
 mod image;
+mod image_compare;
+mod image_convert;
+#[cfg(any(feature = "png", feature = "jpeg"))]
+mod image_decode;
+#[cfg(feature = "png")]
+mod image_encode;
+mod image_sample;
+mod mesh;
+mod pattern;
+mod premul;
+mod ramp;
 mod style;
 
 /// Re-export of the kurbo 2D curve library.
@@ -28,9 +53,27 @@ pub use kurbo;
 
 pub use blend::{BlendMode, Compose, Mix};
 pub use blob::{Blob, WeakBlob};
-pub use brush::{Brush, BrushRef, Extend};
-pub use color::Color;
+pub use brush::{Brush, BrushRef, Extend, TransformedBrush, TransformedBrushRef};
+pub use color::{Color, ColorParseError, ColorParseErrorKind};
+pub use colorspace::{ColorSpace, HueInterpolation};
+pub use css_format::CssFormatError;
+pub use css_gradient::{GradientParseError, GradientParseErrorKind};
+pub use dynamic_color::DynamicColor;
 pub use font::Font;
-pub use gradient::{ColorStop, ColorStops, ColorStopsSource, Gradient, GradientKind};
-pub use image::{Format, Image};
+pub use gradient::{
+    ColorStop, ColorStops, ColorStopsSource, Gradient, GradientError, GradientKind,
+    StopNormalization, TransformedGradient,
+};
+pub use gradient_analysis::GradientAnalysis;
+pub use image::{Channel, ChannelType, Format, Image, ImageError, ImageQuality, ImageRect};
+pub use image_compare::ImageDiff;
+#[cfg(any(feature = "png", feature = "jpeg"))]
+pub use image_decode::DecodeError;
+#[cfg(feature = "png")]
+pub use image_encode::EncodeError;
+pub use image_sample::ImageSampler;
+pub use mesh::{MeshGradient, MeshPatch, MeshTriangle};
+pub use pattern::{Pattern, PatternContent};
+pub use premul::{PremulColor, PremulDynamicColor};
+pub use ramp::{Ramp, RampOptions};
 pub use style::{Fill, Style, StyleRef};
@@ -0,0 +1,550 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::{Blob, ColorSpace, DynamicColor, Format, HueInterpolation, Image, ImageError};
+
+use kurbo::{Affine, Point, Vec2};
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+/// Maximum number of segments along each side of a patch when triangulating.
+const MAX_SEGMENTS: u32 = 64;
+
+/// Tensor-product patch of a [mesh gradient](MeshGradient), with a color at
+/// each corner.
+///
+/// The patch is a bicubic Bézier surface defined by a 4×4 grid of control
+/// points, where `points[i][j]` is the control point `p_ij` of PDF shading
+/// type 7. The surface maps the unit square to `S(u, v) = Σ p_ij B_i(u)
+/// B_j(v)` for the cubic Bernstein polynomials `B`. The corners `p00`,
+/// `p03`, `p33` and `p30` lie on the surface and the other control points on
+/// the boundary define its four cubic edges.
+///
+/// Coons patches (PDF shading type 6 and SVG 2 mesh gradients) only have
+/// boundary curves. They are represented with interior control points
+/// derived from the boundary, see [`MeshPatch::coons`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeshPatch {
+    /// Control points, indexed by `u` then `v`.
+    pub points: [[Point; 4]; 4],
+    /// Colors at the corners `p00`, `p03`, `p33` and `p30`, in that order.
+    pub colors: [DynamicColor; 4],
+}
+
+impl MeshPatch {
+    /// Creates a new tensor-product patch from its control points and corner
+    /// colors.
+    pub fn tensor(points: [[Point; 4]; 4], colors: [impl Into<DynamicColor>; 4]) -> Self {
+        Self {
+            points,
+            colors: colors.map(Into::into),
+        }
+    }
+
+    /// Creates a new Coons patch from its boundary and corner colors.
+    ///
+    /// The twelve boundary points are in the order of PDF shading type 6,
+    /// starting at the corner `p00` and going around the patch: `p00`,
+    /// `p01`, `p02`, `p03`, `p13`, `p23`, `p33`, `p32`, `p31`, `p30`, `p20`
+    /// and `p10`. The interior control points are computed as described for
+    /// shading type 7 in the PDF specification, which makes the tensor-product
+    /// patch equal to the Coons patch.
+    pub fn coons(boundary: [Point; 12], colors: [impl Into<DynamicColor>; 4]) -> Self {
+        let [p00, p01, p02, p03, p13, p23, p33, p32, p31, p30, p20, p10] =
+            boundary.map(Point::to_vec2);
+        let interior = |corner: Vec2,
+                        adjacent: [Vec2; 2],
+                        far_edges: [Vec2; 2],
+                        near_edges: [Vec2; 2],
+                        opposite: Vec2| {
+            ((-4.0 * corner + 6.0 * (adjacent[0] + adjacent[1])
+                - 2.0 * (far_edges[0] + far_edges[1])
+                + 3.0 * (near_edges[0] + near_edges[1])
+                - opposite)
+                / 9.0)
+                .to_point()
+        };
+        let p11 = interior(p00, [p01, p10], [p03, p30], [p31, p13], p33);
+        let p12 = interior(p03, [p02, p13], [p00, p33], [p32, p10], p30);
+        let p22 = interior(p33, [p32, p23], [p30, p03], [p20, p02], p00);
+        let p21 = interior(p30, [p31, p20], [p33, p00], [p01, p23], p03);
+        let points = [
+            [p00, p01, p02, p03].map(Vec2::to_point),
+            [p10.to_point(), p11, p12, p13.to_point()],
+            [p20.to_point(), p21, p22, p23.to_point()],
+            [p30, p31, p32, p33].map(Vec2::to_point),
+        ];
+        Self::tensor(points, colors)
+    }
+
+    /// Returns the point of the surface at the parameters `u` and `v`.
+    #[must_use]
+    pub fn point(&self, u: f64, v: f64) -> Point {
+        let (bu, bv) = (bernstein(u), bernstein(v));
+        let mut sum = Vec2::ZERO;
+        for (row, wu) in self.points.iter().zip(bu) {
+            for (p, wv) in row.iter().zip(bv) {
+                sum += p.to_vec2() * (wu * wv);
+            }
+        }
+        sum.to_point()
+    }
+
+    /// Returns the color of the surface at the parameters `u` and `v`.
+    ///
+    /// The corner colors are interpolated bilinearly in the given color
+    /// space, first along `v` and then along `u`.
+    #[must_use]
+    pub fn color(
+        &self,
+        u: f32,
+        v: f32,
+        space: ColorSpace,
+        hue_interpolation: HueInterpolation,
+    ) -> DynamicColor {
+        let [c00, c03, c33, c30] = self.colors;
+        let u0 = c00.interpolate(c03, v, space, hue_interpolation);
+        let u1 = c30.interpolate(c33, v, space, hue_interpolation);
+        u0.interpolate(u1, u, space, hue_interpolation)
+    }
+
+    /// Splits the patch at `u = 0.5` and `v = 0.5` into four patches that
+    /// together cover the same surface.
+    ///
+    /// The patches are ordered by `u`, then `v`. The colors of the new
+    /// corners are interpolated in the given color space. Repeated
+    /// subdivision is useful for renderers that tessellate adaptively.
+    #[must_use]
+    pub fn subdivide(&self, space: ColorSpace, hue_interpolation: HueInterpolation) -> [Self; 4] {
+        // Split along u, then along v.
+        let columns = self.columns().map(split_cubic);
+        let halves = [false, true].map(|second| {
+            [0, 1, 2, 3].map(|i| columns.map(|(a, b)| if second { b[i] } else { a[i] }))
+        });
+        let color = |u: f32, v: f32| self.color(u, v, space, hue_interpolation);
+        let mut patches = [*self; 4];
+        for (k, half) in halves.iter().enumerate() {
+            for side in 0..2 {
+                let patch = &mut patches[k * 2 + side];
+                for (i, row) in half.iter().enumerate() {
+                    let (a, b) = split_cubic(*row);
+                    patch.points[i] = if side == 0 { a } else { b };
+                }
+                let (u0, v0) = (k as f32 * 0.5, side as f32 * 0.5);
+                patch.colors = [
+                    color(u0, v0),
+                    color(u0, v0 + 0.5),
+                    color(u0 + 0.5, v0 + 0.5),
+                    color(u0 + 0.5, v0),
+                ];
+            }
+        }
+        patches
+    }
+
+    /// Returns the control points indexed by `v` then `u`.
+    fn columns(&self) -> [[Point; 4]; 4] {
+        [0, 1, 2, 3].map(|j| self.points.map(|row| row[j]))
+    }
+
+    /// Returns the number of segments along each side needed to approximate
+    /// the patch with straight lines within `tolerance`.
+    fn segments(&self, tolerance: f64) -> u32 {
+        // The distance between a cubic and a uniform subdivision into `n`
+        // segments is at most 3/4 of the largest second difference of the
+        // control points, divided by `n^2`.
+        let diff =
+            |a: Point, b: Point, c: Point| (a.to_vec2() - 2.0 * b.to_vec2() + c.to_vec2()).hypot();
+        let mut max_diff: f64 = 0.0;
+        for curve in self.points.iter().chain(&self.columns()) {
+            for k in 0..2 {
+                max_diff = max_diff.max(diff(curve[k], curve[k + 1], curve[k + 2]));
+            }
+        }
+        let n = (0.75 * max_diff / tolerance.max(f64::MIN_POSITIVE))
+            .sqrt()
+            .ceil();
+        if n.is_finite() {
+            (n as u32).clamp(1, MAX_SEGMENTS)
+        } else {
+            MAX_SEGMENTS
+        }
+    }
+}
+
+/// Triangle with a color at each vertex, produced by
+/// [triangulating](MeshGradient::triangles) a mesh gradient.
+///
+/// The colors are in the interpolation space of the mesh gradient, and
+/// should be interpolated linearly across the triangle in that space, as in
+/// PDF shading type 4.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MeshTriangle {
+    /// Vertices of the triangle.
+    pub points: [Point; 3],
+    /// Colors at the vertices.
+    pub colors: [DynamicColor; 3],
+}
+
+/// Gradient defined by a mesh of patches, as in PDF shading types 6 and 7 and
+/// SVG 2 mesh gradients.
+///
+/// Patches are painted in order, so later patches cover earlier ones where
+/// they overlap. Points that are not covered by any patch are transparent.
+/// Renderers without native support for mesh gradients can
+/// [triangulate](Self::triangles) them, or [render](Self::render) them to an
+/// image on the CPU.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeshGradient {
+    /// Patches of the mesh.
+    pub patches: Vec<MeshPatch>,
+    /// Color space in which the corner colors are interpolated.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub interpolation_space: ColorSpace,
+    /// Method for interpolating hues when the interpolation space is polar.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hue_interpolation: HueInterpolation,
+}
+
+impl MeshGradient {
+    /// Creates a new mesh gradient from the given patches.
+    pub fn new(patches: impl IntoIterator<Item = MeshPatch>) -> Self {
+        Self {
+            patches: patches.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Builder method for setting the color space in which the corner colors
+    /// are interpolated.
+    #[must_use]
+    pub fn with_interpolation_space(mut self, space: ColorSpace) -> Self {
+        self.interpolation_space = space;
+        self
+    }
+
+    /// Builder method for setting the method for interpolating hues.
+    #[must_use]
+    pub fn with_hue_interpolation(mut self, method: HueInterpolation) -> Self {
+        self.hue_interpolation = method;
+        self
+    }
+
+    /// Returns the mesh gradient with the alpha component of every corner
+    /// color multiplied by the specified factor.
+    #[must_use]
+    pub fn with_alpha_factor(mut self, alpha: f32) -> Self {
+        for patch in &mut self.patches {
+            patch.colors = patch.colors.map(|c| c.with_alpha_factor(alpha));
+        }
+        self
+    }
+
+    /// Returns the mesh gradient with the transform applied to every control
+    /// point.
+    ///
+    /// Unlike other gradients, this is exact for every affine transform.
+    #[must_use]
+    pub fn transformed(&self, transform: Affine) -> Self {
+        let mut mesh = self.clone();
+        for patch in &mut mesh.patches {
+            patch.points = patch.points.map(|row| row.map(|p| transform * p));
+        }
+        mesh
+    }
+
+    /// Approximates the patches with triangles.
+    ///
+    /// Each patch is subdivided uniformly, with enough segments that the
+    /// edges of the triangles are within `tolerance` of the surface (up to a
+    /// limit of 64 segments per side). The colors at the vertices are exact,
+    /// while the colors inside of the triangles are a linear approximation
+    /// of the bilinear interpolation of the patch. Triangles are ordered
+    /// by patch, and then by increasing `u` and `v`, so painting them in
+    /// order matches the painting order of the mesh.
+    #[must_use]
+    pub fn triangles(&self, tolerance: f64) -> Vec<MeshTriangle> {
+        let (space, hue) = (self.interpolation_space, self.hue_interpolation);
+        let mut triangles = Vec::new();
+        let mut row = Vec::new();
+        let mut prev_row: Vec<(Point, DynamicColor)> = Vec::new();
+        for patch in &self.patches {
+            let n = patch.segments(tolerance);
+            let step = 1.0 / f64::from(n);
+            for i in 0..=n {
+                let u = f64::from(i) * step;
+                row.clear();
+                row.extend((0..=n).map(|j| {
+                    let v = f64::from(j) * step;
+                    (
+                        patch.point(u, v),
+                        patch.color(u as f32, v as f32, space, hue),
+                    )
+                }));
+                if i > 0 {
+                    for j in 0..n as usize {
+                        let (a, b) = (prev_row[j], prev_row[j + 1]);
+                        let (c, d) = (row[j], row[j + 1]);
+                        triangles.push(MeshTriangle {
+                            points: [a.0, b.0, c.0],
+                            colors: [a.1, b.1, c.1],
+                        });
+                        triangles.push(MeshTriangle {
+                            points: [b.0, d.0, c.0],
+                            colors: [b.1, d.1, c.1],
+                        });
+                    }
+                }
+                core::mem::swap(&mut row, &mut prev_row);
+            }
+        }
+        triangles
+    }
+
+    /// Renders the mesh gradient to an image of the given size.
+    ///
+    /// This is a reference implementation intended for testing renderers and
+    /// for CPU fallback rendering. The `transform` maps the mesh to pixel
+    /// coordinates, and each pixel takes the color at its center of the last
+    /// triangle that covers it (see [`triangles`](Self::triangles)), without
+    /// anti-aliasing or compositing. The result has the
+    /// [`Rgba32F`](Format::Rgba32F) format, with uncovered pixels left
+    /// transparent.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ImageError::SizeOverflow`] if the size of the image in bytes
+    /// overflows.
+    pub fn render(&self, width: u32, height: u32, transform: Affine) -> Result<Image, ImageError> {
+        const BYTES_PER_PIXEL: usize = 16;
+        let len = Format::Rgba32F
+            .size_in_bytes(width, height)
+            .ok_or(ImageError::SizeOverflow)?;
+        let mut data = alloc::vec![0_u8; len];
+        let (space, hue) = (self.interpolation_space, self.hue_interpolation);
+        // Flattening within a quarter of a pixel is not visible.
+        for triangle in self.transformed(transform).triangles(0.25) {
+            let [p0, p1, p2] = triangle.points;
+            let [c0, c1, c2] = triangle.colors;
+            let area = (p1 - p0).cross(p2 - p0);
+            if area.abs() <= f64::EPSILON || !area.is_finite() {
+                continue;
+            }
+            let min = |a: f64, b: f64, c: f64| a.min(b).min(c);
+            let max = |a: f64, b: f64, c: f64| a.max(b).max(c);
+            let x0 = min(p0.x, p1.x, p2.x).floor().max(0.0) as u32;
+            let y0 = min(p0.y, p1.y, p2.y).floor().max(0.0) as u32;
+            let x1 = (max(p0.x, p1.x, p2.x).ceil().max(0.0) as u32).min(width);
+            let y1 = (max(p0.y, p1.y, p2.y).ceil().max(0.0) as u32).min(height);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let q = Point::new(f64::from(x) + 0.5, f64::from(y) + 0.5);
+                    let w0 = (p1 - q).cross(p2 - q) / area;
+                    let w1 = (p2 - q).cross(p0 - q) / area;
+                    let w2 = 1.0 - w0 - w1;
+                    const EPSILON: f64 = -1e-9;
+                    if w0 < EPSILON || w1 < EPSILON || w2 < EPSILON {
+                        continue;
+                    }
+                    let c01 = if w0 + w1 > 0.0 {
+                        c0.interpolate(c1, (w1 / (w0 + w1)) as f32, space, hue)
+                    } else {
+                        c0
+                    };
+                    let color = c01
+                        .interpolate(c2, w2 as f32, space, hue)
+                        .convert(ColorSpace::LinearSrgb);
+                    let [r, g, b] = color.components;
+                    let offset = (y as usize * width as usize + x as usize) * BYTES_PER_PIXEL;
+                    let pixel = &mut data[offset..offset + BYTES_PER_PIXEL];
+                    for (bytes, c) in pixel.chunks_exact_mut(4).zip([r, g, b, color.alpha]) {
+                        bytes.copy_from_slice(&c.to_le_bytes());
+                    }
+                }
+            }
+        }
+        Image::new(Blob::from(data), Format::Rgba32F, width, height)
+    }
+}
+
+/// Returns the cubic Bernstein polynomials at `t`.
+fn bernstein(t: f64) -> [f64; 4] {
+    let s = 1.0 - t;
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+/// Splits a cubic Bézier curve at `t = 0.5`.
+fn split_cubic(p: [Point; 4]) -> ([Point; 4], [Point; 4]) {
+    let ab = p[0].midpoint(p[1]);
+    let bc = p[1].midpoint(p[2]);
+    let cd = p[2].midpoint(p[3]);
+    let abc = ab.midpoint(bc);
+    let bcd = bc.midpoint(cd);
+    let mid = abc.midpoint(bcd);
+    ([p[0], ab, abc, mid], [mid, bcd, cd, p[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bernstein, MeshGradient, MeshPatch};
+    use crate::{Color, ColorSpace, DynamicColor, Format, HueInterpolation};
+    use kurbo::{Affine, Point, Vec2};
+
+    const SPACE: ColorSpace = ColorSpace::Oklab;
+    const HUE: HueInterpolation = HueInterpolation::Shorter;
+
+    /// Parameters on a grid over the unit square, including its edges.
+    fn grid() -> impl Iterator<Item = (f64, f64)> {
+        (0..=4).flat_map(|i| (0..=4).map(move |j| (f64::from(i) / 4.0, f64::from(j) / 4.0)))
+    }
+
+    /// A curved patch with distinct corner colors.
+    fn patch() -> MeshPatch {
+        let points = [0.0, 1.0, 2.0, 3.0].map(|i: f64| {
+            [0.0, 1.0, 2.0, 3.0].map(|j: f64| {
+                Point::new(
+                    10.0 * i + (i * j).sin() * 3.0,
+                    10.0 * j + (i - j).cos() * 2.0 + i * i,
+                )
+            })
+        });
+        MeshPatch::tensor(points, [Color::RED, Color::LIME, Color::BLUE, Color::WHITE])
+    }
+
+    #[track_caller]
+    fn assert_point(actual: Point, expected: Point, message: &str) {
+        assert!(
+            (actual - expected).hypot() < 1e-9,
+            "{message}: {actual:?} != {expected:?}"
+        );
+    }
+
+    #[track_caller]
+    fn assert_color(actual: DynamicColor, expected: DynamicColor, message: &str) {
+        let (a, e) = (actual.convert(SPACE), expected.convert(SPACE));
+        let close = a
+            .components
+            .iter()
+            .chain([&a.alpha])
+            .zip(e.components.iter().chain([&e.alpha]))
+            .all(|(a, e)| (a - e).abs() < 1e-5);
+        assert!(close, "{message}: {actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn corners() {
+        let patch = patch();
+        let p = patch.points;
+        for ((u, v), point, color) in [
+            ((0.0, 0.0), p[0][0], patch.colors[0]),
+            ((0.0, 1.0), p[0][3], patch.colors[1]),
+            ((1.0, 1.0), p[3][3], patch.colors[2]),
+            ((1.0, 0.0), p[3][0], patch.colors[3]),
+        ] {
+            let message = format!("corner at ({u}, {v})");
+            assert_point(patch.point(u, v), point, &message);
+            assert_color(patch.color(u as f32, v as f32, SPACE, HUE), color, &message);
+        }
+    }
+
+    #[test]
+    fn coons_equals_tensor() {
+        // The boundary of a tensor patch, in the order of `MeshPatch::coons`.
+        let boundary = |p: [[Point; 4]; 4]| {
+            [
+                p[0][0], p[0][1], p[0][2], p[0][3], p[1][3], p[2][3], p[3][3], p[3][2], p[3][1],
+                p[3][0], p[2][0], p[1][0],
+            ]
+        };
+        // A translational surface `F(u) + G(v)` is a Coons patch, so the
+        // interior control points are recovered from its boundary.
+        let f = [(0.0, 0.0), (4.0, 7.0), (12.0, -3.0), (20.0, 2.0)];
+        let g = [(0.0, 0.0), (-2.0, 6.0), (5.0, 14.0), (1.0, 20.0)];
+        let points = f.map(|(fx, fy)| g.map(|(gx, gy)| Point::new(fx + gx, fy + gy)));
+        let tensor = MeshPatch::tensor(points, [Color::RED; 4]);
+        let coons = MeshPatch::coons(boundary(points), [Color::RED; 4]);
+        for (i, j) in [(1, 1), (1, 2), (2, 1), (2, 2)] {
+            assert_point(
+                coons.points[i][j],
+                tensor.points[i][j],
+                &format!("interior point p{i}{j}"),
+            );
+        }
+
+        // Any Coons patch matches the Coons surface of its boundary curves.
+        let curved = patch();
+        let curved_coons = MeshPatch::coons(boundary(curved.points), curved.colors);
+        let p = curved.points.map(|row| row.map(Point::to_vec2));
+        let cubic = |c: [Vec2; 4], t: f64| {
+            let w = bernstein(t);
+            c[0] * w[0] + c[1] * w[1] + c[2] * w[2] + c[3] * w[3]
+        };
+        for (u, v) in grid() {
+            let ruled_v = cubic(p[0], v) * (1.0 - u) + cubic(p[3], v) * u;
+            let ruled_u = cubic([p[0][0], p[1][0], p[2][0], p[3][0]], u) * (1.0 - v)
+                + cubic([p[0][3], p[1][3], p[2][3], p[3][3]], u) * v;
+            let bilinear = p[0][0] * ((1.0 - u) * (1.0 - v))
+                + p[0][3] * ((1.0 - u) * v)
+                + p[3][3] * (u * v)
+                + p[3][0] * (u * (1.0 - v));
+            let expected = (ruled_v + ruled_u - bilinear).to_point();
+            assert_point(
+                curved_coons.point(u, v),
+                expected,
+                &format!("at ({u}, {v})"),
+            );
+        }
+    }
+
+    #[test]
+    fn subdivide() {
+        let parent = patch();
+        let children = parent.subdivide(SPACE, HUE);
+        for (k, child) in children.iter().enumerate() {
+            let (u0, v0) = ((k / 2) as f64 * 0.5, (k % 2) as f64 * 0.5);
+            for (s, t) in grid() {
+                let (u, v) = (u0 + s * 0.5, v0 + t * 0.5);
+                let message = format!("child {k} at ({s}, {t})");
+                assert_point(child.point(s, t), parent.point(u, v), &message);
+                assert_color(
+                    child.color(s as f32, t as f32, SPACE, HUE),
+                    parent.color(u as f32, v as f32, SPACE, HUE),
+                    &message,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn render_flat_patch() {
+        let points = [0.0, 1.0, 2.0, 3.0]
+            .map(|i: f64| [0.0, 1.0, 2.0, 3.0].map(|j: f64| Point::new(2.0 * i, 2.0 * j)));
+        let color = Color::rgba8(255, 128, 0, 128);
+        let mesh = MeshGradient::new([MeshPatch::tensor(points, [color; 4])])
+            .with_interpolation_space(SPACE);
+        let image = mesh.render(8, 8, Affine::translate((1.0, 1.0))).unwrap();
+        assert_eq!(image.format, Format::Rgba32F, "format");
+        let expected = DynamicColor::from(color).convert(ColorSpace::LinearSrgb);
+        for (i, pixel) in image.data.data().chunks_exact(16).enumerate() {
+            let (x, y) = (i % 8, i / 8);
+            let rgba: [f32; 4] = core::array::from_fn(|c| {
+                let b = &pixel[c * 4..c * 4 + 4];
+                f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+            });
+            if (1..7).contains(&x) && (1..7).contains(&y) {
+                let actual =
+                    DynamicColor::new(ColorSpace::LinearSrgb, [rgba[0], rgba[1], rgba[2]], rgba[3]);
+                assert_color(actual, expected, &format!("covered pixel ({x}, {y})"));
+            } else {
+                assert_eq!(rgba, [0.0; 4], "uncovered pixel ({x}, {y})");
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+use super::Extend;
+
+use kurbo::{Affine, Rect, Vec2};
+
+use core::any::Any;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+extern crate alloc;
+use alloc::sync::Arc;
+
+/// Shared handle to the content of a [pattern](Pattern) tile, such as a
+/// recorded drawing.
+///
+/// The content is defined by the consumer of the pattern, and is opaque to
+/// this crate. Cloning the handle is cheap, and handles compare equal when
+/// they were cloned from the same handle.
+///
+/// With the `serde` feature, only the identifier is serialized. A
+/// deserialized handle has no content, so consumers that serialize patterns
+/// are expected to resolve the content from the [identifier](Self::id).
+#[derive(Clone)]
+pub struct PatternContent {
+    data: Option<Arc<dyn Any + Send + Sync>>,
+    id: u64,
+}
+
+static ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl PatternContent {
+    /// Creates a new handle for the given content and generates a unique
+    /// identifier.
+    pub fn new<T: Any + Send + Sync>(content: T) -> Self {
+        Self::from_arc(Arc::new(content))
+    }
+
+    /// Creates a new handle for the given shared content and generates a
+    /// unique identifier.
+    pub fn from_arc(content: Arc<dyn Any + Send + Sync>) -> Self {
+        Self {
+            data: Some(content),
+            id: ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Creates a new handle from the given content and identifier.
+    ///
+    /// As with [`Blob::from_raw_parts`](crate::Blob::from_raw_parts), using
+    /// identifiers that are not uniquely associated with the content can
+    /// lead to inconsistencies.
+    pub fn from_raw_parts(content: Option<Arc<dyn Any + Send + Sync>>, id: u64) -> Self {
+        Self { data: content, id }
+    }
+
+    /// Returns a reference to the content if it has the type `T`, or `None`
+    /// if it has another type or the handle has no content.
+    #[must_use]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.data.as_deref()?.downcast_ref()
+    }
+
+    /// Returns the shared content, or `None` if the handle was deserialized.
+    #[must_use]
+    pub fn data(&self) -> Option<&Arc<dyn Any + Send + Sync>> {
+        self.data.as_ref()
+    }
+
+    /// Returns the unique identifier associated with the content.
+    #[must_use]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl fmt::Debug for PatternContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PatternContent")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for PatternContent {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PatternContent {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        serializer.serialize_u64(self.id)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Deserialize<'de> for PatternContent {
+    fn deserialize<D>(des: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        let id = u64::deserialize(des)?;
+        Ok(Self::from_raw_parts(None, id))
+    }
+}
+
+/// Brush that repeats vector content over the plane, as in SVG `<pattern>`
+/// elements and PDF tiling patterns.
+///
+/// The content of a tile is drawn in pattern space and clipped to the
+/// [tile rectangle](Self::tile). Tiles are placed [`step`](Self::step) apart
+/// according to the extend modes: [`Extend::Repeat`] repeats the tile,
+/// [`Extend::Reflect`] mirrors every other tile, [`Extend::Pad`] extends the
+/// edges of a single tile and [`Extend::Decal`] draws a single tile. The
+/// [transform](Self::transform) maps pattern space to the space of the
+/// brush.
+///
+/// With the `serde` feature, the content is serialized as its
+/// [identifier](PatternContent::id). A deserialized pattern has the tile,
+/// step, transform, extend modes and alpha of the original, and content with
+/// the same identifier but without [data](PatternContent::data), which the
+/// consumer must replace before drawing the pattern.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pattern {
+    /// Content of a tile.
+    pub content: PatternContent,
+    /// Rectangle of a tile in pattern space.
+    pub tile: Rect,
+    /// Horizontal and vertical distance between the origins of adjacent
+    /// tiles in pattern space.
+    pub step: Vec2,
+    /// Transform from pattern space to brush space.
+    pub transform: Affine,
+    /// Extend mode in the horizontal direction.
+    pub x_extend: Extend,
+    /// Extend mode in the vertical direction.
+    pub y_extend: Extend,
+    /// An additional alpha multiplier to use with the pattern.
+    pub alpha: f32,
+}
+
+impl Pattern {
+    /// Creates a new pattern that repeats the given content over adjacent
+    /// copies of the tile rectangle.
+    #[must_use]
+    pub fn new(content: PatternContent, tile: Rect) -> Self {
+        Self {
+            content,
+            tile,
+            step: Vec2::new(tile.width(), tile.height()),
+            transform: Affine::IDENTITY,
+            x_extend: Extend::Repeat,
+            y_extend: Extend::Repeat,
+            alpha: 1.0,
+        }
+    }
+
+    /// Builder method for setting the distance between adjacent tiles.
+    #[must_use]
+    pub fn with_step(mut self, step: Vec2) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Builder method for setting the transform from pattern space to brush
+    /// space.
+    #[must_use]
+    pub fn with_transform(mut self, transform: Affine) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Builder method for setting the pattern [extend mode](Extend) in both
+    /// directions.
+    #[must_use]
+    pub fn with_extend(mut self, mode: Extend) -> Self {
+        self.x_extend = mode;
+        self.y_extend = mode;
+        self
+    }
+
+    /// Builder method for setting the pattern [extend mode](Extend) in the
+    /// horizontal direction.
+    #[must_use]
+    pub fn with_x_extend(mut self, mode: Extend) -> Self {
+        self.x_extend = mode;
+        self
+    }
+
+    /// Builder method for setting the pattern [extend mode](Extend) in the
+    /// vertical direction.
+    #[must_use]
+    pub fn with_y_extend(mut self, mode: Extend) -> Self {
+        self.y_extend = mode;
+        self
+    }
+
+    /// Builder method for setting the pattern alpha.
+    #[must_use]
+    pub fn with_alpha_factor(mut self, alpha: f32) -> Self {
+        self.alpha *= alpha;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    use super::Pattern;
+    use super::PatternContent;
+    #[cfg(feature = "serde")]
+    use kurbo::Rect;
+
+    extern crate alloc;
+    use alloc::string::String;
+    use alloc::sync::Arc;
+
+    #[test]
+    fn identity() {
+        let content = PatternContent::new(String::from("tile"));
+        assert_eq!(content.clone(), content, "clones are equal");
+        assert_eq!(content.clone().id(), content.id(), "clones share the id");
+        let same_value = PatternContent::new(String::from("tile"));
+        assert_ne!(same_value, content, "equal content with another identity");
+        let shared: Arc<dyn core::any::Any + Send + Sync> = Arc::new(1_u32);
+        assert_ne!(
+            PatternContent::from_arc(shared.clone()),
+            PatternContent::from_arc(shared),
+            "each handle created from an Arc has a new identity"
+        );
+        let raw = PatternContent::from_raw_parts(None, content.id());
+        assert_eq!(raw, content, "identity is the id");
+    }
+
+    #[test]
+    fn downcast() {
+        let content = PatternContent::new(String::from("tile"));
+        assert_eq!(
+            content.downcast_ref::<String>().map(String::as_str),
+            Some("tile"),
+            "content type"
+        );
+        assert_eq!(content.downcast_ref::<u32>(), None, "other type");
+        assert!(content.data().is_some(), "data");
+        let empty = PatternContent::from_raw_parts(None, content.id());
+        assert_eq!(empty.downcast_ref::<String>(), None, "no content");
+        assert!(empty.data().is_none(), "no data");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let pattern = Pattern::new(
+            PatternContent::new(String::from("tile")),
+            Rect::new(0.0, 0.0, 4.0, 2.0),
+        )
+        .with_alpha_factor(0.5);
+        let json = serde_json::to_string(&pattern).unwrap();
+        let deserialized: Pattern = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, pattern, "same content identifier");
+        assert_eq!(deserialized.tile, pattern.tile, "tile");
+        assert_eq!(deserialized.alpha, 0.5, "alpha");
+        assert!(
+            deserialized.content.data().is_none(),
+            "deserialized content has no data"
+        );
+    }
+}
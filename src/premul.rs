@@ -0,0 +1,336 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::{Color, ColorSpace, DynamicColor};
+
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+
+/// 32-bit RGBA color with premultiplied alpha.
+///
+/// The color channels have already been multiplied by alpha, so no channel of
+/// a [valid](Self::is_valid) color exceeds the alpha channel. This is the form
+/// used by most compositors and GPU blending. Convert from a straight alpha
+/// [`Color`] with [`From`], and back with [`to_color`](Self::to_color) or the
+/// checked [`try_to_color`](Self::try_to_color).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PremulColor {
+    /// Red component, premultiplied by alpha.
+    pub r: u8,
+    /// Green component, premultiplied by alpha.
+    pub g: u8,
+    /// Blue component, premultiplied by alpha.
+    pub b: u8,
+    /// Alpha component.
+    pub a: u8,
+}
+
+impl PremulColor {
+    /// Fully transparent color.
+    pub const TRANSPARENT: Self = Self::rgba8(0, 0, 0, 0);
+
+    /// Creates a new color from components that are already premultiplied.
+    #[must_use]
+    pub const fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Returns true if no color channel exceeds the alpha channel.
+    ///
+    /// Colors produced by premultiplying a [`Color`] are always valid.
+    #[must_use]
+    pub const fn is_valid(self) -> bool {
+        self.r <= self.a && self.g <= self.a && self.b <= self.a
+    }
+
+    /// Converts to a straight alpha color.
+    ///
+    /// A color with zero alpha converts to [`Color::TRANSPARENT`], since the
+    /// original channels cannot be recovered. Channels of invalid colors
+    /// saturate at `255`.
+    #[must_use]
+    pub fn to_color(self) -> Color {
+        if self.a == 0 {
+            return Color::TRANSPARENT;
+        }
+        let a = u32::from(self.a);
+        let unpremul = |c: u8| ((u32::from(c) * 255 + a / 2) / a).min(255) as u8;
+        Color::rgba8(unpremul(self.r), unpremul(self.g), unpremul(self.b), self.a)
+    }
+
+    /// Converts to a straight alpha color, returning `None` if the color is
+    /// not [valid](Self::is_valid).
+    #[must_use]
+    pub fn try_to_color(self) -> Option<Color> {
+        self.is_valid().then(|| self.to_color())
+    }
+
+    /// Returns the color as a packed value, with red in the most significant
+    /// byte and alpha in the least significant byte.
+    #[must_use]
+    pub const fn to_u32(self) -> u32 {
+        (self.r as u32) << 24 | (self.g as u32) << 16 | (self.b as u32) << 8 | self.a as u32
+    }
+}
+
+impl From<Color> for PremulColor {
+    fn from(color: Color) -> Self {
+        let a = u32::from(color.a);
+        // Rounds `c * a / 255` to the nearest integer. Ties cannot occur as
+        // 255 is odd.
+        let premul = |c: u8| ((u32::from(c) * a + 127) / 255) as u8;
+        Self::rgba8(premul(color.r), premul(color.g), premul(color.b), color.a)
+    }
+}
+
+impl From<PremulColor> for Color {
+    fn from(color: PremulColor) -> Self {
+        color.to_color()
+    }
+}
+
+/// Floating point color with premultiplied alpha and an associated
+/// [color space](ColorSpace).
+///
+/// This is the premultiplied form of [`DynamicColor`]. As in CSS Color Module
+/// Level 4, the hue component of polar color spaces is not premultiplied.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PremulDynamicColor {
+    /// Color space of the components.
+    pub space: ColorSpace,
+    /// Components of the color, premultiplied by alpha except for hue.
+    pub components: [f32; 3],
+    /// Alpha component, in the range `0.0` to `1.0`.
+    pub alpha: f32,
+}
+
+impl Hash for PremulDynamicColor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.space.hash(state);
+        self.components.map(f32::to_bits).hash(state);
+        self.alpha.to_bits().hash(state);
+    }
+}
+
+// Override PartialEq to use to_bits for the components to match with the Hash impl
+impl PartialEq for PremulDynamicColor {
+    fn eq(&self, other: &Self) -> bool {
+        self.space == other.space
+            && self.components.map(f32::to_bits) == other.components.map(f32::to_bits)
+            && self.alpha.to_bits() == other.alpha.to_bits()
+    }
+}
+
+impl Eq for PremulDynamicColor {}
+
+// Order by the bit patterns as well, so that the order agrees with PartialEq
+impl PartialOrd for PremulDynamicColor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let key = |c: &Self| (c.space, c.components.map(f32::to_bits), c.alpha.to_bits());
+        Some(key(self).cmp(&key(other)))
+    }
+}
+
+impl Default for PremulDynamicColor {
+    fn default() -> Self {
+        DynamicColor::default().into()
+    }
+}
+
+impl PremulDynamicColor {
+    /// Creates a new color from premultiplied components in the given color
+    /// space and alpha.
+    #[must_use]
+    pub const fn new(space: ColorSpace, components: [f32; 3], alpha: f32) -> Self {
+        Self {
+            space,
+            components,
+            alpha,
+        }
+    }
+
+    /// Converts to a straight alpha color.
+    ///
+    /// A color with zero alpha converts to a transparent color with zero
+    /// components (other than hue), since the original components cannot be
+    /// recovered.
+    #[must_use]
+    pub fn to_dynamic(self) -> DynamicColor {
+        let hue = self.space.hue_index();
+        let inv_alpha = if self.alpha == 0.0 {
+            0.0
+        } else {
+            1.0 / self.alpha
+        };
+        let mut components = self.components;
+        for (i, c) in components.iter_mut().enumerate() {
+            if Some(i) != hue {
+                *c *= inv_alpha;
+            }
+        }
+        DynamicColor::new(self.space, components, self.alpha)
+    }
+
+    /// Converts the color to the given color space.
+    ///
+    /// Premultiplied components cannot be converted directly, so this
+    /// unpremultiplies, converts and premultiplies again.
+    #[must_use]
+    pub fn convert(self, space: ColorSpace) -> Self {
+        if self.space == space {
+            return self;
+        }
+        self.to_dynamic().convert(space).into()
+    }
+
+    /// Converts to an 8-bit premultiplied sRGB color.
+    ///
+    /// Each component is clamped to the range `0.0` to `alpha` before
+    /// quantization, so the result is always [valid](PremulColor::is_valid).
+    #[must_use]
+    pub fn to_premul_color(self) -> PremulColor {
+        let srgb = self.convert(ColorSpace::Srgb);
+        let a = srgb.alpha.clamp(0.0, 1.0);
+        let a8 = (a * 255.0).round() as u8;
+        let channel = |c: f32| ((c.clamp(0.0, a) * 255.0).round() as u8).min(a8);
+        let [r, g, b] = srgb.components;
+        PremulColor::rgba8(channel(r), channel(g), channel(b), a8)
+    }
+}
+
+impl From<DynamicColor> for PremulDynamicColor {
+    fn from(color: DynamicColor) -> Self {
+        let hue = color.space.hue_index();
+        let mut components = color.components;
+        for (i, c) in components.iter_mut().enumerate() {
+            if Some(i) != hue {
+                *c *= color.alpha;
+            }
+        }
+        Self::new(color.space, components, color.alpha)
+    }
+}
+
+impl From<PremulDynamicColor> for DynamicColor {
+    fn from(color: PremulDynamicColor) -> Self {
+        color.to_dynamic()
+    }
+}
+
+impl From<PremulColor> for PremulDynamicColor {
+    fn from(color: PremulColor) -> Self {
+        let scale = 1.0 / 255.0;
+        Self::new(
+            ColorSpace::Srgb,
+            [
+                color.r as f32 * scale,
+                color.g as f32 * scale,
+                color.b as f32 * scale,
+            ],
+            color.a as f32 * scale,
+        )
+    }
+}
+
+impl From<Color> for PremulDynamicColor {
+    fn from(color: Color) -> Self {
+        DynamicColor::from(color).into()
+    }
+}
+
+impl From<PremulColor> for DynamicColor {
+    fn from(color: PremulColor) -> Self {
+        PremulDynamicColor::from(color).to_dynamic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PremulColor, PremulDynamicColor};
+    use crate::{Color, ColorSpace, DynamicColor};
+
+    #[test]
+    fn round_trip() {
+        for a in 0..=255 {
+            for c in 0..=a {
+                let premul = PremulColor::rgba8(c, c / 2, a - c, a);
+                assert!(premul.is_valid(), "{premul:?} is valid");
+                let color = premul.to_color();
+                assert_eq!(PremulColor::from(color), premul, "{premul:?} via {color:?}");
+            }
+        }
+        for c in 0..=255 {
+            let opaque = Color::rgba8(c, 255 - c, c / 3, 255);
+            assert_eq!(
+                PremulColor::from(opaque).to_color(),
+                opaque,
+                "opaque colors are unchanged"
+            );
+        }
+        let color = DynamicColor::new(ColorSpace::Oklch, [0.7, 0.1, 200.0], 0.5);
+        let premul = PremulDynamicColor::from(color);
+        assert_eq!(
+            premul.components,
+            [0.35, 0.05, 200.0],
+            "hue is not premultiplied"
+        );
+        assert_eq!(premul.to_dynamic(), color, "dynamic round trip");
+    }
+
+    #[test]
+    fn zero_alpha() {
+        let transparent = PremulColor::from(Color::rgba8(200, 100, 50, 0));
+        assert_eq!(transparent, PremulColor::TRANSPARENT, "channels are zero");
+        assert_eq!(
+            transparent.to_color(),
+            Color::TRANSPARENT,
+            "unpremultiplied"
+        );
+        let invalid = PremulColor::rgba8(10, 20, 30, 0);
+        assert_eq!(invalid.to_color(), Color::TRANSPARENT, "invalid color");
+        assert_eq!(invalid.try_to_color(), None, "checked conversion");
+
+        let color = DynamicColor::new(ColorSpace::Oklch, [0.7, 0.1, 200.0], 0.0);
+        let premul = PremulDynamicColor::from(color);
+        assert_eq!(premul.components, [0.0, 0.0, 200.0], "premultiplied");
+        assert_eq!(
+            premul.to_dynamic().components,
+            [0.0, 0.0, 200.0],
+            "unpremultiplied without division by zero"
+        );
+        assert_eq!(
+            premul.to_premul_color(),
+            PremulColor::TRANSPARENT,
+            "quantized"
+        );
+    }
+
+    #[test]
+    fn premul_u32() {
+        // The floating point implementation that `to_premul_u32` replaced.
+        fn reference(color: Color) -> u32 {
+            let a = f64::from(color.a) * (1.0 / 255.0);
+            let r = (f64::from(color.r) * a).round() as u32;
+            let g = (f64::from(color.g) * a).round() as u32;
+            let b = (f64::from(color.b) * a).round() as u32;
+            (r << 24) | (g << 16) | (b << 8) | u32::from(color.a)
+        }
+        for a in 0..=255 {
+            for c in 0..=255 {
+                let color = Color::rgba8(c, 255 - c, c / 2, a);
+                assert_eq!(color.to_premul_u32(), reference(color), "{color:?}");
+                assert_eq!(
+                    PremulDynamicColor::from(color).to_premul_color(),
+                    PremulColor::from(color),
+                    "{color:?} through floating point"
+                );
+            }
+        }
+    }
+}
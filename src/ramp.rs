@@ -0,0 +1,384 @@
+// Copyright 2024 the Peniko Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Baking of gradient color stops into lookup tables.
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[allow(unused_imports)]
+use kurbo::common::FloatFuncs as _;
+
+use super::{ColorSpace, Gradient, PremulDynamicColor};
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+/// Options for baking the color stops of a [gradient](Gradient) into a
+/// [ramp](Ramp).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RampOptions {
+    /// Number of entries in the ramp.
+    pub size: u32,
+    /// Color space of the entries.
+    pub space: ColorSpace,
+    /// Whether the entries have premultiplied alpha.
+    pub premultiplied: bool,
+    /// Whether to dither 8-bit entries to reduce banding.
+    pub dither: bool,
+}
+
+impl Default for RampOptions {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl RampOptions {
+    /// Creates new options for a ramp with the given number of entries, with
+    /// straight alpha sRGB entries and no dithering.
+    #[must_use]
+    pub const fn new(size: u32) -> Self {
+        Self {
+            size,
+            space: ColorSpace::Srgb,
+            premultiplied: false,
+            dither: false,
+        }
+    }
+
+    /// Builder method for setting the color space of the entries.
+    ///
+    /// This is usually [`ColorSpace::Srgb`] for 8-bit sRGB textures and
+    /// [`ColorSpace::LinearSrgb`] for linear textures. It does not affect
+    /// the space in which the stops are interpolated.
+    #[must_use]
+    pub fn with_space(mut self, space: ColorSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Builder method for setting whether the entries have premultiplied
+    /// alpha.
+    #[must_use]
+    pub fn with_premultiplied(mut self, premultiplied: bool) -> Self {
+        self.premultiplied = premultiplied;
+        self
+    }
+
+    /// Builder method for setting whether 8-bit entries are dithered.
+    #[must_use]
+    pub fn with_dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+}
+
+/// Lookup table of RGBA colors baked from the color stops of a
+/// [gradient](Gradient).
+#[derive(Clone, PartialEq, Debug)]
+pub struct Ramp<T> {
+    /// Entries of the ramp.
+    pub data: Vec<T>,
+    /// Hash of the options and entries of the ramp.
+    ///
+    /// The hash is stable across runs and platforms, so ramps with the same
+    /// content can be deduplicated, for example in a shared texture atlas.
+    pub hash: u64,
+}
+
+impl Gradient {
+    /// Bakes the color stops into a ramp of 8-bit RGBA entries.
+    ///
+    /// Entry `i` of `n` holds the color at the parameter `i / (n - 1)`, so
+    /// the first and last entries hold the colors at `0.0` and `1.0` (a ramp
+    /// with a single entry holds the color at `0.5`). The stops are
+    /// evaluated as in [`sample_stops`](Self::sample_stops), with
+    /// interpolation in the interpolation space of the gradient, but the
+    /// extend mode is not applied. The colors are then converted to the
+    /// color space of the options, clamped to the range `0.0` to `1.0`, and
+    /// optionally premultiplied.
+    ///
+    /// Dithering adds deterministic triangular noise of up to one step to
+    /// the color channels before quantization, which hides banding in
+    /// ramps that are stretched over many pixels.
+    #[must_use]
+    pub fn bake_ramp_rgba8(&self, options: RampOptions) -> Ramp<[u8; 4]> {
+        let data: Vec<_> = (0..options.size)
+            .map(|i| {
+                let [r, g, b, a] = self.ramp_entry(i, options);
+                let a = a.clamp(0.0, 1.0);
+                let a8 = (a * 255.0).round() as u8;
+                let max = if options.premultiplied { a } else { 1.0 };
+                let noise = if options.dither { dither_noise(i) } else { 0.0 };
+                let channel = |c: f32| {
+                    let c = (c.clamp(0.0, max) * 255.0 + noise)
+                        .round()
+                        .clamp(0.0, 255.0) as u8;
+                    if options.premultiplied {
+                        c.min(a8)
+                    } else {
+                        c
+                    }
+                };
+                [channel(r), channel(g), channel(b), a8]
+            })
+            .collect();
+        let mut hasher = Fnv1a::new(options, 8);
+        for entry in &data {
+            hasher.write(entry);
+        }
+        Ramp {
+            data,
+            hash: hasher.0,
+        }
+    }
+
+    /// Bakes the color stops into a ramp of 32-bit floating point RGBA
+    /// entries.
+    ///
+    /// This works like [`bake_ramp_rgba8`](Self::bake_ramp_rgba8), except
+    /// that the components are not clamped, which preserves colors outside of
+    /// the gamut of the color space, and that dithering has no effect.
+    #[must_use]
+    pub fn bake_ramp_f32(&self, options: RampOptions) -> Ramp<[f32; 4]> {
+        let data: Vec<_> = (0..options.size)
+            .map(|i| self.ramp_entry(i, options))
+            .collect();
+        let mut hasher = Fnv1a::new(options, 32);
+        for entry in &data {
+            for c in entry {
+                hasher.write(&c.to_le_bytes());
+            }
+        }
+        Ramp {
+            data,
+            hash: hasher.0,
+        }
+    }
+
+    /// Returns the components and alpha of entry `i` of a ramp.
+    fn ramp_entry(&self, i: u32, options: RampOptions) -> [f32; 4] {
+        let t = match options.size {
+            0 | 1 => 0.5,
+            size => (f64::from(i) / f64::from(size - 1)) as f32,
+        };
+        let color = self.interpolate_stops(t).convert(options.space);
+        let [r, g, b] = if options.premultiplied {
+            PremulDynamicColor::from(color).components
+        } else {
+            color.components
+        };
+        [r, g, b, color.alpha]
+    }
+}
+
+/// Returns triangular noise in the range `-1.0` to `1.0` for the index.
+fn dither_noise(i: u32) -> f32 {
+    let uniform = |mut x: u32| {
+        // A 32-bit integer hash with good avalanche behavior.
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x7feb_352d);
+        x ^= x >> 15;
+        x = x.wrapping_mul(0x846c_a68b);
+        x ^= x >> 16;
+        (x >> 8) as f32 / (1 << 24) as f32
+    };
+    uniform(i.wrapping_mul(2)) + uniform(i.wrapping_mul(2) + 1) - 1.0
+}
+
+/// The 64-bit FNV-1a hash, which unlike the hashers of the standard library
+/// is stable across runs and platforms.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new(options: RampOptions, bits: u8) -> Self {
+        let mut hasher = Self(0xcbf2_9ce4_8422_2325);
+        hasher.write(&options.size.to_le_bytes());
+        hasher.write(&[
+            bits,
+            space_tag(options.space),
+            options.premultiplied.into(),
+            options.dither.into(),
+        ]);
+        hasher
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Returns a tag for the color space in ramp hashes, which must not change
+/// when variants are added to [`ColorSpace`].
+fn space_tag(space: ColorSpace) -> u8 {
+    match space {
+        ColorSpace::Srgb => 0,
+        ColorSpace::LinearSrgb => 1,
+        ColorSpace::DisplayP3 => 2,
+        ColorSpace::A98Rgb => 3,
+        ColorSpace::ProphotoRgb => 4,
+        ColorSpace::Rec2020 => 5,
+        ColorSpace::Lab => 6,
+        ColorSpace::Lch => 7,
+        ColorSpace::Hsl => 8,
+        ColorSpace::Hwb => 9,
+        ColorSpace::Oklab => 10,
+        ColorSpace::Oklch => 11,
+        ColorSpace::XyzD50 => 12,
+        ColorSpace::XyzD65 => 13,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dither_noise, RampOptions};
+    use crate::{Color, ColorSpace, Gradient};
+
+    extern crate alloc;
+    use alloc::vec::Vec;
+
+    fn gradient() -> Gradient {
+        Gradient::new_linear((0.0, 0.0), (1.0, 0.0)).with_stops([
+            (0.0, Color::rgba8(255, 0, 0, 255)),
+            (0.5, Color::rgba8(0, 128, 255, 64)),
+            (1.0, Color::rgba8(20, 20, 20, 200)),
+        ])
+    }
+
+    #[test]
+    fn hash_stability() {
+        // The hashes must not change between releases or platforms. Entries
+        // that are interpolated in sRGB without conversion involve no
+        // transcendental functions, so their exact values are portable too.
+        let options = RampOptions::new(16);
+        assert_eq!(
+            gradient().bake_ramp_rgba8(options).hash,
+            0x13cf_8f2c_a04a_1918,
+            "8-bit ramp"
+        );
+        assert_eq!(
+            gradient().bake_ramp_f32(options).hash,
+            0x15a7_4d56_bc32_36cd,
+            "f32 ramp"
+        );
+        let premultiplied = options.with_premultiplied(true).with_dither(true);
+        assert_eq!(
+            gradient().bake_ramp_rgba8(premultiplied).hash,
+            0x2040_ad22_89c9_0bd4,
+            "premultiplied and dithered 8-bit ramp"
+        );
+        let spaces = [
+            ColorSpace::Srgb,
+            ColorSpace::LinearSrgb,
+            ColorSpace::DisplayP3,
+            ColorSpace::A98Rgb,
+            ColorSpace::ProphotoRgb,
+            ColorSpace::Rec2020,
+            ColorSpace::Lab,
+            ColorSpace::Lch,
+            ColorSpace::Hsl,
+            ColorSpace::Hwb,
+            ColorSpace::Oklab,
+            ColorSpace::Oklch,
+            ColorSpace::XyzD50,
+            ColorSpace::XyzD65,
+        ];
+        // An empty ramp only hashes the options.
+        let hashes: Vec<u64> = spaces
+            .iter()
+            .map(|&space| {
+                gradient()
+                    .bake_ramp_rgba8(RampOptions::new(0).with_space(space))
+                    .hash
+            })
+            .collect();
+        assert_eq!(
+            hashes,
+            [
+                0xa89d_5874_d9c8_1e4d,
+                0x9ff3_dd74_d4e0_1fa2,
+                0x974a_6274_cff8_20f7,
+                0x8ea0_e774_cb10_224c,
+                0x85f7_6c74_c628_23a1,
+                0x7d4d_f174_c140_24f6,
+                0x74a4_7674_bc58_264b,
+                0x6bfa_fb74_b770_27a0,
+                0xede9_3075_0108_13a5,
+                0xe53f_b574_fc20_14fa,
+                0xdc96_3a74_f738_164f,
+                0xd3ec_bf74_f250_17a4,
+                0xcb43_4474_ed68_18f9,
+                0xc299_c974_e880_1a4e,
+            ],
+            "color space tags"
+        );
+    }
+
+    #[test]
+    fn rgba8_matches_f32() {
+        for options in [
+            RampOptions::new(64),
+            RampOptions::new(64).with_premultiplied(true),
+            RampOptions::new(64).with_space(ColorSpace::LinearSrgb),
+            RampOptions::new(1),
+        ] {
+            let gradient = gradient().with_interpolation_space(ColorSpace::Oklab);
+            let rgba8 = gradient.bake_ramp_rgba8(options);
+            let f32 = gradient.bake_ramp_f32(options);
+            assert_eq!(rgba8.data.len(), f32.data.len(), "{options:?}: size");
+            for (i, (quantized, exact)) in rgba8.data.iter().zip(&f32.data).enumerate() {
+                for (q, e) in quantized.iter().zip(exact) {
+                    let e = e.clamp(0.0, 1.0) * 255.0;
+                    assert!(
+                        (f32::from(*q) - e).abs() <= 0.5 + 1e-3,
+                        "{options:?}: entry {i} is {quantized:?}, expected {exact:?}"
+                    );
+                }
+                if options.premultiplied {
+                    assert!(
+                        quantized[..3].iter().all(|c| *c <= quantized[3]),
+                        "{options:?}: entry {i} is valid"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dither() {
+        // A long, shallow ramp, where every entry falls between two steps.
+        let shallow = Gradient::new_linear((0.0, 0.0), (1.0, 0.0))
+            .with_stops([Color::rgb8(0, 0, 0), Color::rgb8(16, 16, 16)]);
+        let options = RampOptions::new(1024);
+        let plain = shallow.bake_ramp_rgba8(options);
+        let dithered = shallow.bake_ramp_rgba8(options.with_dither(true));
+        assert_eq!(
+            dithered,
+            shallow.bake_ramp_rgba8(options.with_dither(true)),
+            "deterministic"
+        );
+        assert_ne!(plain.hash, dithered.hash, "dithering is hashed");
+        let mut changed = 0;
+        for (i, (entry, dithered_entry)) in plain.data.iter().zip(&dithered.data).enumerate() {
+            assert_eq!(entry[3], dithered_entry[3], "alpha is not dithered at {i}");
+            for (p, d) in entry.iter().zip(dithered_entry) {
+                assert!(p.abs_diff(*d) <= 1, "entry {i}: {p} and {d}");
+                changed += usize::from(p != d);
+            }
+        }
+        assert!(changed > 0, "dithering changes some entries");
+        for i in (0..10_000).chain([u32::MAX - 1, u32::MAX]) {
+            let noise = dither_noise(i);
+            assert!((-1.0..=1.0).contains(&noise), "noise {noise} at {i}");
+        }
+        let f32 = shallow.bake_ramp_f32(options);
+        assert_eq!(
+            f32.data,
+            shallow.bake_ramp_f32(options.with_dither(true)).data,
+            "f32 ramps are not dithered"
+        );
+    }
+}